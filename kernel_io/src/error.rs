@@ -49,6 +49,23 @@ pub enum Error {
     UnexpectedLevelInfoLength {
         length: usize,
     },
+    #[display(fmt = "InvalidPreimagePage")]
+    InvalidPreimagePage {
+        size: usize,
+    },
+    #[display(fmt = "InvalidRevealedMessage")]
+    InvalidRevealedMessage {
+        size: usize,
+    },
+    #[display(fmt = "UnexpectedTransferLength")]
+    UnexpectedTransferLength {
+        length: usize,
+    },
+    #[display(fmt = "RevealLimitExceeded")]
+    RevealLimitExceeded {
+        max_pages: usize,
+        max_size: usize,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -74,6 +91,7 @@ macro_rules! impl_from_error {
 
 impl_from_error!(&str);
 impl_from_error!(tezos_core::Error);
+impl_from_error!(tezos_michelson::Error);
 impl_from_error!(layered_store::Error);
 impl_from_error!(tezos_smart_rollup_host::runtime::RuntimeError);
 
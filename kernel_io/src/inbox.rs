@@ -2,7 +2,8 @@
 //
 // SPDX-License-Identifier: MIT
 
-use tezos_core::types::encoded::{BlockHash, Encoded};
+use tezos_core::types::encoded::{BlockHash, ContractAddress, Encoded, ImplicitAddress};
+use tezos_michelson::micheline::Micheline;
 use tezos_smart_rollup_core::{smart_rollup_core::ReadInputMessageInfo, SmartRollupCore};
 use tezos_smart_rollup_host::runtime::RuntimeError;
 
@@ -34,6 +35,48 @@ impl TryFrom<&[u8]> for LevelInfo {
     }
 }
 
+/// Size of the raw smart rollup address
+pub const ROLLUP_ADDRESS_SIZE: usize = 20;
+const CONTRACT_HASH_SIZE: usize = 20;
+const PUBLIC_KEY_HASH_SIZE: usize = 21;
+
+/// Internal message sent to a smart rollup by an L1 contract
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    /// Call parameter (e.g. a ticket) in the binary Micheline format
+    pub payload: Micheline,
+    /// L1 contract that made the call
+    pub sender: ContractAddress,
+    /// L1 account that signed the operation
+    pub source: ImplicitAddress,
+    /// Raw address of the rollup, the inbox is shared between all the rollups
+    pub destination: [u8; ROLLUP_ADDRESS_SIZE],
+}
+
+impl TryFrom<&[u8]> for Transfer {
+    type Error = Error;
+
+    /// Fixed-size fields go after the payload, so they are read from the end
+    fn try_from(value: &[u8]) -> Result<Self> {
+        let addresses_size = CONTRACT_HASH_SIZE + PUBLIC_KEY_HASH_SIZE + ROLLUP_ADDRESS_SIZE;
+        if value.len() <= addresses_size {
+            return Err(Error::UnexpectedTransferLength {
+                length: value.len(),
+            });
+        }
+
+        let (payload, addresses) = value.split_at(value.len() - addresses_size);
+        let (sender, rest) = addresses.split_at(CONTRACT_HASH_SIZE);
+        let (source, destination) = rest.split_at(PUBLIC_KEY_HASH_SIZE);
+        Ok(Self {
+            payload: Micheline::from_bytes(payload)?,
+            sender: ContractAddress::from_bytes(sender)?,
+            source: ImplicitAddress::from_bytes(source)?,
+            destination: destination.try_into().expect("Rollup address size"),
+        })
+    }
+}
+
 pub trait PayloadType: Sized {
     fn from_external_message(message: &[u8]) -> Result<Self>;
 }
@@ -42,6 +85,7 @@ pub enum InboxMessage<Payload: PayloadType> {
     BeginBlock(i32),
     EndBlock(i32),
     LevelInfo(LevelInfo),
+    Transfer(Transfer),
     Payload(Payload),
     NoMoreData,
     Foreign(i32),
@@ -106,6 +150,7 @@ pub fn read_inbox<Host: SmartRollupCore, Payload: PayloadType>(
 ) -> Result<InboxMessage<Payload>> {
     match read_input(host) {
         Ok(Some(message)) => match message.as_ref() {
+            [b'\x00', b'\x00', transfer @ ..] => Ok(InboxMessage::Transfer(transfer.try_into()?)),
             b"\x00\x01" => Ok(InboxMessage::BeginBlock(message.level)),
             b"\x00\x02" => Ok(InboxMessage::EndBlock(message.level)),
             [b'\x00', b'\x03', info @ ..] => Ok(InboxMessage::LevelInfo(info.try_into()?)),
@@ -122,3 +167,36 @@ pub fn read_inbox<Host: SmartRollupCore, Payload: PayloadType>(
         Err(err) => Err(err.into()),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_transfer() -> Result<()> {
+        // Unit payload, KT1 sender, tagged tz1 source and the rollup address
+        let mut message: Vec<u8> = vec![0x03, 0x0b];
+        message.extend(1u8..=20);
+        message.push(0x00);
+        message.extend(21u8..=40);
+        message.extend(41u8..=60);
+
+        let transfer = Transfer::try_from(message.as_slice())?;
+        assert_eq!(Micheline::from_bytes(&[0x03, 0x0b])?, transfer.payload);
+        assert_eq!(
+            "KT18g6ejmStajqDwZZ5ZwTfu1ZKzhYq5RboW",
+            transfer.sender.value()
+        );
+        assert_eq!(
+            "tz1MZXM16S6QwnmNn2mvMdzx9xt9LeBgwvh6",
+            transfer.source.value()
+        );
+        assert_eq!(41u8, transfer.destination[0]);
+
+        assert!(matches!(
+            Transfer::try_from(&message[2..]),
+            Err(Error::UnexpectedTransferLength { length: 61 })
+        ));
+        Ok(())
+    }
+}
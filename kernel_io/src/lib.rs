@@ -4,6 +4,8 @@
 
 pub mod error;
 pub mod inbox;
+pub mod outbox;
+pub mod reveal;
 pub mod store;
 
 pub use crate::error::{Error, Result};
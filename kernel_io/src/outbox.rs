@@ -0,0 +1,80 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use tezos_core::types::encoded::{ContractAddress, Encoded};
+use tezos_michelson::micheline::Micheline;
+use tezos_smart_rollup_core::SmartRollupCore;
+use tezos_smart_rollup_host::runtime::Runtime;
+
+use crate::error::Result;
+
+const ATOMIC_TRANSACTION_BATCH_TAG: u8 = 0;
+const ORIGINATED_CONTRACT_TAG: u8 = 1;
+
+/// Smart contract call performed on L1 once the outbox message is executed
+#[derive(Debug, Clone)]
+pub struct OutboxTransaction {
+    pub parameters: Micheline,
+    pub destination: ContractAddress,
+    pub entrypoint: String,
+}
+
+impl OutboxTransaction {
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<()> {
+        bytes.extend_from_slice(self.parameters.to_bytes()?.as_slice());
+        // Contract ID: tag, contract hash and padding
+        bytes.push(ORIGINATED_CONTRACT_TAG);
+        bytes.extend_from_slice(self.destination.to_bytes()?.as_slice());
+        bytes.push(0);
+        bytes.extend_from_slice(&(self.entrypoint.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(self.entrypoint.as_bytes());
+        Ok(())
+    }
+}
+
+/// Encodes an atomic batch of transactions (the outbox message kind supported by Nairobi):
+/// either all the transactions are applied on L1 or none of them
+pub fn encode_atomic_batch(transactions: &[OutboxTransaction]) -> Result<Vec<u8>> {
+    let mut batch: Vec<u8> = Vec::new();
+    for transaction in transactions.iter() {
+        transaction.write(&mut batch)?;
+    }
+
+    let mut message = vec![ATOMIC_TRANSACTION_BATCH_TAG];
+    message.extend_from_slice(&(batch.len() as u32).to_be_bytes());
+    message.extend_from_slice(batch.as_slice());
+    Ok(message)
+}
+
+/// Writes the message to the outbox of the current level, fails if the outbox is full
+pub fn write_outbox<Host: SmartRollupCore>(
+    host: &mut Host,
+    transactions: &[OutboxTransaction],
+) -> Result<()> {
+    let message = encode_atomic_batch(transactions)?;
+    Runtime::write_output(host, message.as_slice())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_atomic_batch() -> Result<()> {
+        let transaction = OutboxTransaction {
+            parameters: Micheline::from_bytes(&[0x03, 0x0b])?,
+            destination: "KT18g6ejmStajqDwZZ5ZwTfu1ZKzhYq5RboW".try_into()?,
+            entrypoint: "withdraw".into(),
+        };
+        let message = encode_atomic_batch(&[transaction])?;
+
+        let mut expected: Vec<u8> = vec![0x00, 0x00, 0x00, 0x00, 0x24, 0x03, 0x0b, 0x01];
+        expected.extend(1u8..=20);
+        expected.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x08]);
+        expected.extend_from_slice(b"withdraw");
+        assert_eq!(expected, message);
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use tezos_smart_rollup_core::{SmartRollupCore, PREIMAGE_HASH_SIZE};
+use tezos_smart_rollup_host::runtime::Runtime;
+
+use crate::error::{Error, Result};
+
+pub const MAX_PAGE_SIZE: usize = 4096;
+pub const PAGE_HEADER_SIZE: usize = 5;
+
+/// Maximum number of pages revealed per root hash, including the pages of hashes
+pub const MAX_REVEAL_PAGES: usize = 256;
+/// Maximum size of the data revealed per root hash
+pub const MAX_REVEAL_SIZE: usize = 512 * 1024;
+
+const CONTENTS_PAGE_TAG: u8 = 0;
+const HASHES_PAGE_TAG: u8 = 1;
+
+/// Reveals data split into a Merkle tree of preimage pages (DAC format):
+/// every page starts with a tag (0 — contents, 1 — child hashes) followed by
+/// the 4-byte big-endian payload length. Leaves are concatenated in order.
+/// The tree is bounded by `MAX_REVEAL_PAGES` and `MAX_REVEAL_SIZE`, so that a single root hash
+/// cannot exhaust the ticks of a run.
+pub fn reveal_data<Host: SmartRollupCore>(
+    host: &Host,
+    root_hash: &[u8; PREIMAGE_HASH_SIZE],
+) -> Result<Vec<u8>> {
+    let mut data: Vec<u8> = Vec::new();
+    let mut buffer = [0u8; MAX_PAGE_SIZE];
+    let mut pending: Vec<[u8; PREIMAGE_HASH_SIZE]> = vec![*root_hash];
+    let mut pages: usize = 0;
+
+    while let Some(hash) = pending.pop() {
+        pages += 1;
+        if pages > MAX_REVEAL_PAGES {
+            return Err(Error::RevealLimitExceeded {
+                max_pages: MAX_REVEAL_PAGES,
+                max_size: MAX_REVEAL_SIZE,
+            });
+        }
+
+        let size = Runtime::reveal_preimage(host, &hash, &mut buffer)?;
+        if size < PAGE_HEADER_SIZE {
+            return Err(Error::InvalidPreimagePage { size });
+        }
+
+        let length = u32::from_be_bytes([buffer[1], buffer[2], buffer[3], buffer[4]]) as usize;
+        let payload = match buffer[PAGE_HEADER_SIZE..size].get(..length) {
+            Some(payload) => payload,
+            None => return Err(Error::InvalidPreimagePage { size }),
+        };
+
+        match buffer[0] {
+            CONTENTS_PAGE_TAG => {
+                if data.len() + payload.len() > MAX_REVEAL_SIZE {
+                    return Err(Error::RevealLimitExceeded {
+                        max_pages: MAX_REVEAL_PAGES,
+                        max_size: MAX_REVEAL_SIZE,
+                    });
+                }
+                data.extend_from_slice(payload)
+            }
+            HASHES_PAGE_TAG => {
+                if length % PREIMAGE_HASH_SIZE != 0 {
+                    return Err(Error::InvalidPreimagePage { size });
+                }
+                // Stack is LIFO, so push children in reverse to keep the order
+                for child in payload.chunks(PREIMAGE_HASH_SIZE).rev() {
+                    pending.push(child.try_into().expect("Preimage hash size"));
+                }
+            }
+            _ => return Err(Error::InvalidPreimagePage { size }),
+        }
+    }
+
+    Ok(data)
+}
+
+/// Splits data into a list of messages, each one prefixed with its 4-byte big-endian length
+pub fn split_messages(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut messages: Vec<Vec<u8>> = Vec::new();
+    let mut rest = data;
+
+    while !rest.is_empty() {
+        if rest.len() < 4 {
+            return Err(Error::InvalidRevealedMessage { size: rest.len() });
+        }
+        let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        match rest[4..].get(..length) {
+            Some(message) => messages.push(message.to_vec()),
+            None => return Err(Error::InvalidRevealedMessage { size: rest.len() }),
+        }
+        rest = &rest[4 + length..];
+    }
+
+    Ok(messages)
+}
+
+/// Reveals a list of messages, each one prefixed with its 4-byte big-endian length
+pub fn reveal_messages<Host: SmartRollupCore>(
+    host: &Host,
+    root_hash: &[u8; PREIMAGE_HASH_SIZE],
+) -> Result<Vec<Vec<u8>>> {
+    let data = reveal_data(host, root_hash)?;
+    split_messages(data.as_slice())
+}
+
+#[cfg(test)]
+mod test {
+    use tezos_smart_rollup_mock::MockHost;
+
+    use super::*;
+    use crate::Result;
+
+    fn make_page(tag: u8, payload: &[u8]) -> Vec<u8> {
+        let mut page = vec![tag];
+        page.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        page.extend_from_slice(payload);
+        page
+    }
+
+    #[test]
+    fn test_reveal_single_page() -> Result<()> {
+        let mut host = MockHost::default();
+        let root_hash = *host
+            .set_preimage(make_page(CONTENTS_PAGE_TAG, b"deadbeef"))
+            .as_ref();
+
+        assert_eq!(b"deadbeef".to_vec(), reveal_data(&host, &root_hash)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reveal_hash_tree() -> Result<()> {
+        let mut host = MockHost::default();
+
+        let mut hashes: Vec<u8> = Vec::new();
+        for chunk in [b"dead".as_slice(), b"beef".as_slice(), b"cafe".as_slice()] {
+            let hash = host.set_preimage(make_page(CONTENTS_PAGE_TAG, chunk));
+            hashes.extend_from_slice(hash.as_ref());
+        }
+        let root_hash = *host
+            .set_preimage(make_page(HASHES_PAGE_TAG, hashes.as_slice()))
+            .as_ref();
+
+        assert_eq!(b"deadbeefcafe".to_vec(), reveal_data(&host, &root_hash)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reveal_messages() -> Result<()> {
+        let mut host = MockHost::default();
+        let mut payload: Vec<u8> = Vec::new();
+        for message in [b"dead".as_slice(), b"beefcafe".as_slice()] {
+            payload.extend_from_slice(&(message.len() as u32).to_be_bytes());
+            payload.extend_from_slice(message);
+        }
+        let root_hash = *host
+            .set_preimage(make_page(CONTENTS_PAGE_TAG, payload.as_slice()))
+            .as_ref();
+
+        assert_eq!(
+            vec![b"dead".to_vec(), b"beefcafe".to_vec()],
+            reveal_messages(&host, &root_hash)?
+        );
+
+        let root_hash = *host
+            .set_preimage(make_page(CONTENTS_PAGE_TAG, &payload[..10]))
+            .as_ref();
+        assert!(reveal_messages(&host, &root_hash).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reveal_limits() {
+        let mut host = MockHost::default();
+        let leaf = host.set_preimage(make_page(CONTENTS_PAGE_TAG, b"deadbeef"));
+        let node = host.set_preimage(make_page(HASHES_PAGE_TAG, &leaf.as_ref().repeat(100)));
+        let root_hash = *host
+            .set_preimage(make_page(HASHES_PAGE_TAG, &node.as_ref().repeat(3)))
+            .as_ref();
+
+        assert!(matches!(
+            reveal_data(&host, &root_hash),
+            Err(Error::RevealLimitExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_reveal_invalid_page() {
+        let mut host = MockHost::default();
+        let root_hash = *host.set_preimage(vec![7u8, 0, 0, 0, 0]).as_ref();
+
+        assert!(reveal_data(&host, &root_hash).is_err());
+    }
+}
@@ -47,9 +47,11 @@ impl<'rt, Host: SmartRollupCore> KernelBackend<'rt, Host> {
                     )
                     .map_err(err_into)?;
             } else {
-                self.host
-                    .store_delete(&str_to_path!(&key))
-                    .map_err(err_into)?;
+                // The key might have been created and deleted within the same run
+                match self.host.store_delete(&str_to_path!(&key)) {
+                    Ok(()) | Err(RuntimeError::PathNotFound) => {}
+                    Err(err) => return Err(err_into(err)),
+                }
             }
         }
         Ok(())
@@ -142,9 +144,14 @@ impl<'rt, Host: SmartRollupCore> StoreBackend for KernelBackend<'rt, Host> {
     }
 
     fn delete(&mut self, key: &str) -> Result<()> {
-        self.host
+        // Keys persisted in earlier runs and not written in this one have no temporary copy
+        match self
+            .host
             .store_delete(&str_to_path!([TMP_PREFIX, key].concat().as_str()))
-            .map_err(err_into)?;
+        {
+            Ok(()) | Err(RuntimeError::PathNotFound) => {}
+            Err(err) => return Err(err_into(err)),
+        }
         self.saved_state.insert(key.into(), false);
         Ok(())
     }
@@ -184,6 +191,40 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_delete_persisted_key() -> Result<()> {
+        let mut host = MockHost::default();
+        let mut store: KernelBackend<MockHost> = KernelBackend::new(&mut host);
+
+        store.write("/test", b"deadbeef")?;
+        store.persist()?;
+        store.clear();
+
+        // Next run deletes the key without writing it first
+        store.delete("/test")?;
+        assert!(!store.has("/test")?);
+        store.persist()?;
+        store.clear();
+
+        assert!(!store.has("/test")?);
+        assert_eq!(None, store.read("/test")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_key_created_in_same_run() -> Result<()> {
+        let mut host = MockHost::default();
+        let mut store: KernelBackend<MockHost> = KernelBackend::new(&mut host);
+
+        store.write("/test", b"deadbeef")?;
+        store.delete("/test")?;
+        store.persist()?;
+        store.clear();
+
+        assert!(!store.has("/test")?);
+        Ok(())
+    }
+
     #[test]
     fn store_read_all_above_max_file_chunk_size() -> Result<()> {
         // The value read is formed of 3 chunks, two of the max chunk value and
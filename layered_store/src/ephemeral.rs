@@ -60,7 +60,9 @@ pub trait EphemeralCopy {
 
 impl EphemeralCopy for LayeredStore<EphemeralBackend> {
     fn spawn(&self) -> Self {
-        Self::new(self.as_ref().spawn())
+        let mut store = Self::new(self.as_ref().spawn());
+        store.set_merkle_filter(self.merkle_filter());
+        store
     }
 }
 
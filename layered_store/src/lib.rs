@@ -4,6 +4,7 @@
 
 pub mod ephemeral;
 pub mod error;
+pub mod merkle;
 pub mod store;
 pub mod types;
 
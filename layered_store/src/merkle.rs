@@ -0,0 +1,467 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+//! Compact sparse Merkle tree over hashed keys.
+//!
+//! Leaves are positioned by the bits of `blake2b(key)`, subtrees containing a single leaf
+//! are collapsed into that leaf, so the depth grows logarithmically with the number of keys.
+//! Nodes are stored in the same backend under `MERKLE_PREFIX`, addressed by their position.
+//! Every committed key is included unless the store filter says otherwise
+//! (see `LayeredStore::set_merkle_filter`): applications exclude their bookkeeping data,
+//! so that e.g. receipts written after the root is computed do not change it.
+
+use blake2::{digest::consts::U32, Blake2b, Digest};
+
+use crate::{internal_error, store::StoreBackend, Result, StoreType};
+
+pub const MERKLE_PREFIX: &str = "/merkle";
+pub const EMPTY_HASH: Hash = [0u8; 32];
+
+const LEAF_TAG: u8 = 0;
+const INTERNAL_TAG: u8 = 1;
+const MAX_DEPTH: usize = 256;
+
+pub type Hash = [u8; 32];
+
+/// Tells whether the key is hashed into the tree
+pub type MerkleFilter = fn(&str) -> bool;
+
+pub fn blake2b_256(data: &[u8]) -> Hash {
+    let mut hash = EMPTY_HASH;
+    hash.copy_from_slice(Blake2b::<U32>::digest(data).as_slice());
+    hash
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Leaf { key_hash: Hash, value_hash: Hash },
+    Internal { left: Hash, right: Hash },
+}
+
+impl Node {
+    pub fn hash(&self) -> Hash {
+        match self {
+            Node::Leaf {
+                key_hash,
+                value_hash,
+            } => blake2b_256(
+                &[
+                    [LEAF_TAG].as_slice(),
+                    key_hash.as_slice(),
+                    value_hash.as_slice(),
+                ]
+                .concat(),
+            ),
+            Node::Internal { left, right } => blake2b_256(
+                &[[INTERNAL_TAG].as_slice(), left.as_slice(), right.as_slice()].concat(),
+            ),
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != 65 {
+            return Err(internal_error!(
+                "Invalid Merkle node length: {}",
+                bytes.len()
+            ));
+        }
+        let first: Hash = bytes[1..33].try_into().unwrap();
+        let second: Hash = bytes[33..].try_into().unwrap();
+        match bytes[0] {
+            LEAF_TAG => Ok(Node::Leaf {
+                key_hash: first,
+                value_hash: second,
+            }),
+            INTERNAL_TAG => Ok(Node::Internal {
+                left: first,
+                right: second,
+            }),
+            tag => Err(internal_error!("Invalid Merkle node tag: {}", tag)),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Node::Leaf {
+                key_hash,
+                value_hash,
+            } => [
+                [LEAF_TAG].as_slice(),
+                key_hash.as_slice(),
+                value_hash.as_slice(),
+            ]
+            .concat(),
+            Node::Internal { left, right } => {
+                [[INTERNAL_TAG].as_slice(), left.as_slice(), right.as_slice()].concat()
+            }
+        }
+    }
+}
+
+fn bit(key_hash: &Hash, depth: usize) -> bool {
+    (key_hash[depth / 8] >> (7 - depth % 8)) & 1 == 1
+}
+
+pub fn node_path(depth: usize, key_hash: &Hash) -> String {
+    let mut prefix = *key_hash;
+    for i in depth..MAX_DEPTH {
+        prefix[i / 8] &= !(1 << (7 - i % 8));
+    }
+    let hex: String = prefix.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}/{}/{}", MERKLE_PREFIX, depth, hex)
+}
+
+fn read_node(backend: &impl StoreBackend, depth: usize, key_hash: &Hash) -> Result<Option<Node>> {
+    match backend.read(&node_path(depth, key_hash))? {
+        Some(bytes) => Ok(Some(Node::from_bytes(bytes.as_slice())?)),
+        None => Ok(None),
+    }
+}
+
+fn write_node(
+    backend: &mut impl StoreBackend,
+    depth: usize,
+    key_hash: &Hash,
+    node: &Node,
+) -> Result<Hash> {
+    backend.write(&node_path(depth, key_hash), node.to_bytes().as_slice())?;
+    Ok(node.hash())
+}
+
+fn internal_node(key_hash: &Hash, depth: usize, child: Hash, sibling: Hash) -> Node {
+    match bit(key_hash, depth) {
+        false => Node::Internal {
+            left: child,
+            right: sibling,
+        },
+        true => Node::Internal {
+            left: sibling,
+            right: child,
+        },
+    }
+}
+
+fn sibling_hash(node: &Node, key_hash: &Hash, depth: usize) -> Hash {
+    match (node, bit(key_hash, depth)) {
+        (Node::Internal { right, .. }, false) => *right,
+        (Node::Internal { left, .. }, true) => *left,
+        _ => EMPTY_HASH,
+    }
+}
+
+fn insert(
+    backend: &mut impl StoreBackend,
+    depth: usize,
+    key_hash: &Hash,
+    value_hash: &Hash,
+) -> Result<Hash> {
+    if depth >= MAX_DEPTH {
+        return Err(internal_error!("Merkle tree depth exceeded"));
+    }
+
+    let leaf = Node::Leaf {
+        key_hash: *key_hash,
+        value_hash: *value_hash,
+    };
+    match read_node(backend, depth, key_hash)? {
+        None => write_node(backend, depth, key_hash, &leaf),
+        Some(Node::Leaf {
+            key_hash: other, ..
+        }) if other == *key_hash => write_node(backend, depth, key_hash, &leaf),
+        Some(other_leaf @ Node::Leaf { .. }) => {
+            // Push the existing leaf one level down and retry from there
+            let other = match &other_leaf {
+                Node::Leaf { key_hash, .. } => *key_hash,
+                _ => unreachable!(),
+            };
+            write_node(backend, depth + 1, &other, &other_leaf)?;
+            let child = insert(backend, depth + 1, key_hash, value_hash)?;
+            let sibling = match bit(&other, depth) == bit(key_hash, depth) {
+                true => EMPTY_HASH,
+                false => other_leaf.hash(),
+            };
+            let node = internal_node(key_hash, depth, child, sibling);
+            write_node(backend, depth, key_hash, &node)
+        }
+        Some(node) => {
+            let child = insert(backend, depth + 1, key_hash, value_hash)?;
+            let node = internal_node(key_hash, depth, child, sibling_hash(&node, key_hash, depth));
+            write_node(backend, depth, key_hash, &node)
+        }
+    }
+}
+
+fn remove(backend: &mut impl StoreBackend, depth: usize, key_hash: &Hash) -> Result<Hash> {
+    match read_node(backend, depth, key_hash)? {
+        None => Ok(EMPTY_HASH),
+        Some(Node::Leaf {
+            key_hash: other, ..
+        }) if other == *key_hash => {
+            backend.delete(&node_path(depth, key_hash))?;
+            Ok(EMPTY_HASH)
+        }
+        Some(leaf @ Node::Leaf { .. }) => Ok(leaf.hash()),
+        Some(node) => {
+            let child = remove(backend, depth + 1, key_hash)?;
+            let sibling = sibling_hash(&node, key_hash, depth);
+
+            // Subtree with a single leaf collapses into that leaf
+            let single = match (child == EMPTY_HASH, sibling == EMPTY_HASH) {
+                (true, true) => {
+                    backend.delete(&node_path(depth, key_hash))?;
+                    return Ok(EMPTY_HASH);
+                }
+                (true, false) => {
+                    let mut sibling_key = *key_hash;
+                    sibling_key[depth / 8] ^= 1 << (7 - depth % 8);
+                    Some(sibling_key)
+                }
+                (false, true) => Some(*key_hash),
+                (false, false) => None,
+            };
+            if let Some(position) = single {
+                if let Some(leaf @ Node::Leaf { .. }) = read_node(backend, depth + 1, &position)? {
+                    backend.delete(&node_path(depth + 1, &position))?;
+                    return write_node(backend, depth, key_hash, &leaf);
+                }
+            }
+
+            let node = internal_node(key_hash, depth, child, sibling);
+            write_node(backend, depth, key_hash, &node)
+        }
+    }
+}
+
+/// Updates the tree with the new value of the key (`None` for removal)
+pub fn update(backend: &mut impl StoreBackend, key: &str, value: Option<&[u8]>) -> Result<()> {
+    let key_hash = blake2b_256(key.as_bytes());
+    match value {
+        Some(value) => insert(backend, 0, &key_hash, &blake2b_256(value))?,
+        None => remove(backend, 0, &key_hash)?,
+    };
+    Ok(())
+}
+
+/// Root hash of the tree, `EMPTY_HASH` if there are no keys
+pub fn root_hash(backend: &impl StoreBackend) -> Result<Hash> {
+    match read_node(backend, 0, &EMPTY_HASH)? {
+        Some(node) => Ok(node.hash()),
+        None => Ok(EMPTY_HASH),
+    }
+}
+
+/// Default filter, all the keys except the tree nodes
+pub fn commit_all(key: &str) -> bool {
+    !key.starts_with(MERKLE_PREFIX)
+}
+
+/// Path from the root to the position of a key.
+/// If the position is occupied by a leaf of another key, the proof also contains that leaf.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MerkleProof {
+    pub siblings: Vec<Hash>,
+    /// Key and value hashes of another leaf (proves non-inclusion)
+    pub leaf: Option<(Hash, Hash)>,
+}
+
+impl MerkleProof {
+    /// Appends the next node on the key path, returns `true` once the path is complete
+    pub fn push(&mut self, key_hash: &Hash, node: Option<Node>) -> bool {
+        match node {
+            None => true,
+            Some(Node::Leaf {
+                key_hash: other,
+                value_hash,
+            }) => {
+                if other != *key_hash {
+                    self.leaf = Some((other, value_hash));
+                }
+                true
+            }
+            Some(node) => {
+                let depth = self.siblings.len();
+                self.siblings.push(sibling_hash(&node, key_hash, depth));
+                false
+            }
+        }
+    }
+
+    /// Computes the root hash implied by the proof for the given key and value
+    /// (`None` stands for the key absence), returns `None` if the proof is malformed
+    pub fn root_hash(&self, key: &str, value: Option<&[u8]>) -> Option<Hash> {
+        let key_hash = blake2b_256(key.as_bytes());
+        let depth = self.siblings.len();
+        if depth >= MAX_DEPTH {
+            return None;
+        }
+
+        let mut hash = match (value, &self.leaf) {
+            (Some(value), None) => Node::Leaf {
+                key_hash,
+                value_hash: blake2b_256(value),
+            }
+            .hash(),
+            (None, None) => EMPTY_HASH,
+            (None, Some((other, value_hash))) => {
+                // The other leaf has to be located on the key path
+                if *other == key_hash || (0..depth).any(|d| bit(other, d) != bit(&key_hash, d)) {
+                    return None;
+                }
+                Node::Leaf {
+                    key_hash: *other,
+                    value_hash: *value_hash,
+                }
+                .hash()
+            }
+            (Some(_), Some(_)) => return None,
+        };
+
+        for (depth, sibling) in self.siblings.iter().enumerate().rev() {
+            hash = internal_node(&key_hash, depth, hash, *sibling).hash();
+        }
+        Some(hash)
+    }
+
+    pub fn verify(&self, root: &Hash, key: &str, value: Option<&[u8]>) -> bool {
+        self.root_hash(key, value) == Some(*root)
+    }
+}
+
+/// Builds an inclusion (or non-inclusion) proof for the key
+pub fn prove(backend: &impl StoreBackend, key: &str) -> Result<MerkleProof> {
+    let key_hash = blake2b_256(key.as_bytes());
+    let mut proof = MerkleProof::default();
+    loop {
+        let node = read_node(backend, proof.siblings.len(), &key_hash)?;
+        if proof.push(&key_hash, node) {
+            return Ok(proof);
+        }
+    }
+}
+
+impl StoreType for Node {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Node::from_bytes(bytes)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(Node::to_bytes(self))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ephemeral::EphemeralBackend;
+
+    #[test]
+    fn test_merkle_root() -> Result<()> {
+        let mut backend = EphemeralBackend::new();
+        assert_eq!(EMPTY_HASH, root_hash(&backend)?);
+
+        update(&mut backend, "/context/a", Some(b"1"))?;
+        let root_a = root_hash(&backend)?;
+        assert_ne!(EMPTY_HASH, root_a);
+
+        update(&mut backend, "/context/b", Some(b"2"))?;
+        update(&mut backend, "/context/c", Some(b"3"))?;
+        let root_abc = root_hash(&backend)?;
+
+        // Insertion order does not matter
+        let mut other = EphemeralBackend::new();
+        update(&mut other, "/context/c", Some(b"3"))?;
+        update(&mut other, "/context/a", Some(b"1"))?;
+        update(&mut other, "/context/b", Some(b"2"))?;
+        assert_eq!(root_abc, root_hash(&other)?);
+
+        // Values are committed
+        update(&mut other, "/context/b", Some(b"4"))?;
+        assert_ne!(root_abc, root_hash(&other)?);
+
+        // Removal restores the previous state, including the node layout
+        update(&mut backend, "/context/c", None)?;
+        update(&mut backend, "/context/b", None)?;
+        assert_eq!(root_a, root_hash(&backend)?);
+        assert_eq!(1, backend.state.len());
+
+        update(&mut backend, "/context/a", None)?;
+        assert_eq!(EMPTY_HASH, root_hash(&backend)?);
+        assert!(backend.state.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_merkle_filter() -> Result<()> {
+        let mut store = crate::EphemeralStore::default();
+        store.set("/a".into(), Some(1u64))?;
+        store.commit()?;
+        let root = store.merkle_root()?;
+        assert_ne!(EMPTY_HASH, root);
+
+        // Keys rejected by the filter are stored, but do not change the root
+        store.set_merkle_filter(|key| !key.starts_with("/receipts/"));
+        store.set("/receipts/0".into(), Some(2u64))?;
+        store.commit()?;
+        assert_eq!(root, store.merkle_root()?);
+        assert_eq!(Some(2u64), store.get("/receipts/0".into())?);
+
+        store.set("/b".into(), Some(3u64))?;
+        store.commit()?;
+        assert_ne!(root, store.merkle_root()?);
+
+        store.set("/a".into(), None::<u64>)?;
+        store.set("/b".into(), None::<u64>)?;
+        store.commit()?;
+        assert_eq!(EMPTY_HASH, store.merkle_root()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merkle_many_keys() -> Result<()> {
+        let mut backend = EphemeralBackend::new();
+        for i in 0..100 {
+            update(&mut backend, &format!("/context/{}", i), Some(&[i as u8]))?;
+        }
+        let root = root_hash(&backend)?;
+
+        let mut other = EphemeralBackend::new();
+        for i in (0..150).rev() {
+            update(&mut other, &format!("/context/{}", i), Some(&[i as u8]))?;
+        }
+        for i in 100..150 {
+            update(&mut other, &format!("/context/{}", i), None)?;
+        }
+        assert_eq!(root, root_hash(&other)?);
+        assert_eq!(backend.state, other.state);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merkle_proofs() -> Result<()> {
+        let mut backend = EphemeralBackend::new();
+        for i in 0..20 {
+            update(&mut backend, &format!("/context/{}", i), Some(&[i as u8]))?;
+        }
+        let root = root_hash(&backend)?;
+
+        // Inclusion
+        let proof = prove(&backend, "/context/7")?;
+        assert!(proof.leaf.is_none());
+        assert!(proof.verify(&root, "/context/7", Some(&[7u8])));
+        assert!(!proof.verify(&root, "/context/7", Some(&[8u8])));
+        assert!(!proof.verify(&root, "/context/7", None));
+
+        // Non-inclusion
+        for key in ["/context/20", "/context/21", "/context/22", "/context/23"] {
+            let proof = prove(&backend, key)?;
+            assert!(proof.verify(&root, key, None));
+            assert!(!proof.verify(&root, key, Some(&[0u8])));
+        }
+
+        // Empty tree
+        let empty = EphemeralBackend::new();
+        assert!(prove(&empty, "/context/0")?.verify(&EMPTY_HASH, "/context/0", None));
+        Ok(())
+    }
+}
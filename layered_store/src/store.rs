@@ -2,7 +2,7 @@
 //
 // SPDX-License-Identifier: MIT
 
-use crate::{Error, Result};
+use crate::{merkle, Error, Result};
 use std::{
     any::Any,
     collections::{HashMap, HashSet},
@@ -42,6 +42,7 @@ pub struct LayeredStore<Backend: StoreBackend> {
     backend: Backend,
     pending_state: HashMap<String, Option<(DynStoreType, StoreTypeSer)>>,
     modified_keys: HashSet<String>,
+    merkle_filter: merkle::MerkleFilter,
 }
 
 impl<Backend: StoreBackend> LayeredStore<Backend> {
@@ -50,6 +51,7 @@ impl<Backend: StoreBackend> LayeredStore<Backend> {
             backend,
             pending_state: HashMap::new(),
             modified_keys: HashSet::new(),
+            merkle_filter: merkle::commit_all,
         }
     }
 
@@ -57,6 +59,16 @@ impl<Backend: StoreBackend> LayeredStore<Backend> {
         Self::new(Backend::default())
     }
 
+    /// Selects the keys included into the Merkle tree, all the keys are included by default.
+    /// Should be set before the first commit, keys committed earlier are not re-hashed.
+    pub fn set_merkle_filter(&mut self, filter: merkle::MerkleFilter) {
+        self.merkle_filter = filter;
+    }
+
+    pub fn merkle_filter(&self) -> merkle::MerkleFilter {
+        self.merkle_filter
+    }
+
     pub fn log(&self, msg: String) {
         self.backend.log(&msg);
     }
@@ -118,15 +130,31 @@ impl<Backend: StoreBackend> LayeredStore<Backend> {
                 Some((dyn_value, ser)) => {
                     let bytes = ser(dyn_value)?;
                     self.backend.write(&key, bytes.as_slice())?;
+                    if (self.merkle_filter)(&key) {
+                        merkle::update(&mut self.backend, &key, Some(bytes.as_slice()))?;
+                    }
                 }
                 None => {
                     self.backend.delete(&key)?;
+                    if (self.merkle_filter)(&key) {
+                        merkle::update(&mut self.backend, &key, None)?;
+                    }
                 }
             };
         }
         Ok(())
     }
 
+    /// Root of the Merkle tree over all committed keys
+    pub fn merkle_root(&self) -> Result<merkle::Hash> {
+        merkle::root_hash(&self.backend)
+    }
+
+    /// Proof of the committed value of the key against the current Merkle root
+    pub fn merkle_proof(&self, key: &str) -> Result<merkle::MerkleProof> {
+        merkle::prove(&self.backend, key)
+    }
+
     pub fn rollback(&mut self) {
         for key in self.modified_keys.drain().into_iter() {
             self.pending_state.remove(&key);
@@ -52,3 +52,13 @@ impl StoreType for [u8; 32] {
         Ok(self.to_vec())
     }
 }
+
+impl StoreType for Vec<u8> {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(bytes.to_vec())
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(self.clone())
+    }
+}
@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: MIT
 
 use tezos_core::types::{
-    encoded::{ContractAddress, Encoded, OperationHash, PublicKey},
+    encoded::{ContractAddress, Encoded, ImplicitAddress, OperationHash, PublicKey, Signature},
     mutez::Mutez,
     number::Nat,
 };
@@ -28,6 +28,8 @@ macro_rules! impl_for_core {
 impl_for_core!(Encoded, PublicKey);
 impl_for_core!(Encoded, OperationHash);
 impl_for_core!(Encoded, ContractAddress);
+impl_for_core!(Encoded, ImplicitAddress);
+impl_for_core!(Encoded, Signature);
 impl_for_core!(Micheline, Micheline);
 impl_for_core!(Mutez, Mutez);
 impl_for_core!(Nat, Nat);
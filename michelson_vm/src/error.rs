@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: MIT
 
 use derive_more::{Display, Error};
+use ibig::{IBig, UBig};
 use std::{backtrace::Backtrace, fmt::Display};
 use tezos_michelson::micheline::Micheline;
 
@@ -86,8 +87,44 @@ pub enum Error {
     MutezOverflow,
     #[display(fmt = "MutezUnderflow")]
     MutezUnderflow,
+    #[display(fmt = "MutezAdditionOverflow: {} + {}", a, b)]
+    MutezAdditionOverflow {
+        a: i64,
+        b: i64,
+    },
+    #[display(fmt = "MutezSubtractionUnderflow: {} - {}", a, b)]
+    MutezSubtractionUnderflow {
+        a: i64,
+        b: i64,
+    },
+    #[display(fmt = "MutezMultiplicationOverflow: {} * {}", a, b)]
+    MutezMultiplicationOverflow {
+        a: i64,
+        b: UBig,
+    },
+    #[display(fmt = "MutezOutOfRange: {}", value)]
+    MutezOutOfRange {
+        value: IBig,
+    },
     #[display(fmt = "GeneralOverflow")]
     GeneralOverflow,
+    #[display(fmt = "StackOverflow: {}", limit)]
+    StackOverflow {
+        limit: usize,
+    },
+    #[display(fmt = "CallDepthExceeded: {}", limit)]
+    CallDepthExceeded {
+        limit: usize,
+    },
+    #[display(fmt = "StepLimitExceeded: {}", limit)]
+    StepLimitExceeded {
+        limit: usize,
+    },
+    #[display(fmt = "PackedValueTooLarge: {} (limit {})", size, limit)]
+    PackedValueTooLarge {
+        size: usize,
+        limit: usize,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
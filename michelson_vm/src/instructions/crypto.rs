@@ -54,6 +54,7 @@ impl PureInterpreter for CheckSignature {
         let signature = sig.unwrap().to_bytes()?;
         let message = crypto.blake2b(msg.unwrap().as_slice(), 32)?;
 
+        // BLS (tz4) keys are not supported: tezos_core cannot represent them
         let res = match public_key {
             PublicKey::Ed25519(public_key) => crypto.verify_ed25519(
                 message.as_slice(),
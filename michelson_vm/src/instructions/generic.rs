@@ -13,13 +13,13 @@ use tezos_michelson::michelson::Michelson;
 use crate::{
     err_mismatch,
     formatter::Formatter,
-    interpreter::PureInterpreter,
+    interpreter::{OperationScope, PureInterpreter, ScopedInterpreter},
     pop_cast,
     stack::Stack,
     trace_log,
     typechecker::{check_type_comparable, check_types_equal},
     types::{IntItem, OptionItem, StackItem},
-    Result,
+    Error, Result,
 };
 
 impl PureInterpreter for Compare {
@@ -137,14 +137,20 @@ impl PureInterpreter for Concat {
     }
 }
 
-impl PureInterpreter for Pack {
-    fn execute(&self, stack: &mut Stack) -> Result<()> {
+impl ScopedInterpreter for Pack {
+    fn execute(&self, stack: &mut Stack, scope: &OperationScope) -> Result<()> {
         let item = stack.pop()?;
         let ty = item.get_type()?;
         // TODO: check if packable
         let data = item.into_micheline(&ty)?;
         let schema: Micheline = Michelson::from(ty).into();
         let res = data.pack(Some(&schema))?;
+        if res.len() > scope.limits.max_pack_size {
+            return Err(Error::PackedValueTooLarge {
+                size: res.len(),
+                limit: scope.limits.max_pack_size,
+            });
+        }
         stack.push(StackItem::Bytes(res.into()))
     }
 }
@@ -15,7 +15,7 @@ use crate::{
     pop_cast,
     stack::Stack,
     types::{LambdaItem, StackItem},
-    Result,
+    Error, Result,
 };
 
 impl PureInterpreter for Lambda {
@@ -40,10 +40,12 @@ impl Interpreter for Exec {
         let (body, (param_type, return_type)) = pop_cast!(stack, Lambda).unwrap();
         arg.type_check(&param_type)?;
 
-        let mut inner_stack = Stack::new();
+        let mut inner_stack = stack.spawn()?;
         inner_stack.push(arg)?;
         body.execute(&mut inner_stack, scope, context)?;
-        assert_eq!(1, inner_stack.len());
+        if inner_stack.len() != 1 {
+            return Err(Error::BadReturn);
+        }
 
         let ret = inner_stack.pop()?;
         ret.type_check(&return_type)?;
@@ -137,7 +137,7 @@ impl PureInterpreter for SubMutez {
         let b = pop_cast!(stack, Mutez);
         let res = match a - b {
             Ok(res) => OptionItem::some(res.into()),
-            Err(Error::MutezUnderflow) => OptionItem::none(&types::mutez()),
+            Err(Error::MutezSubtractionUnderflow { .. }) => OptionItem::none(&types::mutez()),
             Err(err) => return Err(err),
         };
         stack.push(res.into())
@@ -38,6 +38,26 @@ pub trait InterpreterContext {
     ) -> Result<()>;
 }
 
+#[derive(Clone, Debug)]
+pub struct InterpreterLimits {
+    pub max_stack_size: usize,
+    pub max_call_depth: usize,
+    pub max_pack_size: usize,
+    /// Instructions executed per call, including nested lambda calls
+    pub max_steps: usize,
+}
+
+impl InterpreterLimits {
+    pub fn default() -> Self {
+        Self {
+            max_stack_size: 10000,
+            max_call_depth: 128,
+            max_pack_size: 50000,
+            max_steps: 1000000,
+        }
+    }
+}
+
 pub struct OperationScope {
     pub chain_id: ChainId,
     pub source: ImplicitAddress,
@@ -50,6 +70,7 @@ pub struct OperationScope {
     pub self_address: ContractAddress,
     pub self_type: Micheline,
     pub level: i32,
+    pub limits: InterpreterLimits,
 }
 
 pub trait Interpreter {
@@ -90,6 +111,7 @@ impl Interpreter for Instruction {
         context: &mut impl InterpreterContext,
     ) -> Result<()> {
         trace_enter!(self);
+        stack.step()?;
         let res = match self {
             Instruction::Sequence(seq) => return seq.execute(stack, scope, context),
             Instruction::Push(instr) => instr.execute(stack),
@@ -138,7 +160,7 @@ impl Interpreter for Instruction {
             Instruction::Size(instr) => instr.execute(stack),
             Instruction::Slice(instr) => instr.execute(stack),
             Instruction::Concat(instr) => instr.execute(stack),
-            Instruction::Pack(instr) => instr.execute(stack),
+            Instruction::Pack(instr) => instr.execute(stack, scope),
             Instruction::Unpack(instr) => instr.execute(stack),
             Instruction::Unit(instr) => instr.execute(stack),
             Instruction::Car(instr) => instr.execute(stack),
@@ -19,7 +19,7 @@ pub mod mock;
 
 pub use {
     error::{Error, Result},
-    interpreter::{InterpreterContext, OperationScope},
+    interpreter::{InterpreterContext, InterpreterLimits, OperationScope},
     script::{MichelsonScript, ScriptReturn},
     stack::Stack,
 };
@@ -11,7 +11,7 @@ use tezos_michelson::micheline::{primitive_application, Micheline};
 use tezos_michelson::michelson::types::unit;
 
 use crate::{
-    interpreter::{InterpreterContext, OperationScope},
+    interpreter::{InterpreterContext, InterpreterLimits, OperationScope},
     trace_log, Result,
 };
 
@@ -32,6 +32,7 @@ pub fn default_scope() -> OperationScope {
         self_type: unit(),
         sender: DEFAULT_IMPLICIT_ADDRESS.try_into().unwrap(),
         source: DEFAULT_IMPLICIT_ADDRESS.try_into().unwrap(),
+        limits: InterpreterLimits::default(),
     }
 }
 
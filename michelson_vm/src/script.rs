@@ -116,19 +116,29 @@ impl MichelsonScript {
         scope: &OperationScope,
         context: &mut impl InterpreterContext,
     ) -> Result<ScriptReturn> {
-        let mut stack = Stack::new();
+        let mut stack = Stack::with_limits(&scope.limits);
+        self.call_with_stack(&mut stack, scope, context)
+    }
 
-        if let Err(err) = self.call_begin(&mut stack, scope) {
+    /// Same as `call`, the stack is left to the caller so that the steps spent
+    /// can be read even if the call fails
+    pub fn call_with_stack(
+        &self,
+        stack: &mut Stack,
+        scope: &OperationScope,
+        context: &mut impl InterpreterContext,
+    ) -> Result<ScriptReturn> {
+        if let Err(err) = self.call_begin(stack, scope) {
             trace_exit!(Some(&err));
             return Err(err);
         }
 
-        if let Err(err) = self.execute(&mut stack, scope, context) {
+        if let Err(err) = self.execute(stack, scope, context) {
             trace_exit!(Some(&err));
             return Err(err);
         }
 
-        match self.call_end(&mut stack, scope, context) {
+        match self.call_end(stack, scope, context) {
             Ok(ret) => Ok(ret),
             Err(err) => {
                 trace_exit!(Some(&err));
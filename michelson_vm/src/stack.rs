@@ -2,9 +2,11 @@
 //
 // SPDX-License-Identifier: MIT
 
-use std::collections::VecDeque;
+use std::{cell::Cell, collections::VecDeque, rc::Rc};
 
-use crate::{trace_log, trace_stack, types::StackItem, Error, Result};
+use crate::{
+    interpreter::InterpreterLimits, trace_log, trace_stack, types::StackItem, Error, Result,
+};
 
 #[macro_export]
 macro_rules! pop_cast {
@@ -19,14 +21,64 @@ macro_rules! pop_cast {
 pub struct Stack {
     items: VecDeque<StackItem>,
     protected: usize,
+    depth: usize,
+    max_size: usize,
+    max_depth: usize,
+    /// Shared with the stacks spawned for nested calls
+    steps: Rc<Cell<usize>>,
+    max_steps: usize,
 }
 
 impl Stack {
     pub fn new() -> Self {
+        Self::with_limits(&InterpreterLimits::default())
+    }
+
+    pub fn with_limits(limits: &InterpreterLimits) -> Self {
         Self {
             items: VecDeque::new(),
             protected: 0,
+            depth: 0,
+            max_size: limits.max_stack_size,
+            max_depth: limits.max_call_depth,
+            steps: Rc::new(Cell::new(0)),
+            max_steps: limits.max_steps,
+        }
+    }
+
+    /// Creates an empty stack for a nested call (e.g. lambda execution) inheriting the limits
+    pub fn spawn(&self) -> Result<Self> {
+        if self.depth >= self.max_depth {
+            return Err(Error::CallDepthExceeded {
+                limit: self.max_depth,
+            });
         }
+        Ok(Self {
+            items: VecDeque::new(),
+            protected: 0,
+            depth: self.depth + 1,
+            max_size: self.max_size,
+            max_depth: self.max_depth,
+            steps: self.steps.clone(),
+            max_steps: self.max_steps,
+        })
+    }
+
+    /// Counts an executed instruction, fails once the limit is reached
+    pub fn step(&self) -> Result<()> {
+        let steps = self.steps.get();
+        if steps >= self.max_steps {
+            return Err(Error::StepLimitExceeded {
+                limit: self.max_steps,
+            });
+        }
+        self.steps.set(steps + 1);
+        Ok(())
+    }
+
+    /// Number of instructions executed so far, including nested calls
+    pub fn steps(&self) -> usize {
+        self.steps.get()
     }
 
     pub fn len(&self) -> usize {
@@ -61,6 +113,11 @@ impl Stack {
     pub fn push_at(&mut self, depth: usize, item: StackItem) -> Result<()> {
         let depth = depth + self.protected;
         trace_stack!("Insert", &item, Some(&depth));
+        if self.items.len() >= self.max_size {
+            return Err(Error::StackOverflow {
+                limit: self.max_size,
+            });
+        }
         if self.items.len() < depth {
             return Err(Error::BadStack { location: depth }.into());
         }
@@ -73,6 +130,11 @@ impl Stack {
             self.push_at(0, item)
         } else {
             trace_stack!("Push", &item, None);
+            if self.items.len() >= self.max_size {
+                return Err(Error::StackOverflow {
+                    limit: self.max_size,
+                });
+            }
             self.items.push_front(item);
             Ok(())
         }
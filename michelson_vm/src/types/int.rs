@@ -132,7 +132,9 @@ impl BitAnd<NatItem> for IntItem {
     type Output = Result<NatItem>;
 
     fn bitand(self, rhs: NatItem) -> Self::Output {
-        let a: i128 = self.0.try_into()?; // FIXME: find generic solution
-        Ok(NatItem(a & rhs.0))
+        // IBig follows two's complement semantics for bitwise operations,
+        // and the result is always non-negative because rhs is
+        let res = self.0 & IBig::from(rhs.0);
+        Ok(NatItem(res.try_into()?))
     }
 }
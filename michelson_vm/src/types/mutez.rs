@@ -23,17 +23,20 @@ use crate::{
 impl MutezItem {
     pub fn new(value: i64) -> Result<Self> {
         if value < 0 {
-            return Err(Error::MutezUnderflow.into());
+            return Err(Error::MutezOutOfRange {
+                value: value.into(),
+            });
         }
-        Ok(Self(value)) // TODO: check overflow
+        // Mutez domain is [0; 2^63 - 1] as in L1, upper bound is enforced by the type
+        Ok(Self(value))
     }
 
     pub fn from_data(data: Data) -> Result<StackItem> {
         match data {
-            Data::Int(val) => match TryInto::<i64>::try_into(val) {
-                Ok(val) => Ok(Self::new(val)?.into()),
-                Err(_) => Err(Error::MutezOverflow.into()),
-            },
+            Data::Int(val) => {
+                let value: IBig = val.into();
+                Ok(Self::try_from(value)?.into())
+            }
             _ => err_mismatch!("Int", data.format()),
         }
     }
@@ -54,9 +57,9 @@ impl TryFrom<IBig> for MutezItem {
     type Error = Error;
 
     fn try_from(value: IBig) -> Result<Self> {
-        match value.try_into() {
+        match i64::try_from(&value) {
             Ok(val) => MutezItem::new(val),
-            Err(_) => Err(Error::MutezOverflow.into()),
+            Err(_) => Err(Error::MutezOutOfRange { value }),
         }
     }
 }
@@ -65,9 +68,11 @@ impl TryFrom<UBig> for MutezItem {
     type Error = Error;
 
     fn try_from(value: UBig) -> Result<Self> {
-        match value.try_into() {
+        match i64::try_from(&value) {
             Ok(val) => MutezItem::new(val),
-            Err(_) => Err(Error::MutezOverflow.into()),
+            Err(_) => Err(Error::MutezOutOfRange {
+                value: value.into(),
+            }),
         }
     }
 }
@@ -95,7 +100,10 @@ impl Add<MutezItem> for MutezItem {
     fn add(self, rhs: MutezItem) -> Self::Output {
         match self.0.checked_add(rhs.0) {
             Some(res) => MutezItem::new(res),
-            None => Err(Error::MutezOverflow.into()),
+            None => Err(Error::MutezAdditionOverflow {
+                a: self.0,
+                b: rhs.0,
+            }),
         }
     }
 }
@@ -104,7 +112,13 @@ impl Sub<MutezItem> for MutezItem {
     type Output = Result<MutezItem>;
 
     fn sub(self, rhs: MutezItem) -> Self::Output {
-        MutezItem::new(self.0 - rhs.0)
+        match self.0.checked_sub(rhs.0) {
+            Some(res) if res >= 0 => MutezItem::new(res),
+            _ => Err(Error::MutezSubtractionUnderflow {
+                a: self.0,
+                b: rhs.0,
+            }),
+        }
     }
 }
 
@@ -112,7 +126,15 @@ impl Mul<NatItem> for MutezItem {
     type Output = Result<MutezItem>;
 
     fn mul(self, rhs: NatItem) -> Self::Output {
-        (rhs.0 * self.0).try_into()
+        // MutezItem restricts internal value to be non-negative, so casting is safe
+        let res = UBig::from(self.0 as u64) * &rhs.0;
+        match i64::try_from(res) {
+            Ok(val) => MutezItem::new(val),
+            Err(_) => Err(Error::MutezMultiplicationOverflow {
+                a: self.0,
+                b: rhs.0,
+            }),
+        }
     }
 }
 
@@ -17,6 +17,9 @@ use crate::{
     Error, Result,
 };
 
+/// L1 forbids shifting naturals by more than 256 bits
+pub const MAX_SHIFT: u16 = 256;
+
 impl NatItem {
     pub fn from_data(data: Data) -> Result<StackItem> {
         let val: UBig = match data {
@@ -36,6 +39,13 @@ impl NatItem {
     pub fn int(self) -> IntItem {
         IntItem(IBig::from(self.0))
     }
+
+    fn shift_amount(&self) -> Result<usize> {
+        if self.0 > UBig::from(MAX_SHIFT) {
+            return Err(Error::GeneralOverflow);
+        }
+        Ok(self.0.clone().try_into()?)
+    }
 }
 
 impl Display for NatItem {
@@ -102,10 +112,7 @@ impl Shl<NatItem> for NatItem {
     type Output = Result<NatItem>;
 
     fn shl(self, rhs: NatItem) -> Self::Output {
-        if rhs.0 >= 257u16.into() {
-            return Err(Error::GeneralOverflow.into());
-        }
-        let shift: usize = rhs.0.try_into()?;
+        let shift = rhs.shift_amount()?;
         Ok(NatItem(self.0 << shift))
     }
 }
@@ -114,10 +121,7 @@ impl Shr<NatItem> for NatItem {
     type Output = Result<NatItem>;
 
     fn shr(self, rhs: NatItem) -> Self::Output {
-        if rhs.0 >= 257u16.into() {
-            return Err(Error::GeneralOverflow.into());
-        }
-        let shift: usize = rhs.0.try_into()?;
+        let shift = rhs.shift_amount()?;
         Ok(NatItem(self.0 >> shift))
     }
 }
@@ -0,0 +1,205 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+// Property tests checking numeric operations against a reference model built on primitive types
+use ibig::{IBig, UBig};
+use michelson_vm::{
+    types::{IntItem, MutezItem, NatItem, OptionItem, PairItem},
+    Error, Result,
+};
+
+const ITERATIONS: usize = 10000;
+
+/// Deterministic xorshift generator, so that failures are reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn next_raw(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Values spread across all magnitudes, including edge cases
+    fn next_u64(&mut self) -> u64 {
+        match self.next_raw() % 8 {
+            0 => 0,
+            1 => u64::MAX >> (self.next_raw() % 2),
+            _ => self.next_raw() >> (self.next_raw() % 64),
+        }
+    }
+
+    fn next_mutez(&mut self) -> i64 {
+        (self.next_u64() >> 1) as i64
+    }
+}
+
+fn mutez(value: i64) -> MutezItem {
+    MutezItem::new(value).expect("Non-negative mutez")
+}
+
+fn nat(value: u128) -> NatItem {
+    NatItem::from(UBig::from(value))
+}
+
+#[test]
+fn test_mutez_add() -> Result<()> {
+    let mut rng = Rng(0xdeadbeef);
+    for _ in 0..ITERATIONS {
+        let (a, b) = (rng.next_mutez(), rng.next_mutez());
+        let expected = a as i128 + b as i128;
+        match mutez(a) + mutez(b) {
+            Ok(res) => assert_eq!(mutez(expected as i64), res),
+            Err(Error::MutezAdditionOverflow { .. }) => assert!(expected > i64::MAX as i128),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_mutez_sub() -> Result<()> {
+    let mut rng = Rng(0xcafebabe);
+    for _ in 0..ITERATIONS {
+        let (a, b) = (rng.next_mutez(), rng.next_mutez());
+        let expected = a as i128 - b as i128;
+        match mutez(a) - mutez(b) {
+            Ok(res) => assert_eq!(mutez(expected as i64), res),
+            Err(Error::MutezSubtractionUnderflow { .. }) => assert!(expected < 0),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_mutez_mul() -> Result<()> {
+    let mut rng = Rng(0xfeedface);
+    for _ in 0..ITERATIONS {
+        let (a, b) = (rng.next_mutez(), rng.next_u64());
+        let expected = a as u128 * b as u128;
+        match mutez(a) * nat(b as u128) {
+            Ok(res) => assert_eq!(mutez(expected as i64), res),
+            Err(Error::MutezMultiplicationOverflow { .. }) => {
+                assert!(expected > i64::MAX as u128)
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_mutez_ediv() -> Result<()> {
+    let mut rng = Rng(0xbadc0ffee);
+    for _ in 0..ITERATIONS {
+        let (a, b) = (rng.next_mutez(), rng.next_mutez());
+        if b == 0 {
+            assert!((mutez(a) / mutez(b))?.is_none());
+            assert!((mutez(a) / nat(0))?.is_none());
+            continue;
+        }
+
+        let expected = PairItem::new(nat((a / b) as u128).into(), mutez(a % b).into());
+        assert_eq!(OptionItem::some(expected.into()), (mutez(a) / mutez(b))?);
+
+        let expected = PairItem::new(mutez(a / b).into(), mutez(a % b).into());
+        assert_eq!(
+            OptionItem::some(expected.into()),
+            (mutez(a) / nat(b as u128))?
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_mutez_conversions() -> Result<()> {
+    let max = IBig::from(i64::MAX);
+    assert_eq!(mutez(i64::MAX), MutezItem::try_from(max.clone())?);
+    assert_eq!(
+        Err(Error::MutezOutOfRange {
+            value: &max + IBig::from(1u8)
+        }),
+        MutezItem::try_from(&max + IBig::from(1u8))
+    );
+    assert_eq!(
+        Err(Error::MutezOutOfRange {
+            value: IBig::from(-1)
+        }),
+        MutezItem::try_from(IBig::from(-1))
+    );
+    assert_eq!(
+        Err(Error::MutezOutOfRange {
+            value: IBig::from(u64::MAX)
+        }),
+        MutezItem::try_from(UBig::from(u64::MAX))
+    );
+    assert_eq!(
+        Err(Error::MutezMultiplicationOverflow {
+            a: i64::MAX,
+            b: UBig::from(2u8)
+        }),
+        mutez(i64::MAX) * nat(2)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_nat_shifts() -> Result<()> {
+    let mut rng = Rng(0x12345678);
+    for _ in 0..ITERATIONS {
+        let value = rng.next_u64();
+        let shift = rng.next_raw() % 300;
+
+        match nat(value as u128) << nat(shift as u128) {
+            Ok(res) => {
+                assert!(shift <= 256);
+                let expected = UBig::from(value) * UBig::from(2u8).pow(shift as usize);
+                assert_eq!(NatItem::from(expected), res);
+            }
+            Err(Error::GeneralOverflow) => assert!(shift > 256),
+            Err(err) => return Err(err),
+        }
+
+        match nat(value as u128) >> nat(shift as u128) {
+            Ok(res) => {
+                assert!(shift <= 256);
+                let expected = value.checked_shr(shift as u32).unwrap_or(0);
+                assert_eq!(nat(expected as u128), res);
+            }
+            Err(Error::GeneralOverflow) => assert!(shift > 256),
+            Err(err) => return Err(err),
+        }
+    }
+
+    let huge = UBig::from(u128::MAX) * UBig::from(u128::MAX);
+    assert_eq!(
+        Err(Error::GeneralOverflow),
+        nat(1) << NatItem::from(huge.clone())
+    );
+    assert_eq!(Err(Error::GeneralOverflow), nat(1) >> NatItem::from(huge));
+    Ok(())
+}
+
+#[test]
+fn test_int_and_nat() -> Result<()> {
+    let mut rng = Rng(0x87654321);
+    for _ in 0..ITERATIONS {
+        let (a, b) = (rng.next_u64() as i64, rng.next_u64());
+        let expected = (a as i128) & (b as i128);
+        let res = (IntItem::from(a) & nat(b as u128))?;
+        assert_eq!(nat(expected as u128), res);
+    }
+
+    // Beyond 128 bits
+    let a = -(IBig::from(u128::MAX) << 64);
+    let b = UBig::from(u128::MAX) << 128;
+    let expected = (UBig::from(u128::MAX) << 192) & (UBig::from(u128::MAX) << 128);
+    assert_eq!(
+        NatItem::from(expected),
+        (IntItem::from(a) & NatItem::from(b))?
+    );
+    Ok(())
+}
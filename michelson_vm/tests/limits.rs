@@ -0,0 +1,59 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use ibig::UBig;
+use michelson_vm::{types::NatItem, Error, InterpreterLimits, Result, Stack};
+
+fn limits() -> InterpreterLimits {
+    InterpreterLimits {
+        max_stack_size: 4,
+        max_call_depth: 2,
+        max_pack_size: 100,
+        max_steps: 3,
+    }
+}
+
+fn nat(value: u32) -> NatItem {
+    NatItem::from(UBig::from(value))
+}
+
+#[test]
+fn test_stack_size_limit() -> Result<()> {
+    let mut stack = Stack::with_limits(&limits());
+    for i in 0..4u32 {
+        stack.push(nat(i).into())?;
+    }
+    assert_eq!(
+        Err(Error::StackOverflow { limit: 4 }),
+        stack.push(nat(4).into())
+    );
+    assert_eq!(
+        Err(Error::StackOverflow { limit: 4 }),
+        stack.push_at(1, nat(4).into())
+    );
+    Ok(())
+}
+
+#[test]
+fn test_call_depth_limit() -> Result<()> {
+    let stack = Stack::with_limits(&limits());
+    let nested = stack.spawn()?.spawn()?;
+    assert_eq!(
+        Err(Error::CallDepthExceeded { limit: 2 }),
+        nested.spawn().map(|_| ())
+    );
+    Ok(())
+}
+
+#[test]
+fn test_step_limit() -> Result<()> {
+    let stack = Stack::with_limits(&limits());
+    let nested = stack.spawn()?;
+    stack.step()?;
+    nested.step()?;
+    stack.step()?;
+    assert_eq!(3, stack.steps());
+    assert_eq!(Err(Error::StepLimitExceeded { limit: 3 }), nested.step());
+    Ok(())
+}
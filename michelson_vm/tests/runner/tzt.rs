@@ -92,7 +92,7 @@ impl TZT {
             }
             Err(err) => {
                 let expected = self.output.error.as_ref().expect("Error undefined");
-                assert_eq!(*expected, err);
+                assert_error_eq(expected, &err);
                 trace_exit!(Some(&err.into()));
             }
         }
@@ -106,6 +106,18 @@ impl TZT {
     }
 }
 
+fn assert_error_eq(expected: &Error, actual: &Error) {
+    // TZT does not distinguish between particular mutez arithmetic errors
+    match (expected, actual) {
+        (Error::MutezOverflow, Error::MutezAdditionOverflow { .. }) => {}
+        (Error::MutezOverflow, Error::MutezMultiplicationOverflow { .. }) => {}
+        (Error::MutezUnderflow, Error::MutezSubtractionUnderflow { .. }) => {}
+        (Error::MutezOverflow, Error::MutezOutOfRange { .. }) => {}
+        (Error::MutezUnderflow, Error::MutezOutOfRange { .. }) => {}
+        (lhs, rhs) => assert_eq!(lhs, rhs),
+    }
+}
+
 fn parse_elements(sequence: Sequence) -> Result<Vec<StackItem>> {
     let mut items: Vec<StackItem> = Vec::new();
     for item in sequence.into_values() {
@@ -0,0 +1,394 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use layered_store::{error::err_into, LayeredStore, StoreBackend, StoreType};
+use tezos_core::types::encoded::{Encoded, OperationHash};
+use tezos_operation::operations::SignedOperation;
+
+use crate::{
+    internal_error,
+    payload::{parse_l2_operation, OperationChunk},
+    Error, Result,
+};
+
+/// Number of levels after which incomplete chunk sets are discarded
+pub const CHUNKS_TTL: i32 = 10;
+
+/// Number of operations that can be reassembled at the same time
+pub const MAX_PENDING_CHUNK_SETS: usize = 100;
+
+const PENDING_CHUNKS_PATH: &str = "/chunks/pending";
+
+/// Progress of a chunked operation reassembly
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChunkSet {
+    pub total: u16,
+    pub received: u16,
+    /// Level at which the first chunk was received
+    pub level: i32,
+    /// Indices of the stored chunks
+    pub indices: Vec<u16>,
+}
+
+impl StoreType for ChunkSet {
+    fn from_bytes(bytes: &[u8]) -> layered_store::Result<Self> {
+        if bytes.len() < 8 || bytes.len() % 2 != 0 {
+            return Err(layered_store::internal_error!("Invalid byte length"));
+        }
+        Ok(Self {
+            total: u16::from_be_bytes([bytes[0], bytes[1]]),
+            received: u16::from_be_bytes([bytes[2], bytes[3]]),
+            level: i32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            indices: bytes[8..]
+                .chunks(2)
+                .map(|index| u16::from_be_bytes([index[0], index[1]]))
+                .collect(),
+        })
+    }
+
+    fn to_bytes(&self) -> layered_store::Result<Vec<u8>> {
+        let mut bytes = [
+            self.total.to_be_bytes().as_slice(),
+            self.received.to_be_bytes().as_slice(),
+            self.level.to_be_bytes().as_slice(),
+        ]
+        .concat();
+        for index in self.indices.iter() {
+            bytes.extend_from_slice(&index.to_be_bytes());
+        }
+        Ok(bytes)
+    }
+}
+
+/// Operations that are being reassembled, identified by hash and number of chunks
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingChunks(pub Vec<(OperationHash, u16)>);
+
+impl StoreType for PendingChunks {
+    fn from_bytes(bytes: &[u8]) -> layered_store::Result<Self> {
+        if bytes.len() % 34 != 0 {
+            return Err(layered_store::internal_error!("Invalid byte length"));
+        }
+        let entries = bytes
+            .chunks(34)
+            .map(|entry| {
+                let hash = OperationHash::from_bytes(&entry[..32]).map_err(err_into)?;
+                Ok((hash, u16::from_be_bytes([entry[32], entry[33]])))
+            })
+            .collect::<layered_store::Result<Vec<(OperationHash, u16)>>>()?;
+        Ok(Self(entries))
+    }
+
+    fn to_bytes(&self) -> layered_store::Result<Vec<u8>> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.0.len() * 34);
+        for (hash, total) in self.0.iter() {
+            bytes.extend(hash.to_bytes().map_err(err_into)?);
+            bytes.extend_from_slice(&total.to_be_bytes());
+        }
+        Ok(bytes)
+    }
+}
+
+// Chunk sets of the same operation with a different number of chunks are kept apart,
+// so that a forged first chunk cannot fix the total for everyone else
+fn chunk_set_path(hash: &OperationHash, total: u16) -> String {
+    format!("/chunks/{}/{}/info", hash.value(), total)
+}
+
+fn chunk_path(hash: &OperationHash, total: u16, index: u16) -> String {
+    format!("/chunks/{}/{}/{}", hash.value(), total, index)
+}
+
+fn get_pending_chunks<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+) -> Result<PendingChunks> {
+    Ok(context
+        .get(PENDING_CHUNKS_PATH.into())?
+        .unwrap_or(PendingChunks(Vec::new())))
+}
+
+fn remove_chunk_set<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    hash: &OperationHash,
+    chunk_set: &ChunkSet,
+) -> Result<()> {
+    for index in chunk_set.indices.iter() {
+        context.set::<Vec<u8>>(chunk_path(hash, chunk_set.total, *index), None)?;
+    }
+    context.set::<ChunkSet>(chunk_set_path(hash, chunk_set.total), None)?;
+
+    let mut pending = get_pending_chunks(context)?;
+    pending
+        .0
+        .retain(|(h, total)| h != hash || *total != chunk_set.total);
+    context.set(PENDING_CHUNKS_PATH.into(), Some(pending))?;
+    Ok(())
+}
+
+/// Stores the chunk and returns the complete operation once all the chunks are received.
+/// Changes are committed so that they survive the batch application, the chunk set is
+/// removed once complete even if the reassembled operation turns out to be invalid.
+pub fn add_chunk<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    chunk: OperationChunk,
+    level: i32,
+) -> Result<Option<(OperationHash, SignedOperation)>> {
+    let hash = chunk.hash.clone();
+    let payload = match apply_chunk(context, chunk, level) {
+        Ok(Some(payload)) => {
+            context.commit()?;
+            payload
+        }
+        Ok(None) => {
+            context.commit()?;
+            return Ok(None);
+        }
+        Err(err) => {
+            context.rollback();
+            return Err(err);
+        }
+    };
+
+    let (opg_hash, opg) = parse_l2_operation(payload.as_slice())?;
+    if opg_hash != hash {
+        return Err(Error::ChunkedOperationHashMismatch {
+            expected: hash.value().to_string(),
+            found: opg_hash.value().to_string(),
+        });
+    }
+    Ok(Some((opg_hash, opg)))
+}
+
+/// Returns the reassembled payload once all the chunks are received
+fn apply_chunk<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    chunk: OperationChunk,
+    level: i32,
+) -> Result<Option<Vec<u8>>> {
+    let set_path = chunk_set_path(&chunk.hash, chunk.total);
+    let mut chunk_set = match context.get::<ChunkSet>(set_path.clone())? {
+        Some(chunk_set) => chunk_set,
+        None => {
+            let mut pending = get_pending_chunks(context)?;
+            if pending.0.len() >= MAX_PENDING_CHUNK_SETS {
+                return Err(Error::TooManyPendingChunkSets {
+                    max: MAX_PENDING_CHUNK_SETS,
+                });
+            }
+            pending.0.push((chunk.hash.clone(), chunk.total));
+            context.set(PENDING_CHUNKS_PATH.into(), Some(pending))?;
+            ChunkSet {
+                total: chunk.total,
+                received: 0,
+                level,
+                indices: Vec::new(),
+            }
+        }
+    };
+
+    // Data of a resent chunk replaces the stored one, so that a forged chunk can be corrected
+    context.set(
+        chunk_path(&chunk.hash, chunk.total, chunk.index),
+        Some(chunk.data),
+    )?;
+    if chunk_set.indices.contains(&chunk.index) {
+        return Ok(None);
+    }
+    chunk_set.indices.push(chunk.index);
+    chunk_set.received += 1;
+
+    if chunk_set.received < chunk_set.total {
+        context.set(set_path, Some(chunk_set))?;
+        return Ok(None);
+    }
+
+    let mut payload: Vec<u8> = Vec::new();
+    for index in 0..chunk_set.total {
+        match context.get::<Vec<u8>>(chunk_path(&chunk.hash, chunk_set.total, index))? {
+            Some(data) => payload.extend(data),
+            None => return Err(internal_error!("Missing chunk #{}", index)),
+        }
+    }
+    remove_chunk_set(context, &chunk.hash, &chunk_set)?;
+    Ok(Some(payload))
+}
+
+/// Discards incomplete chunk sets that were started more than `CHUNKS_TTL` levels ago
+pub fn expire_chunks<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    level: i32,
+) -> Result<Vec<OperationHash>> {
+    let pending = get_pending_chunks(context)?;
+
+    let mut expired: Vec<OperationHash> = Vec::new();
+    for (hash, total) in pending.0.into_iter() {
+        if let Some(chunk_set) = context.get::<ChunkSet>(chunk_set_path(&hash, total))? {
+            if level - chunk_set.level >= CHUNKS_TTL {
+                remove_chunk_set(context, &hash, &chunk_set)?;
+                expired.push(hash);
+            }
+        }
+    }
+
+    context.commit()?;
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod test {
+    use layered_store::EphemeralStore;
+
+    use super::*;
+    use crate::fixtures::signed_operation;
+
+    fn make_chunks(hash: &OperationHash, payload: &[u8], size: usize) -> Vec<OperationChunk> {
+        let total = payload.chunks(size).len() as u16;
+        payload
+            .chunks(size)
+            .enumerate()
+            .map(|(index, data)| OperationChunk {
+                hash: hash.clone(),
+                index: index as u16,
+                total,
+                data: data.to_vec(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunks_reassembly() -> Result<()> {
+        let mut context = EphemeralStore::default();
+        let payload = signed_operation();
+        let (hash, _) = parse_l2_operation(payload.as_slice())?;
+
+        let mut chunks = make_chunks(&hash, payload.as_slice(), 50);
+        assert!(chunks.len() > 2);
+        let total = chunks[0].total;
+        // Chunks can arrive in any order
+        let last = chunks.remove(0);
+        let duplicate = chunks[0].clone();
+
+        for chunk in chunks.into_iter() {
+            assert!(add_chunk(&mut context, chunk, 1)?.is_none());
+        }
+        assert!(add_chunk(&mut context, duplicate, 1)?.is_none());
+
+        let (opg_hash, _) = add_chunk(&mut context, last, 2)?.expect("Complete operation");
+        assert_eq!(hash, opg_hash);
+
+        let pending: Option<PendingChunks> = context.get(PENDING_CHUNKS_PATH.into())?;
+        assert_eq!(Some(PendingChunks(vec![])), pending);
+        assert!(!context.has(chunk_set_path(&hash, total))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunks_forged() -> Result<()> {
+        let mut context = EphemeralStore::default();
+        let payload = signed_operation();
+        let (hash, _) = parse_l2_operation(payload.as_slice())?;
+        let chunks = make_chunks(&hash, payload.as_slice(), 100);
+
+        // Forged chunks with another total or data do not block the genuine ones
+        let mut forged_total = make_chunks(&hash, payload.as_slice(), 50).remove(0);
+        forged_total.data = vec![0u8; 50];
+        assert!(add_chunk(&mut context, forged_total, 1)?.is_none());
+        let mut forged_data = chunks[0].clone();
+        forged_data.data = vec![0u8; 100];
+        assert!(add_chunk(&mut context, forged_data, 1)?.is_none());
+
+        let mut res = None;
+        for chunk in chunks.into_iter() {
+            res = add_chunk(&mut context, chunk, 1)?;
+        }
+        assert_eq!(Some(hash.clone()), res.map(|(hash, _)| hash));
+        assert_eq!(1, get_pending_chunks(&mut context)?.0.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunks_hash_mismatch() -> Result<()> {
+        let mut context = EphemeralStore::default();
+        let payload = signed_operation();
+        let hash: OperationHash = "ooKPP7mUdw5fKbXsPPReekQz6LWGpG4yLYg3NEDK6ESd4wCJq7x"
+            .try_into()
+            .unwrap();
+
+        let chunks = make_chunks(&hash, payload.as_slice(), 100);
+        let total = chunks[0].total;
+        let mut res = Ok(None);
+        for chunk in chunks {
+            res = add_chunk(&mut context, chunk, 1);
+        }
+        assert!(matches!(
+            res,
+            Err(Error::ChunkedOperationHashMismatch { .. })
+        ));
+
+        // The chunk set is removed, so that the operation can be resent
+        assert!(!context.has(chunk_set_path(&hash, total))?);
+        assert!(!context.has(chunk_path(&hash, total, 0))?);
+        assert!(get_pending_chunks(&mut context)?.0.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pending_chunks_cap() -> Result<()> {
+        let mut context = EphemeralStore::default();
+        let payload = signed_operation();
+        let (hash, _) = parse_l2_operation(payload.as_slice())?;
+
+        let pending = (1..=MAX_PENDING_CHUNK_SETS as u16)
+            .map(|total| (hash.clone(), total + 1))
+            .collect();
+        context.set(PENDING_CHUNKS_PATH.into(), Some(PendingChunks(pending)))?;
+        context.commit()?;
+
+        let chunk = make_chunks(&hash, payload.as_slice(), 100).remove(0);
+        assert!(matches!(
+            add_chunk(&mut context, chunk, 1),
+            Err(Error::TooManyPendingChunkSets { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunks_expiration() -> Result<()> {
+        let mut context = EphemeralStore::default();
+        let payload = signed_operation();
+        let (hash, _) = parse_l2_operation(payload.as_slice())?;
+
+        let chunk = make_chunks(&hash, payload.as_slice(), 100).remove(0);
+        let total = chunk.total;
+        add_chunk(&mut context, chunk, 1)?;
+
+        assert!(expire_chunks(&mut context, CHUNKS_TTL)?.is_empty());
+        assert_eq!(
+            vec![hash.clone()],
+            expire_chunks(&mut context, 1 + CHUNKS_TTL)?
+        );
+        assert!(!context.has(chunk_path(&hash, total, 0))?);
+        assert!(!context.has(chunk_set_path(&hash, total))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunks_total_cap() -> Result<()> {
+        let payload = signed_operation();
+        let (hash, _) = parse_l2_operation(payload.as_slice())?;
+
+        let bytes = [
+            hash.to_bytes()?.as_slice(),
+            0u16.to_be_bytes().as_slice(),
+            u16::MAX.to_be_bytes().as_slice(),
+            &payload[..100],
+        ]
+        .concat();
+        assert!(matches!(
+            OperationChunk::from_bytes(bytes.as_slice()),
+            Err(Error::TooManyChunks { .. })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,454 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use layered_store::{error::err_into, LayeredStore, StoreBackend, StoreType};
+use tezos_core::types::encoded::{ChainId, Encoded, ImplicitAddress, OperationHash};
+use tezos_operation::operations::{OperationContent, SignedOperation};
+use tezos_proto::{context::TezosContext, executor::reveal::public_key_hash};
+
+use crate::{
+    internal_error,
+    payload::{parse_l2_operation, SignedCommitment},
+    signature::verify_signature,
+    Error, Result,
+};
+
+/// Number of levels after which commitments that are not revealed are dropped
+pub const COMMITMENT_TTL: i32 = 5;
+
+/// Maximum number of pending commitments, the queue is read on every level
+pub const MAX_COMMITMENTS: usize = 256;
+
+const COMMITMENTS_PATH: &str = "/commitments/pending";
+const REVEALS_PATH: &str = "/commitments/reveals";
+
+fn commitment_path(hash: &OperationHash) -> String {
+    format!("/commitments/{}", hash.value())
+}
+
+fn committer_path(address: &ImplicitAddress) -> String {
+    format!("/commitments/committers/{}", address.value())
+}
+
+fn revealed_operation_path(hash: &OperationHash) -> String {
+    format!("/commitments/revealed/{}", hash.value())
+}
+
+/// Hash of a signed operation submitted ahead of the operation itself
+#[derive(Clone, Debug, PartialEq)]
+pub struct Commitment {
+    pub hash: OperationHash,
+    /// Level at which the commitment was received
+    pub level: i32,
+}
+
+/// Commitments in the order of arrival, which is the execution order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Commitments(pub Vec<Commitment>);
+
+impl StoreType for Commitments {
+    fn from_bytes(bytes: &[u8]) -> layered_store::Result<Self> {
+        if bytes.len() % 36 != 0 {
+            return Err(layered_store::internal_error!("Invalid byte length"));
+        }
+        let commitments = bytes
+            .chunks(36)
+            .map(|chunk| {
+                Ok(Commitment {
+                    hash: OperationHash::from_bytes(&chunk[..32]).map_err(err_into)?,
+                    level: i32::from_be_bytes([chunk[32], chunk[33], chunk[34], chunk[35]]),
+                })
+            })
+            .collect::<layered_store::Result<Vec<Commitment>>>()?;
+        Ok(Self(commitments))
+    }
+
+    fn to_bytes(&self) -> layered_store::Result<Vec<u8>> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.0.len() * 36);
+        for commitment in self.0.iter() {
+            bytes.extend(commitment.hash.to_bytes().map_err(err_into)?);
+            bytes.extend_from_slice(&commitment.level.to_be_bytes());
+        }
+        Ok(bytes)
+    }
+}
+
+/// Hashes of the operations revealed since the last block
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Reveals(pub Vec<OperationHash>);
+
+impl StoreType for Reveals {
+    fn from_bytes(bytes: &[u8]) -> layered_store::Result<Self> {
+        let hashes = bytes
+            .chunks(32)
+            .map(|hash| OperationHash::from_bytes(hash).map_err(err_into))
+            .collect::<layered_store::Result<Vec<OperationHash>>>()?;
+        Ok(Self(hashes))
+    }
+
+    fn to_bytes(&self) -> layered_store::Result<Vec<u8>> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.0.len() * 32);
+        for hash in self.0.iter() {
+            bytes.extend(hash.to_bytes().map_err(err_into)?);
+        }
+        Ok(bytes)
+    }
+}
+
+fn get_commitments<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+) -> Result<Commitments> {
+    Ok(context.get(COMMITMENTS_PATH.into())?.unwrap_or_default())
+}
+
+fn get_reveals<Backend: StoreBackend>(context: &mut LayeredStore<Backend>) -> Result<Reveals> {
+    Ok(context.get(REVEALS_PATH.into())?.unwrap_or_default())
+}
+
+fn is_sent_by(opg: &SignedOperation, committer: &ImplicitAddress) -> bool {
+    opg.contents.iter().all(|content| match content {
+        OperationContent::Reveal(reveal) => &reveal.source == committer,
+        OperationContent::Transaction(transaction) => &transaction.source == committer,
+        OperationContent::Origination(origination) => &origination.source == committer,
+        _ => false,
+    })
+}
+
+/// Whether the operation waits for its turn, it cannot be included in a block otherwise.
+/// Only the commitment of the operation source counts, so that nobody can hold
+/// an operation back by committing its hash.
+pub fn is_committed<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    hash: &OperationHash,
+    opg: &SignedOperation,
+) -> Result<bool> {
+    match context.get::<ImplicitAddress>(commitment_path(hash))? {
+        Some(committer) => Ok(is_sent_by(opg, &committer)),
+        None => Ok(false),
+    }
+}
+
+/// Checks the committer signature over the chain id and the operation hash.
+/// The committer has to be funded and can have one pending commitment at a time.
+/// Returns `false` if the same operation is already committed.
+pub fn accept_commitment<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    chain_id: &ChainId,
+    commitment: &SignedCommitment,
+    level: i32,
+) -> Result<bool> {
+    let committer = public_key_hash(&commitment.public_key)?;
+    let payload = [chain_id.to_bytes()?, commitment.hash.to_bytes()?].concat();
+    if !verify_signature(&commitment.public_key, &payload, &commitment.signature)? {
+        return Err(Error::InvalidCommitmentSignature);
+    }
+
+    match context.get_balance(committer.value())? {
+        Some(balance) if balance > 0u32.into() => {}
+        _ => {
+            return Err(Error::EmptyCommitterBalance {
+                address: committer.value().to_string(),
+            })
+        }
+    }
+
+    add_commitment(context, commitment.hash.clone(), committer, level)
+}
+
+/// Fixes the position of the operation in the execution order.
+/// Returns `false` if the same operation is already committed.
+pub fn add_commitment<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    hash: OperationHash,
+    committer: ImplicitAddress,
+    level: i32,
+) -> Result<bool> {
+    let path = commitment_path(&hash);
+    if context.has(path.clone())? {
+        return Ok(false);
+    }
+
+    if context.has(committer_path(&committer))? {
+        return Err(Error::CommitmentPending {
+            address: committer.value().to_string(),
+        });
+    }
+
+    let mut commitments = get_commitments(context)?;
+    if commitments.0.len() >= MAX_COMMITMENTS {
+        return Err(Error::CommitmentQueueFull {
+            max: MAX_COMMITMENTS,
+        });
+    }
+
+    context.set(committer_path(&committer), Some(hash.clone()))?;
+    context.set(path, Some(committer))?;
+    commitments.0.push(Commitment { hash, level });
+    context.set(COMMITMENTS_PATH.into(), Some(commitments))?;
+    context.commit()?;
+    Ok(true)
+}
+
+/// Stores the operation until the next block, the operation must be committed beforehand.
+/// Returns the operation hash.
+pub fn reveal_committed_operation<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    operation: Vec<u8>,
+) -> Result<OperationHash> {
+    let (hash, opg) = parse_l2_operation(operation.as_slice())?;
+    let committer: ImplicitAddress = match context.get(commitment_path(&hash))? {
+        Some(committer) => committer,
+        None => {
+            return Err(Error::UnknownCommitment {
+                hash: hash.value().to_string(),
+            })
+        }
+    };
+
+    // Otherwise anyone who has seen the operation could commit it in the user's stead
+    if !is_sent_by(&opg, &committer) {
+        return Err(Error::CommitterMismatch {
+            hash: hash.value().to_string(),
+        });
+    }
+
+    let path = revealed_operation_path(&hash);
+    if !context.has(path.clone())? {
+        let mut reveals = get_reveals(context)?;
+        reveals.0.push(hash.clone());
+        context.set(REVEALS_PATH.into(), Some(reveals))?;
+        context.set(path, Some(operation))?;
+        context.commit()?;
+    }
+    Ok(hash)
+}
+
+fn remove_commitment<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    hash: &OperationHash,
+) -> Result<()> {
+    let path = commitment_path(hash);
+    if let Some(committer) = context.get::<ImplicitAddress>(path.clone())? {
+        context.set::<OperationHash>(committer_path(&committer), None)?;
+    }
+    context.set::<ImplicitAddress>(path, None)?;
+    Ok(())
+}
+
+pub fn has_revealed_operations<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+) -> Result<bool> {
+    Ok(context.has(REVEALS_PATH.into())?)
+}
+
+/// Takes the operations revealed since the last block in the commitment order.
+/// They go ahead of the operations of the next block, whoever produces it,
+/// so that nobody who has seen them can get an operation applied before.
+pub fn take_revealed_operations<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+) -> Result<Vec<(OperationHash, SignedOperation)>> {
+    let reveals = get_reveals(context)?;
+    if reveals.0.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut commitments = get_commitments(context)?;
+    let mut operations: Vec<(OperationHash, SignedOperation)> = Vec::new();
+    let mut pending: Vec<Commitment> = Vec::with_capacity(commitments.0.len());
+    for commitment in commitments.0.into_iter() {
+        if !reveals.0.contains(&commitment.hash) {
+            pending.push(commitment);
+            continue;
+        }
+        let path = revealed_operation_path(&commitment.hash);
+        let operation: Vec<u8> = context
+            .get(path.clone())?
+            .ok_or_else(|| internal_error!("Revealed operation is missing"))?;
+        context.set::<Vec<u8>>(path, None)?;
+        remove_commitment(context, &commitment.hash)?;
+        operations.push(parse_l2_operation(operation.as_slice())?);
+    }
+
+    commitments.0 = pending;
+    context.set(COMMITMENTS_PATH.into(), Some(commitments))?;
+    context.set::<Reveals>(REVEALS_PATH.into(), None)?;
+    context.commit()?;
+    Ok(operations)
+}
+
+/// Drops the commitments that are not revealed within `COMMITMENT_TTL` levels.
+/// Returns the dropped commitments.
+pub fn expire_commitments<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    level: i32,
+) -> Result<Vec<OperationHash>> {
+    let mut commitments = get_commitments(context)?;
+    let mut dropped: Vec<OperationHash> = Vec::new();
+    // The queue is in the order of arrival, so the next ones are not expired either
+    while let Some(commitment) = commitments.0.first() {
+        if level - commitment.level < COMMITMENT_TTL
+            || context.has(revealed_operation_path(&commitment.hash))?
+        {
+            break;
+        }
+        remove_commitment(context, &commitment.hash)?;
+        dropped.push(commitments.0.remove(0).hash);
+    }
+
+    if !dropped.is_empty() {
+        context.set(COMMITMENTS_PATH.into(), Some(commitments))?;
+        context.commit()?;
+    }
+    Ok(dropped)
+}
+
+#[cfg(test)]
+mod test {
+    use layered_store::EphemeralStore;
+
+    use super::*;
+    use crate::fixtures::signed_operation;
+
+    const SOURCE: &str = "tz1grSQDByRpnVs7sPtaprNZRp531ZKz6Jmm";
+    const OTHER: &str = "tz1Qr9uevaimfiPS6X1otehsKrwvZjX7bsyL";
+
+    fn address(value: &str) -> ImplicitAddress {
+        value.try_into().unwrap()
+    }
+
+    #[test]
+    fn test_accept_commitment() -> Result<()> {
+        let mut context = EphemeralStore::default();
+        let chain_id: ChainId = "NetXH12Aer3be93".try_into().unwrap();
+        // hash (32 bytes), public key of tz1Qr9uevaimfiPS6X1otehsKrwvZjX7bsyL, signature
+        let commitment = SignedCommitment::from_bytes(
+            &hex::decode(
+                "58f3390ad6af4b7fc96e1167449d0532da1210b69297bd9da11707f2c994c68a\
+                00ea4a6c63e29c520abef5507b132ec5f9954776aebebe7b92421eea691446d22c\
+                e92c33e87db1120e2ca2e58caa51fd66ece4427c86a01ca8d0e9a863bae0ffcc\
+                f3c4404690f38f08e5498b650849fb59326a695c8b6cbeb5c0ba9b1c6fe7d909",
+            )
+            .unwrap(),
+        )?;
+
+        assert!(matches!(
+            accept_commitment(&mut context, &chain_id, &commitment, 1),
+            Err(Error::EmptyCommitterBalance { .. })
+        ));
+
+        context.set_balance(OTHER, 1u32.into())?;
+        let other_chain: ChainId = "NetXdQprcVkpaWU".try_into().unwrap();
+        assert!(matches!(
+            accept_commitment(&mut context, &other_chain, &commitment, 1),
+            Err(Error::InvalidCommitmentSignature)
+        ));
+
+        assert!(accept_commitment(&mut context, &chain_id, &commitment, 1)?);
+        assert!(context.has(commitment_path(&commitment.hash))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_reveal_order() -> Result<()> {
+        let mut context = EphemeralStore::default();
+        let operation = signed_operation();
+        let (hash, opg) = parse_l2_operation(operation.as_slice())?;
+        let missing: OperationHash = "ooKPP7mUdw5fKbXsPPReekQz6LWGpG4yLYg3NEDK6ESd4wCJq7x"
+            .try_into()
+            .unwrap();
+
+        assert!(matches!(
+            reveal_committed_operation(&mut context, operation.clone()),
+            Err(Error::UnknownCommitment { .. })
+        ));
+
+        // The commitment that is never revealed does not hold the operation back
+        assert!(add_commitment(
+            &mut context,
+            missing.clone(),
+            address(OTHER),
+            1
+        )?);
+        assert!(matches!(
+            add_commitment(&mut context, hash.clone(), address(OTHER), 1),
+            Err(Error::CommitmentPending { .. })
+        ));
+        assert!(add_commitment(
+            &mut context,
+            hash.clone(),
+            address(SOURCE),
+            1
+        )?);
+        assert!(!add_commitment(
+            &mut context,
+            hash.clone(),
+            address(SOURCE),
+            2
+        )?);
+        assert!(is_committed(&mut context, &hash, &opg)?);
+        assert!(!has_revealed_operations(&mut context)?);
+        assert_eq!(hash, reveal_committed_operation(&mut context, operation)?);
+        assert!(has_revealed_operations(&mut context)?);
+
+        let operations = take_revealed_operations(&mut context)?;
+        assert_eq!(1, operations.len());
+        assert_eq!(hash, operations[0].0);
+        assert!(!is_committed(&mut context, &hash, &opg)?);
+        assert!(!has_revealed_operations(&mut context)?);
+        assert!(!context.has(revealed_operation_path(&hash))?);
+        assert!(!context.has(committer_path(&address(SOURCE)))?);
+        assert!(take_revealed_operations(&mut context)?.is_empty());
+
+        assert!(expire_commitments(&mut context, COMMITMENT_TTL)?.is_empty());
+        assert_eq!(
+            vec![missing.clone()],
+            expire_commitments(&mut context, 1 + COMMITMENT_TTL)?
+        );
+        assert!(!context.has(commitment_path(&missing))?);
+        assert!(!context.has(committer_path(&address(OTHER)))?);
+        assert!(get_commitments(&mut context)?.0.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reveal_by_other_committer() -> Result<()> {
+        let mut context = EphemeralStore::default();
+        let operation = signed_operation();
+        let (hash, opg) = parse_l2_operation(operation.as_slice())?;
+
+        // A third party commitment does not hold the operation back
+        assert!(add_commitment(
+            &mut context,
+            hash.clone(),
+            address(OTHER),
+            1
+        )?);
+        assert!(!is_committed(&mut context, &hash, &opg)?);
+        assert!(matches!(
+            reveal_committed_operation(&mut context, operation),
+            Err(Error::CommitterMismatch { .. })
+        ));
+        assert!(!has_revealed_operations(&mut context)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_commitments_cap() -> Result<()> {
+        let mut context = EphemeralStore::default();
+        let hash: OperationHash = "ooKPP7mUdw5fKbXsPPReekQz6LWGpG4yLYg3NEDK6ESd4wCJq7x"
+            .try_into()
+            .unwrap();
+        let queue = Commitments(vec![Commitment { hash, level: 1 }; MAX_COMMITMENTS]);
+        context.set(COMMITMENTS_PATH.into(), Some(queue))?;
+        context.commit()?;
+
+        let operation = signed_operation();
+        let (hash, _) = parse_l2_operation(operation.as_slice())?;
+        assert!(matches!(
+            add_commitment(&mut context, hash, address(SOURCE), 1),
+            Err(Error::CommitmentQueueFull { .. })
+        ));
+        Ok(())
+    }
+}
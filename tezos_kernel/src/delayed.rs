@@ -0,0 +1,277 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use layered_store::{error::err_into, LayeredStore, StoreBackend, StoreType};
+use tezos_core::types::encoded::{Encoded, OperationHash};
+use tezos_operation::operations::SignedOperation;
+use tezos_proto::validator::operation::check_signature_and_branch;
+
+use crate::{internal_error, payload::parse_l2_operation, Error, Result};
+
+/// Number of levels after which delayed operations are included regardless of the sequencer
+pub const DEFAULT_DELAYED_TIMEOUT: i32 = 20;
+
+/// Maximum number of queued operations, the queue is read on every block
+pub const MAX_DELAYED_OPERATIONS: usize = 1000;
+
+/// Overrides the default timeout (in levels), set along with the sequencer allowlist
+pub const DELAYED_TIMEOUT_PATH: &str = "/delayed/timeout";
+
+const DELAYED_QUEUE_PATH: &str = "/delayed/pending";
+
+fn delayed_operation_path(hash: &OperationHash) -> String {
+    format!("/delayed/operations/{}", hash.value())
+}
+
+/// Operation posted to the inbox by a user in permissioned mode, waiting for inclusion
+#[derive(Clone, Debug)]
+pub struct DelayedOperation {
+    /// Level at which the operation was received
+    pub level: i32,
+    pub hash: OperationHash,
+    pub opg: SignedOperation,
+}
+
+impl StoreType for DelayedOperation {
+    fn from_bytes(bytes: &[u8]) -> layered_store::Result<Self> {
+        if bytes.len() < 4 {
+            return Err(layered_store::internal_error!("Invalid byte length"));
+        }
+        let (level, operation) = bytes.split_at(4);
+        let (hash, opg) = parse_l2_operation(operation).map_err(err_into)?;
+        Ok(Self {
+            level: i32::from_be_bytes(level.try_into().expect("Level size")),
+            hash,
+            opg,
+        })
+    }
+
+    fn to_bytes(&self) -> layered_store::Result<Vec<u8>> {
+        Ok([
+            self.level.to_be_bytes().as_slice(),
+            self.opg.to_forged_bytes().map_err(err_into)?.as_slice(),
+            Encoded::to_bytes(&self.opg.signature)
+                .map_err(err_into)?
+                .as_slice(),
+        ]
+        .concat())
+    }
+}
+
+/// Hashes of delayed operations in the order of arrival
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DelayedQueue(pub Vec<OperationHash>);
+
+impl StoreType for DelayedQueue {
+    fn from_bytes(bytes: &[u8]) -> layered_store::Result<Self> {
+        let hashes = bytes
+            .chunks(32)
+            .map(|hash| OperationHash::from_bytes(hash).map_err(err_into))
+            .collect::<layered_store::Result<Vec<OperationHash>>>()?;
+        Ok(Self(hashes))
+    }
+
+    fn to_bytes(&self) -> layered_store::Result<Vec<u8>> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.0.len() * 32);
+        for hash in self.0.iter() {
+            bytes.extend(hash.to_bytes().map_err(err_into)?);
+        }
+        Ok(bytes)
+    }
+}
+
+fn get_delayed_queue<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+) -> Result<DelayedQueue> {
+    Ok(context.get(DELAYED_QUEUE_PATH.into())?.unwrap_or_default())
+}
+
+pub fn get_delayed_timeout<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+) -> Result<i32> {
+    let timeout: Option<i64> = context.get(DELAYED_TIMEOUT_PATH.into())?;
+    match timeout {
+        Some(timeout) => timeout
+            .try_into()
+            .map_err(|_| internal_error!("Invalid delayed timeout: {}", timeout)),
+        None => Ok(DEFAULT_DELAYED_TIMEOUT),
+    }
+}
+
+/// Queues the operation until the sequencer includes it or the timeout expires.
+/// Only operations with a live branch and a valid signature are accepted, instructions
+/// executed by the wallet validation are added to `steps` even if the operation is rejected.
+/// Returns `false` if the operation is already queued.
+pub fn delay_operation<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    hash: OperationHash,
+    opg: SignedOperation,
+    level: i32,
+    steps: &mut usize,
+) -> Result<bool> {
+    let path = delayed_operation_path(&hash);
+    if context.has(path.clone())? {
+        return Ok(false);
+    }
+
+    let mut queue = get_delayed_queue(context)?;
+    if queue.0.len() >= MAX_DELAYED_OPERATIONS {
+        return Err(Error::DelayedQueueFull {
+            max: MAX_DELAYED_OPERATIONS,
+        });
+    }
+
+    let (valid, spent) = check_signature_and_branch(context, &opg)?;
+    // Wallet checks run the contract code, its side effects are discarded
+    context.rollback();
+    *steps += spent;
+    if !valid {
+        return Err(Error::InvalidDelayedOperation {
+            hash: hash.value().into(),
+        });
+    }
+
+    queue.0.push(hash.clone());
+    context.set(DELAYED_QUEUE_PATH.into(), Some(queue))?;
+    context.set(path, Some(DelayedOperation { level, hash, opg }))?;
+    context.commit()?;
+    Ok(true)
+}
+
+/// Drops delayed operations that the sequencer has included on its own
+pub fn remove_delayed_operations<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    hashes: &[OperationHash],
+) -> Result<()> {
+    let mut queue = get_delayed_queue(context)?;
+    let len = queue.0.len();
+    queue.0.retain(|hash| !hashes.contains(hash));
+    if queue.0.len() == len {
+        return Ok(());
+    }
+
+    for hash in hashes.iter() {
+        context.set::<DelayedOperation>(delayed_operation_path(hash), None)?;
+    }
+    context.set(DELAYED_QUEUE_PATH.into(), Some(queue))?;
+    context.commit()?;
+    Ok(())
+}
+
+/// Removes from the queue the operations received at least `timeout` levels ago,
+/// and returns them in the order of arrival so that they are force-applied
+pub fn take_expired_operations<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    level: i32,
+) -> Result<Vec<(OperationHash, SignedOperation)>> {
+    let timeout = get_delayed_timeout(context)?;
+    let mut queue = get_delayed_queue(context)?;
+
+    let mut expired: Vec<(OperationHash, SignedOperation)> = Vec::new();
+    let mut taken = 0;
+    for hash in queue.0.iter() {
+        let path = delayed_operation_path(hash);
+        match context.get::<DelayedOperation>(path.clone())? {
+            Some(delayed) if level - delayed.level >= timeout => {
+                context.set::<DelayedOperation>(path, None)?;
+                expired.push((delayed.hash, delayed.opg));
+            }
+            // The queue is in the order of arrival, so the next ones are not expired either
+            Some(_) => break,
+            None => {}
+        }
+        taken += 1;
+    }
+
+    if taken > 0 {
+        queue.0.drain(..taken);
+        context.set(DELAYED_QUEUE_PATH.into(), Some(queue))?;
+        context.commit()?;
+    }
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod test {
+    use layered_store::EphemeralStore;
+    use tezos_proto::context::TezosContext;
+
+    use super::*;
+    use crate::fixtures::{operation_live_blocks, signed_operation};
+
+    #[test]
+    fn test_delayed_operations() -> Result<()> {
+        let mut context = EphemeralStore::default();
+        let payload = signed_operation();
+        let (hash, opg) = parse_l2_operation(payload.as_slice())?;
+        let mut steps: usize = 0;
+
+        // operation branch has to be known to the rollup
+        assert!(matches!(
+            delay_operation(&mut context, hash.clone(), opg.clone(), 1, &mut steps),
+            Err(Error::InvalidDelayedOperation { .. })
+        ));
+        context.set_live_blocks(operation_live_blocks())?;
+        context.commit()?;
+
+        assert!(delay_operation(
+            &mut context,
+            hash.clone(),
+            opg.clone(),
+            1,
+            &mut steps
+        )?);
+        assert!(!delay_operation(
+            &mut context,
+            hash.clone(),
+            opg.clone(),
+            2,
+            &mut steps
+        )?);
+
+        let timeout = get_delayed_timeout(&mut context)?;
+        assert!(take_expired_operations(&mut context, timeout)?.is_empty());
+
+        let expired = take_expired_operations(&mut context, 1 + timeout)?;
+        assert_eq!(
+            vec![hash.clone()],
+            expired.into_iter().map(|(h, _)| h).collect::<Vec<_>>()
+        );
+        assert!(!context.has(delayed_operation_path(&hash))?);
+        assert!(get_delayed_queue(&mut context)?.0.is_empty());
+
+        // Operations included by the sequencer are not forced later
+        delay_operation(&mut context, hash.clone(), opg, 5, &mut steps)?;
+        remove_delayed_operations(&mut context, &[hash.clone()])?;
+        assert!(take_expired_operations(&mut context, 5 + timeout)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_delayed_operation_rejected() -> Result<()> {
+        let mut context = EphemeralStore::default();
+        let mut payload = signed_operation();
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+        let (hash, opg) = parse_l2_operation(payload.as_slice())?;
+        let mut steps: usize = 0;
+        context.set_live_blocks(operation_live_blocks())?;
+        context.commit()?;
+
+        assert!(matches!(
+            delay_operation(&mut context, hash.clone(), opg.clone(), 1, &mut steps),
+            Err(Error::InvalidDelayedOperation { .. })
+        ));
+        assert!(get_delayed_queue(&mut context)?.0.is_empty());
+
+        let queue = DelayedQueue(vec![hash.clone(); MAX_DELAYED_OPERATIONS]);
+        context.set(DELAYED_QUEUE_PATH.into(), Some(queue))?;
+        context.commit()?;
+        assert!(matches!(
+            delay_operation(&mut context, hash, opg, 1, &mut steps),
+            Err(Error::DelayedQueueFull { .. })
+        ));
+        Ok(())
+    }
+}
@@ -49,11 +49,153 @@ pub enum Error {
     UnexpectedL2OperationLength {
         length: usize,
     },
-    #[display(fmt = "InconsistentHeadLevel")]
-    InconsistentHeadLevel {
-        expected: i32,
+    #[display(fmt = "UnexpectedPayloadTag")]
+    UnexpectedPayloadTag {
+        tag: u8,
+    },
+    #[display(fmt = "UnexpectedKernelUpgradeLength")]
+    UnexpectedKernelUpgradeLength {
+        length: usize,
+    },
+    #[display(fmt = "UnexpectedChunkLength")]
+    UnexpectedChunkLength {
+        length: usize,
+    },
+    #[display(fmt = "InvalidChunkIndex")]
+    InvalidChunkIndex {
+        index: u16,
+        total: u16,
+    },
+    #[display(fmt = "TooManyChunks")]
+    TooManyChunks {
+        max: u16,
+        found: u16,
+    },
+    #[display(fmt = "TooManyPendingChunkSets")]
+    TooManyPendingChunkSets {
+        max: usize,
+    },
+    #[display(fmt = "ChunkedOperationHashMismatch")]
+    ChunkedOperationHashMismatch {
+        expected: String,
+        found: String,
+    },
+    #[display(fmt = "UnexpectedRevealHashLength")]
+    UnexpectedRevealHashLength {
+        length: usize,
+    },
+    AdminKeyMissing,
+    InvalidUpgradeSignature,
+    #[display(fmt = "UpgradeNonceInThePast")]
+    UpgradeNonceInThePast {
+        expected: u64,
+        found: u64,
+    },
+    #[display(fmt = "UnexpectedSealLength")]
+    UnexpectedSealLength {
+        length: usize,
+    },
+    #[display(fmt = "InconsistentInboxLevel")]
+    InconsistentInboxLevel {
+        last: i32,
         found: i32,
     },
+    #[display(fmt = "InvalidBatchTimestamp")]
+    InvalidBatchTimestamp {
+        min: i64,
+        max: i64,
+        found: i64,
+    },
+    #[display(fmt = "UnexpectedPublicKeyLength")]
+    UnexpectedPublicKeyLength {
+        length: usize,
+    },
+    #[display(fmt = "UnexpectedSignedBatchLength")]
+    UnexpectedSignedBatchLength {
+        length: usize,
+    },
+    #[display(fmt = "UnexpectedBatchKind")]
+    UnexpectedBatchKind {
+        kind: u8,
+    },
+    #[display(fmt = "UnexpectedHeaderSignatureLength")]
+    UnexpectedHeaderSignatureLength {
+        length: usize,
+    },
+    #[display(fmt = "UnknownSequencer")]
+    UnknownSequencer {
+        address: String,
+    },
+    InvalidSequencerSignature,
+    #[display(fmt = "SequencerNonceInThePast")]
+    SequencerNonceInThePast {
+        expected: u64,
+        found: u64,
+    },
+    #[display(fmt = "BatchTooLarge")]
+    BatchTooLarge {
+        max: usize,
+    },
+    #[display(fmt = "UnexpectedBlockSigner")]
+    UnexpectedBlockSigner {
+        hash: String,
+        found: String,
+    },
+    #[display(fmt = "DelayedQueueFull")]
+    DelayedQueueFull {
+        max: usize,
+    },
+    #[display(fmt = "InvalidDelayedOperation")]
+    InvalidDelayedOperation {
+        hash: String,
+    },
+    #[display(fmt = "UnexpectedCommitmentLength")]
+    UnexpectedCommitmentLength {
+        length: usize,
+    },
+    #[display(fmt = "CommitmentQueueFull")]
+    CommitmentQueueFull {
+        max: usize,
+    },
+    #[display(fmt = "UnknownCommitment")]
+    UnknownCommitment {
+        hash: String,
+    },
+    InvalidCommitmentSignature,
+    #[display(fmt = "EmptyCommitterBalance")]
+    EmptyCommitterBalance {
+        address: String,
+    },
+    #[display(fmt = "CommitmentPending")]
+    CommitmentPending {
+        address: String,
+    },
+    #[display(fmt = "CommitterMismatch")]
+    CommitterMismatch {
+        hash: String,
+    },
+    #[display(fmt = "UnexpectedSponsorSignatureLength")]
+    UnexpectedSponsorSignatureLength {
+        length: usize,
+    },
+    #[display(fmt = "UnknownSponsor")]
+    UnknownSponsor {
+        address: String,
+    },
+    InvalidSponsorSignature,
+    #[display(fmt = "UnexpectedOracleUpdateLength")]
+    UnexpectedOracleUpdateLength {
+        length: usize,
+    },
+    #[display(fmt = "InvalidOracleKey")]
+    InvalidOracleKey {
+        key: String,
+    },
+    #[display(fmt = "InvalidOracleTimestamp")]
+    InvalidOracleTimestamp {
+        max: i64,
+        found: i64,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -81,6 +223,8 @@ impl_from_error!(tezos_operation::Error);
 impl_from_error!(tezos_core::Error);
 impl_from_error!(tezos_smart_rollup_host::runtime::RuntimeError);
 impl_from_error!(tezos_smart_rollup_host::path::PathError);
+impl_from_error!(kernel_io::Error);
+impl_from_error!(layered_store::Error);
 
 impl From<tezos_proto::Error> for Error {
     fn from(error: tezos_proto::Error) -> Self {
@@ -0,0 +1,27 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use tezos_proto::context::head::LiveBlocks;
+
+/// Reveal and transaction signed by tz1grSQDByRpnVs7sPtaprNZRp531ZKz6Jmm
+/// (forged operation followed by the signature)
+pub const SIGNED_OPERATION: &str = "62fd30ac16979d9b88aca559e8fd8b97abd2519bebe09ad8a269d60df0b17ddc6b\
+    00e8b36c80efb51ec85a14562426049aa182a3ce38f902e18a18e807000017143f62ff9c2f41b30ee00b8c64d233fda43adf05\
+    eb829cfd2e733ee9a8f44b6c00e8b36c80efb51ec85a14562426049aa182a3ce3800e28a18ab0b8102c0843d00006b82198cb1\
+    79e8306c1bedd08f12dc863f32888600b2014573fd63d27895841ea6ca9d45e23e1e3b836298801b5e390b3b0a0b412003af89\
+    c08e63b6d8cf6847300e627c4ce0882ce4e2b842295309de3a0bd6260f";
+
+pub fn signed_operation() -> Vec<u8> {
+    hex::decode(SIGNED_OPERATION).unwrap()
+}
+
+/// Operation branch has to be known to the rollup
+pub fn operation_live_blocks() -> LiveBlocks {
+    LiveBlocks(
+        vec!["BLTssqiWSEK2Vp1HWYfb4hF58Tt1rWyznr8jcBSk9c32o3te5xc"
+            .try_into()
+            .unwrap()]
+        .into(),
+    )
+}
@@ -3,19 +3,202 @@
 // SPDX-License-Identifier: MIT
 
 use kernel_io::{
-    inbox::{read_inbox, InboxMessage},
+    inbox::{read_inbox, InboxMessage, Transfer},
+    reveal::reveal_messages,
     KernelStore, KernelStoreAsHost,
 };
-use tezos_core::types::encoded::{ChainId, Encoded, OperationHash};
+use tezos_core::types::encoded::{ChainId, Encoded, ImplicitAddress, OperationHash};
 use tezos_operation::operations::SignedOperation;
-use tezos_proto::{batcher::apply_batch, context::TezosContext};
-use tezos_smart_rollup_core::SmartRollupCore;
-use tezos_smart_rollup_host::runtime::Runtime;
+use tezos_proto::{
+    batcher::{apply_next_operation, begin_batch, finalize_batch},
+    context::{batch::PendingBatch, head::Head, TezosContext},
+    executor::deposit::Deposit,
+};
+use tezos_smart_rollup_core::{SmartRollupCore, PREIMAGE_HASH_SIZE};
+use tezos_smart_rollup_host::{metadata::RollupMetadata, path::RefPath, runtime::Runtime};
+
+use crate::{
+    chunks::{add_chunk, expire_chunks},
+    commitments::{
+        accept_commitment, expire_commitments, has_revealed_operations, is_committed,
+        reveal_committed_operation, take_revealed_operations,
+    },
+    delayed::{delay_operation, remove_delayed_operations, take_expired_operations},
+    internal_error,
+    levels::{
+        batch_timestamp, begin_inbox_level, get_inbox_level, index_legacy_head, record_batch,
+        set_inbox_level, InboxLevel,
+    },
+    oracle::accept_oracle_update,
+    outbox::flush_outbox,
+    payload::{parse_l2_operation, BatchContents, SignedBatch, TezosPayload},
+    sequencer::{accept_batch, accept_header_signature, is_permissioned, record_batcher},
+    sponsor::accept_sponsor_signature,
+    upgrade::{apply_pending_upgrade, schedule_upgrade},
+    Result,
+};
+
+fn reveal_operations<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    root_hash: &[u8; PREIMAGE_HASH_SIZE],
+) -> Result<Vec<(OperationHash, SignedOperation)>> {
+    reveal_messages(context.as_host(), root_hash)?
+        .iter()
+        .map(|message| parse_l2_operation(message.as_slice()))
+        .collect()
+}
+
+/// Number of operation contents validated or applied (and scheduled calls executed)
+/// in a single kernel run.
+/// Leaves enough room under the PVM tick limit for reading the inbox and persisting changes.
+pub const OPERATIONS_BUDGET: usize = 1000;
+
+/// Michelson instructions executed by wallet validations that count as one operation content
+pub const STEPS_PER_OPERATION: usize = 10000;
+
+fn steps_cost(steps: usize) -> usize {
+    steps.div_ceil(STEPS_PER_OPERATION)
+}
+
+fn contents_count(operations: &[(OperationHash, SignedOperation)]) -> usize {
+    operations.iter().map(|(_, opg)| opg.contents.len()).sum()
+}
+
+const REBOOT_PATH: RefPath = RefPath::assert_from(b"/kernel/env/reboot");
+
+/// Kernel state written along with or after the block header, not committed to the context hash
+const BOOKKEEPING_PREFIXES: &[&str] = &[
+    "/inbox/",
+    "/levels/",
+    "/batchers/",
+    "/signatures/",
+    "/kernel/",
+];
+
+/// Merkle filter of the kernel storage, extends the one of the context
+fn is_merkle_key(key: &str) -> bool {
+    tezos_proto::context::is_merkle_key(key)
+        && !BOOKKEEPING_PREFIXES
+            .iter()
+            .any(|prefix| key.starts_with(prefix))
+}
+
+/// Applies batch operations until the operations budget is exhausted.
+/// Returns the new head, or `None` if the batch is saved to be continued after reboot.
+pub fn process_batch<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    mut batch: PendingBatch,
+    spent: &mut usize,
+    budget: usize,
+) -> Result<Option<Head>> {
+    while !batch.is_complete() {
+        // At least one operation is applied per run, so that the batch always makes progress
+        if *spent >= budget && *spent > 0 {
+            context.set_pending_batch(Some(batch))?;
+            context.commit()?;
+            return Ok(None);
+        }
+        *spent += apply_next_operation(context, &mut batch)?;
+    }
+
+    let batcher = batch.batcher.clone();
+    let head = finalize_batch(context, batch)?;
+    if let Some(batcher) = batcher {
+        record_batcher(context, &head.hash, batcher)?;
+    }
+    context.set_pending_batch(None)?;
+    context.commit()?;
+    Ok(Some(head))
+}
+
+/// Operations and deposits collected for the next L2 block
+struct BlockContents {
+    timestamp: i64,
+    operations: Vec<(OperationHash, SignedOperation)>,
+    /// Delayed operations forced by the kernel, included regardless of their branch
+    forced: Vec<OperationHash>,
+    deposits: Vec<Deposit>,
+    /// Sequencer that signed the batch, `None` for unsigned batches
+    batcher: Option<ImplicitAddress>,
+}
+
+fn begin_and_process_batch<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    head: Head,
+    contents: BlockContents,
+    spent: &mut usize,
+) -> Result<Option<Head>> {
+    let mut batch = begin_batch(
+        context,
+        head,
+        contents.timestamp,
+        contents.operations,
+        &contents.forced,
+        contents.deposits,
+        false,
+    )?;
+    batch.batcher = contents.batcher;
+    // Scheduled calls and wallet validations at the beginning of the batch count towards the budget
+    let scheduled = context
+        .get_scheduled_receipts(batch.prev_head.level + 1)?
+        .len();
+    *spent += batch
+        .operations
+        .iter()
+        .map(|opg| opg.origin.contents.len())
+        .sum::<usize>()
+        + scheduled
+        + steps_cost(batch.steps);
+    process_batch(context, batch, spent, OPERATIONS_BUDGET)
+}
 
-use crate::{payload::TezosPayload, Error, Result};
+fn resume_batch<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    batch: PendingBatch,
+    spent: &mut usize,
+) -> Result<Option<Head>> {
+    match process_batch(context, batch, spent, OPERATIONS_BUDGET)? {
+        Some(head) => {
+            let mut inbox = get_inbox_level(context)?
+                .ok_or_else(|| internal_error!("Inbox level is not started"))?;
+            record_batch(context, &mut inbox, &head)?;
+            context.log(format!("Batch applied: {}", head));
+            Ok(Some(head))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Makes sure that a resumed batch failing with an error does not fail every next run:
+/// the batch is cut at the operation it was resumed from, so that the next run produces
+/// the block out of the operations applied by previous runs. A batch failing to finalize is dropped.
+fn cut_failed_batch<Host: SmartRollupCore>(context: &mut KernelStore<Host>) -> Result<()> {
+    let mut batch = match context.get_pending_batch()? {
+        Some(batch) => batch,
+        None => return Ok(()),
+    };
+    if batch.is_complete() {
+        context.log("Pending batch dropped".into());
+        context.set_pending_batch(None)?;
+    } else {
+        context.log(format!(
+            "Pending batch cut: {}/{}",
+            batch.cursor,
+            batch.operations.len()
+        ));
+        batch.operations.truncate(batch.cursor);
+        context.set_pending_batch(Some(batch))?;
+    }
+    context.commit()?;
+    context.as_mut().persist()?;
+    Ok(())
+}
 
 pub fn kernel_run<Host: SmartRollupCore>(host: &mut Host) {
     let mut context = KernelStore::attach(host);
+    context.set_merkle_filter(is_merkle_key);
+    // Discard leftovers of a run that was interrupted before persisting changes
+    context.clear();
 
     let metadata = Runtime::reveal_metadata(context.as_host());
     let mut head = context.get_head().expect("Failed to get head");
@@ -24,72 +207,506 @@ pub fn kernel_run<Host: SmartRollupCore>(host: &mut Host) {
 
     context.log(format!("Kernel invoked, prev head: {}", head));
 
+    index_legacy_head(&mut context, &head, metadata.origination_level)
+        .expect("Failed to index legacy head");
+
+    let pending_batch = context
+        .get_pending_batch()
+        .expect("Failed to get pending batch");
+    // Deposits read from the inbox in this run, they cannot be read again
+    let mut received: Vec<Deposit> = Vec::new();
+    // Budget spent in this run, in operation contents
+    let mut spent: usize = 0;
+
+    let mut batch_failed = false;
+
+    let res: Result<Option<Head>> = match pending_batch {
+        Some(batch) => {
+            context.log(format!(
+                "Batch resumed: {}/{}",
+                batch.cursor,
+                batch.operations.len()
+            ));
+            match resume_batch(&mut context, batch, &mut spent) {
+                // Batch might have been sealed in the middle of the level
+                Ok(Some(head)) => {
+                    read_level(&mut context, &metadata, head, &mut received, &mut spent)
+                }
+                Ok(None) => Ok(None),
+                Err(err) => {
+                    batch_failed = true;
+                    Err(err)
+                }
+            }
+        }
+        None => read_level(&mut context, &metadata, head, &mut received, &mut spent),
+    };
+
+    match res {
+        Ok(Some(_)) => {
+            // Upgrade is applied after the batch, so that it takes effect on the next reboot
+            match apply_pending_upgrade(&mut context) {
+                Ok(Some(nonce)) => context.log(format!("Kernel upgrade installed: #{}", nonce)),
+                Ok(None) => {}
+                Err(err) => context.log(format!("Kernel upgrade rejected: {}", err.format())),
+            }
+            context
+                .as_mut()
+                .persist()
+                .expect("Failed to persist changes");
+            send_outbox(&mut context);
+            context.log(format!("Kernel yields"));
+        }
+        Ok(None) => {
+            // Batch state is persisted along with the changes made so far,
+            // the next run resumes the batch or continues reading the inbox
+            context
+                .as_mut()
+                .persist()
+                .expect("Failed to persist changes");
+            send_outbox(&mut context);
+            Runtime::store_write(context.as_host(), &REBOOT_PATH, &[0u8], 0)
+                .expect("Failed to request reboot");
+            context.log("Run suspended, kernel reboots".into());
+        }
+        Err(err) => {
+            context.log(err.format());
+            context.clear();
+            drain_deposits(&mut context, &metadata, &mut received);
+            if batch_failed {
+                if let Err(err) = cut_failed_batch(&mut context) {
+                    context.log(format!("Failed to cut pending batch: {}", err.format()));
+                }
+            }
+            // Changes are discarded, but the tickets are already held by the rollup
+            match keep_deposits(&mut context, received) {
+                Ok(0) => {}
+                Ok(count) => context.log(format!("Deposits kept pending: {}", count)),
+                Err(err) => context.log(format!("Failed to keep deposits: {}", err.format())),
+            }
+        }
+    }
+}
+
+/// L1 calls go to the outbox only once the changes that produced them are persisted
+fn send_outbox<Host: SmartRollupCore>(context: &mut KernelStore<Host>) {
+    match flush_outbox(context) {
+        Ok(0) => {}
+        Ok(count) => context.log(format!("Messages sent to the outbox: {}", count)),
+        Err(err) => context.log(format!("Outbox flush failed: {}", err.format())),
+    }
+}
+
+/// Persists the deposits received in a failed run, so that they are credited in the next block
+fn keep_deposits<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    received: Vec<Deposit>,
+) -> Result<usize> {
+    if received.is_empty() {
+        return Ok(0);
+    }
+    let count = received.len();
+    let mut deposits = context.get_pending_deposits()?;
+    deposits.extend(received);
+    context.set_pending_deposits(deposits)?;
+    context.commit()?;
+    context.as_mut().persist()?;
+    Ok(count)
+}
+
+/// Parses a transfer addressed to the rollup into a deposit
+fn receive_transfer<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    metadata: &RollupMetadata,
+    transfer: Transfer,
+) -> Option<Deposit> {
+    if transfer.destination != metadata.raw_rollup_address {
+        context.log(format!("Foreign transfer from {}", transfer.sender.value()));
+        return None;
+    }
+    match Deposit::from_micheline(transfer.payload, transfer.sender, transfer.source) {
+        Ok(deposit) => {
+            context.log(format!(
+                "Deposit pending: {} to {}",
+                deposit.amount,
+                deposit.receiver.value()
+            ));
+            Some(deposit)
+        }
+        Err(err) => {
+            context.log(format!("Transfer rejected: {}", err.format()));
+            None
+        }
+    }
+}
+
+/// Reads the rest of the level after a failure: the messages cannot be read again,
+/// so the deposits are collected (the tickets are already held by the rollup),
+/// while the other messages are dropped
+fn drain_deposits<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    metadata: &RollupMetadata,
+    received: &mut Vec<Deposit>,
+) {
+    loop {
+        match read_inbox(context.as_host(), &metadata.raw_rollup_address[..4]) {
+            Ok(InboxMessage::Transfer(transfer)) => {
+                if let Some(deposit) = receive_transfer(context, metadata, transfer) {
+                    received.push(deposit);
+                }
+            }
+            Ok(InboxMessage::EndBlock(_)) | Ok(InboxMessage::NoMoreData) => break,
+            Ok(_) => context.log("Message dropped after failure".into()),
+            Err(err) => context.log(err.to_string()),
+        }
+    }
+}
+
+fn current_inbox(inbox: &mut Option<InboxLevel>) -> Result<&mut InboxLevel> {
+    inbox
+        .as_mut()
+        .ok_or_else(|| internal_error!("Inbox level is not started"))
+}
+
+/// Produces the next L2 block out of the collected operations and deposits,
+/// returns `false` if the batch is suspended or the budget of the run is exhausted
+fn seal_batch<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    inbox: &mut InboxLevel,
+    head: &mut Head,
+    mut contents: BlockContents,
+    spent: &mut usize,
+) -> Result<bool> {
+    // Committed operations wait for their turn, nobody can include them earlier
+    let mut operations = take_revealed_operations(context)?;
+    for (hash, opg) in contents.operations.into_iter() {
+        if is_committed(context, &hash, &opg)? {
+            context.log(format!("Committed operation skipped: {}", hash.value()));
+        } else {
+            operations.push((hash, opg));
+        }
+    }
+    contents.operations = operations;
+
+    match begin_and_process_batch(context, head.clone(), contents, spent)? {
+        Some(new_head) => {
+            record_batch(context, inbox, &new_head)?;
+            context.log(format!("Batch applied: {}", new_head));
+            *head = new_head;
+            // Nothing is collected for the next block yet, so the inbox can be read after reboot
+            Ok(*spent < OPERATIONS_BUDGET)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Takes delayed operations that the sequencer has not included in time
+fn force_delayed_operations<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    level: i32,
+) -> Result<Vec<(OperationHash, SignedOperation)>> {
+    let operations = take_expired_operations(context, level)?;
+    for (hash, _) in operations.iter() {
+        context.log(format!("Delayed operation forced: {}", hash.value()));
+    }
+    Ok(operations)
+}
+
+/// Puts an operation posted by a user into the delayed inbox (permissioned mode)
+fn delay_inbox_operation<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    hash: OperationHash,
+    opg: SignedOperation,
+    level: i32,
+    spent: &mut usize,
+) {
+    let mut steps: usize = 0;
+    let res = delay_operation(context, hash.clone(), opg, level, &mut steps);
+    *spent += steps_cost(steps);
+    match res {
+        Ok(true) => context.log(format!("Operation delayed: {}", &hash.value())),
+        Ok(false) => {}
+        Err(err) => context.log(format!("Operation rejected: {}", err.format())),
+    }
+}
+
+/// Checks the batch signature and collects its operations, the batch nonce is consumed
+/// even if the batch is rejected afterwards. Returns the block timestamp and the sequencer.
+fn open_signed_batch<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    inbox: &InboxLevel,
+    head: &Head,
+    block_time: i64,
+    batch: SignedBatch,
+) -> Result<(i64, Vec<(OperationHash, SignedOperation)>, ImplicitAddress)> {
+    let batcher = accept_batch(context, &head.chain_id, &batch)?;
+    let timestamp = batch_timestamp(inbox, head, block_time, batch.timestamp)?;
+    let operations = match batch.contents {
+        BatchContents::Inline(operations) => operations,
+        BatchContents::Reveal(root_hash) => reveal_operations(context, &root_hash)?,
+    };
+    if contents_count(&operations) > OPERATIONS_BUDGET {
+        return Err(Error::BatchTooLarge {
+            max: OPERATIONS_BUDGET,
+        });
+    }
+    Ok((timestamp, operations, batcher))
+}
+
+/// Reads inbox messages until the end of the level.
+/// Operations are split into L2 blocks by seal messages, the remaining ones
+/// are batched at the end of the level (there is at least one L2 block per L1 level).
+/// Returns `None` if a batch is suspended to be continued after reboot.
+fn read_level<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    metadata: &RollupMetadata,
+    mut head: Head,
+    received: &mut Vec<Deposit>,
+    spent: &mut usize,
+) -> Result<Option<Head>> {
+    let block_time = context.get_config()?.block_time();
+    let permissioned = is_permissioned(context)?;
+    let mut inbox = get_inbox_level(context)?;
     let mut batch_payload: Vec<(OperationHash, SignedOperation)> = Vec::new();
-    let res: Result<()> = loop {
+    // Deposits kept by a failed run go to the first block
+    let mut deposits = context.get_pending_deposits()?;
+    if !deposits.is_empty() {
+        context.set_pending_deposits(Vec::new())?;
+        context.commit()?;
+    }
+    loop {
+        // Operations collected over a level are split into blocks that fit into a run
+        if contents_count(&batch_payload) >= OPERATIONS_BUDGET {
+            let inbox = current_inbox(&mut inbox)?;
+            let contents = BlockContents {
+                timestamp: batch_timestamp(inbox, &head, block_time, None)?,
+                operations: std::mem::take(&mut batch_payload),
+                forced: vec![],
+                deposits: std::mem::take(&mut deposits),
+                batcher: None,
+            };
+            if !seal_batch(context, inbox, &mut head, contents, spent)? {
+                return Ok(None);
+            }
+        }
         match read_inbox(context.as_host(), &metadata.raw_rollup_address[..4]) {
             Ok(InboxMessage::BeginBlock(inbox_level)) => {
-                // Origination level is the one before kernel is first time invoked
-                // We assume single rollup block per inbox block here
-                // Note that head level is the one prior to the current block
-                let expected = inbox_level - metadata.origination_level as i32 - 2;
-                if head.level != expected {
-                    break Err(Error::InconsistentHeadLevel {
-                        expected,
-                        found: head.level,
-                    });
-                }
+                inbox = Some(begin_inbox_level(context, inbox_level)?);
             }
             Ok(InboxMessage::LevelInfo(info)) => {
-                head.timestamp = info.predecessor_timestamp;
+                let inbox = current_inbox(&mut inbox)?;
+                inbox.timestamp = info.predecessor_timestamp;
+                set_inbox_level(context, inbox)?;
+            }
+            Ok(InboxMessage::Transfer(transfer)) => {
+                if let Some(deposit) = receive_transfer(context, metadata, transfer) {
+                    received.push(deposit.clone());
+                    deposits.push(deposit);
+                }
+            }
+            Ok(InboxMessage::Payload(TezosPayload::Operation { hash, opg })) if permissioned => {
+                let level = current_inbox(&mut inbox)?.level;
+                delay_inbox_operation(context, hash, opg, level, spent);
+            }
+            Ok(InboxMessage::Payload(TezosPayload::Chunk(chunk))) if permissioned => {
+                // Large operations can be force-included as well
+                let level = current_inbox(&mut inbox)?.level;
+                let hash = chunk.hash.clone();
+                match add_chunk(context, chunk, level) {
+                    Ok(Some((hash, opg))) => {
+                        delay_inbox_operation(context, hash, opg, level, spent)
+                    }
+                    Ok(None) => context.log(format!("Chunk received: {}", &hash.value())),
+                    Err(err) => context.log(format!("Chunk rejected: {}", err.format())),
+                }
+            }
+            Ok(InboxMessage::Payload(payload)) if permissioned && payload.is_unsigned() => {
+                context.log("Unsigned message ignored (permissioned mode)".into());
             }
             Ok(InboxMessage::Payload(TezosPayload::Operation { hash, opg })) => {
                 context.log(format!("Operation pending: {}", &hash.value()));
                 batch_payload.push((hash, opg));
             }
+            Ok(InboxMessage::Payload(TezosPayload::Upgrade(upgrade))) => {
+                let nonce = upgrade.nonce;
+                match schedule_upgrade(context, upgrade) {
+                    Ok(()) => context.log(format!("Kernel upgrade pending: #{}", nonce)),
+                    Err(err) => context.log(format!("Kernel upgrade rejected: {}", err.format())),
+                }
+            }
+            Ok(InboxMessage::Payload(TezosPayload::Chunk(chunk))) => {
+                let level = current_inbox(&mut inbox)?.level;
+                let hash = chunk.hash.clone();
+                match add_chunk(context, chunk, level) {
+                    Ok(Some((hash, opg))) => {
+                        context.log(format!("Operation pending: {}", &hash.value()));
+                        batch_payload.push((hash, opg));
+                    }
+                    Ok(None) => context.log(format!("Chunk received: {}", &hash.value())),
+                    Err(err) => context.log(format!("Chunk rejected: {}", err.format())),
+                }
+            }
+            Ok(InboxMessage::Payload(TezosPayload::Seal { timestamp })) => {
+                let inbox = current_inbox(&mut inbox)?;
+                match batch_timestamp(inbox, &head, block_time, timestamp) {
+                    Ok(timestamp) => {
+                        let contents = BlockContents {
+                            timestamp,
+                            operations: std::mem::take(&mut batch_payload),
+                            forced: vec![],
+                            deposits: std::mem::take(&mut deposits),
+                            batcher: None,
+                        };
+                        if !seal_batch(context, inbox, &mut head, contents, spent)? {
+                            return Ok(None);
+                        }
+                    }
+                    // Operations stay pending and go to the next batch
+                    Err(err) => context.log(format!("Seal rejected: {}", err.format())),
+                }
+            }
+            Ok(InboxMessage::Payload(TezosPayload::SignedBatch(batch))) => {
+                let inbox = current_inbox(&mut inbox)?;
+                match open_signed_batch(context, inbox, &head, block_time, batch) {
+                    Ok((timestamp, payload, batcher)) => {
+                        context.log(format!("Signed batch received: {}", batcher.value()));
+                        let hashes: Vec<OperationHash> =
+                            payload.iter().map(|(hash, _)| hash.clone()).collect();
+                        if let Err(err) = remove_delayed_operations(context, &hashes) {
+                            context.rollback();
+                            context.log(format!("Delayed operations kept: {}", err.format()));
+                        }
+                        // Expired delayed operations go ahead of the sequencer ones
+                        let mut operations = force_delayed_operations(context, inbox.level)?;
+                        let forced = operations.iter().map(|(hash, _)| hash.clone()).collect();
+                        operations.extend(payload);
+                        let contents = BlockContents {
+                            timestamp,
+                            operations,
+                            forced,
+                            deposits: std::mem::take(&mut deposits),
+                            batcher: Some(batcher),
+                        };
+                        if !seal_batch(context, inbox, &mut head, contents, spent)? {
+                            return Ok(None);
+                        }
+                    }
+                    Err(err) => context.log(format!("Signed batch rejected: {}", err.format())),
+                }
+            }
+            Ok(InboxMessage::Payload(TezosPayload::Commitment(commitment))) => {
+                let level = current_inbox(&mut inbox)?.level;
+                match accept_commitment(context, &head.chain_id, &commitment, level) {
+                    Ok(true) => {
+                        context.log(format!("Operation committed: {}", commitment.hash.value()))
+                    }
+                    Ok(false) => {}
+                    Err(err) => context.log(format!("Commitment rejected: {}", err.format())),
+                }
+            }
+            Ok(InboxMessage::Payload(TezosPayload::CommittedOperation(operation))) => {
+                match reveal_committed_operation(context, operation) {
+                    Ok(hash) => context.log(format!("Operation revealed: {}", hash.value())),
+                    Err(err) => context.log(format!("Operation rejected: {}", err.format())),
+                }
+            }
+            Ok(InboxMessage::Payload(TezosPayload::HeaderSignature(signature))) => {
+                match accept_header_signature(context, &head.chain_id, &signature) {
+                    Ok(()) => context.log(format!("Block signed: {}", signature.hash.value())),
+                    Err(err) => context.log(format!("Block signature rejected: {}", err.format())),
+                }
+            }
+            Ok(InboxMessage::Payload(TezosPayload::SponsorSignature(signature))) => {
+                match accept_sponsor_signature(context, &head.chain_id, &signature) {
+                    Ok(sponsor) => context.log(format!(
+                        "Operation sponsored: {} by {}",
+                        signature.hash.value(),
+                        sponsor.value()
+                    )),
+                    Err(err) => context.log(format!("Sponsorship rejected: {}", err.format())),
+                }
+            }
+            Ok(InboxMessage::Payload(TezosPayload::OracleUpdate(update))) => {
+                let inbox = current_inbox(&mut inbox)?;
+                match accept_oracle_update(context, inbox, &head, &update) {
+                    Ok(updated) => context.log(format!("Oracle feeds updated: {}", updated)),
+                    Err(err) => context.log(format!("Oracle update rejected: {}", err.format())),
+                }
+            }
             Ok(InboxMessage::EndBlock(_)) => {
-                match apply_batch(&mut context, head.clone(), batch_payload, false) {
-                    Ok(new_head) => {
-                        head = new_head;
-                        context.log(format!("Batch applied: {}", head));
-                        break Ok(());
+                let inbox = current_inbox(&mut inbox)?;
+                for hash in expire_chunks(context, inbox.level)? {
+                    context.log(format!("Chunks expired: {}", hash.value()));
+                }
+                let mut forced: Vec<OperationHash> = Vec::new();
+                if permissioned {
+                    // The level produces a block even if the sequencer is silent
+                    let operations = force_delayed_operations(context, inbox.level)?;
+                    forced.extend(operations.iter().map(|(hash, _)| hash.clone()));
+                    batch_payload.extend(operations);
+                }
+                if !batch_payload.is_empty()
+                    || !deposits.is_empty()
+                    || inbox.batches == 0
+                    || has_revealed_operations(context)?
+                {
+                    let contents = BlockContents {
+                        timestamp: batch_timestamp(inbox, &head, block_time, None)?,
+                        operations: std::mem::take(&mut batch_payload),
+                        forced,
+                        deposits: std::mem::take(&mut deposits),
+                        batcher: None,
+                    };
+                    if !seal_batch(context, inbox, &mut head, contents, spent)? {
+                        return Ok(None);
                     }
-                    Err(err) => break Err(err.into()),
                 }
+                for hash in expire_commitments(context, inbox.level)? {
+                    context.log(format!("Commitment expired: {}", hash.value()));
+                }
+                return Ok(Some(head));
+            }
+            Ok(InboxMessage::NoMoreData) => {
+                // Deposits are credited once the level is closed
+                context.set_pending_deposits(deposits)?;
+                context.commit()?;
+                // The level was closed by a run suspended at its end
+                if let Some(inbox) = &inbox {
+                    for hash in expire_commitments(context, inbox.level)? {
+                        context.log(format!("Commitment expired: {}", hash.value()));
+                    }
+                }
+                return Ok(Some(head));
             }
-            Ok(InboxMessage::NoMoreData) => break Ok(()),
             Ok(InboxMessage::Foreign(id)) => context.log(format!("Foreign message #{}", id)),
             Ok(InboxMessage::Unknown(id)) => context.log(format!("Unknown message #{}", id)),
             Err(err) => context.log(err.to_string()),
         }
-    };
-
-    match res {
-        Ok(_) => {
-            context
-                .as_mut()
-                .persist()
-                .expect("Failed to persist changes");
-            context.log(format!("Kernel yields"));
-        }
-        Err(err) => {
-            context.log(err.format());
-            context.clear();
-        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::io::Write;
+    use std::{collections::VecDeque, io::Write};
 
     use super::*;
 
     use hex;
     use kernel_io::{KernelStore, KernelStoreAsHost};
+    use tezos_core::types::{encoded::PublicKey, mutez::Mutez};
     use tezos_data_encoding::enc::{BinResult, BinWriter};
-    use tezos_proto::context::TezosContext;
+    use tezos_proto::context::head::LiveBlocks;
+    use tezos_rpc::models::balance_update::{BalanceUpdate, Contract, Origin};
     use tezos_smart_rollup_mock::MockHost;
 
+    use crate::{
+        delayed::DELAYED_TIMEOUT_PATH,
+        fixtures::{operation_live_blocks, signed_operation, SIGNED_OPERATION},
+        levels::{l1_level_path, l2_level_path},
+        sequencer::{block_batcher_path, sequencer_nonce_path, Sequencers, SEQUENCERS_PATH},
+        upgrade::{PENDING_UPGRADE_PATH, UPGRADE_NONCE_PATH},
+    };
+
     struct ExternalMessage(Vec<u8>);
 
     impl ExternalMessage {
@@ -113,14 +730,17 @@ mod test {
         let mut context = KernelStore::<MockHost>::attach(&mut host);
         // default rollup address is sr163Lv22CdE8QagCwf48PWDTquk6isQwv57Head
         // chain_id is first 4 bytes (00000000)
+        // then goes the payload tag (00 for operations)
         // the rest is the operation payload
-        let message = ExternalMessage::from_hex(
-            "0000000062fd30ac16979d9b88aca559e8fd8b97abd2519bebe09ad8a269d60df0b17ddc6b\
-            00e8b36c80efb51ec85a14562426049aa182a3ce38f902e18a18e807000017143f62ff9c2f41b30ee00b8c64d233fda43adf05\
-            eb829cfd2e733ee9a8f44b6c00e8b36c80efb51ec85a14562426049aa182a3ce3800e28a18ab0b8102c0843d00006b82198cb1\
-            79e8306c1bedd08f12dc863f32888600b2014573fd63d27895841ea6ca9d45e23e1e3b836298801b5e390b3b0a0b412003af89\
-            c08e63b6d8cf6847300e627c4ce0882ce4e2b842295309de3a0bd6260f",
-        );
+        let message = ExternalMessage::from_hex(&format!("0000000000{}", SIGNED_OPERATION));
+
+        // operation branch has to be known to the rollup
+        context.set_live_blocks(operation_live_blocks())?;
+        context.commit()?;
+        context
+            .as_mut()
+            .persist()
+            .expect("Failed to persist changes");
 
         context.as_host().run_level(|_| {}); // Add StartOfLevel & InfoPerLevel
         context.as_host().add_external(message);
@@ -141,4 +761,418 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn seal_batches() -> Result<()> {
+        let mut host = MockHost::default();
+        let mut context = KernelStore::<MockHost>::attach(&mut host);
+        // chain_id (00000000), payload tag (04 for seals), no explicit timestamp
+        let seal = ExternalMessage::from_hex("0000000004");
+        let message = ExternalMessage::from_hex(&format!("0000000000{}", SIGNED_OPERATION));
+
+        context.set_live_blocks(operation_live_blocks())?;
+        context.commit()?;
+        context
+            .as_mut()
+            .persist()
+            .expect("Failed to persist changes");
+
+        // first block is empty, the operation goes to the second one
+        context.as_host().run_level(|_| {});
+        context.as_host().add_external(seal);
+        context.as_host().add_external(message);
+        context.as_host().run_level(kernel_run);
+
+        let head = context.get_head()?;
+        assert_eq!(1, head.level);
+        assert_eq!(1, head.operations.len());
+
+        let inbox = get_inbox_level(&mut context)?.expect("Inbox level");
+        assert_eq!(2, inbox.batches);
+        assert_eq!(inbox.timestamp + 8 + 1, head.timestamp);
+
+        let l2_level: Option<i64> = context.get(l1_level_path(inbox.level))?;
+        assert_eq!(Some(1), l2_level);
+        let l1_level: Option<i64> = context.get(l2_level_path(0))?;
+        assert_eq!(Some(inbox.level as i64), l1_level);
+
+        // both blocks of the inbox level are reachable by their L2 level
+        let first = context.get_block_head(0)?.expect("First block");
+        assert!(first.operations.is_empty());
+        assert_eq!(
+            0,
+            context.get_batch_receipt(first.hash.value())?.header.level
+        );
+        assert_eq!(Some(head), context.get_block_head(1)?);
+        Ok(())
+    }
+
+    #[test]
+    fn signed_batch() -> Result<()> {
+        let mut host = MockHost::default();
+        let mut context = KernelStore::<MockHost>::attach(&mut host);
+        // unsigned messages are ignored once the allowlist is set
+        let seal = ExternalMessage::from_hex("0000000004");
+        // chain_id (00000000), payload tag (05 for signed batches), nonce (8 bytes),
+        // timestamp (8 bytes, zero for default), public key (33 bytes), kind (00 for inline),
+        // length-prefixed operations, signature (64 bytes) of the chain id and the fields above
+        let message = ExternalMessage::from_hex(&format!(
+            "000000000500000000000000010000000000000000\
+            008a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c00000000d7{}\
+            c53f501295709e1489c683bf7482c31d1e7edefae5d3af7b498fa5a7377ce04632da24877d0d94d7\
+            5b6bd248b38cb7a313f4c8e8272626deaea8298834352202",
+            SIGNED_OPERATION
+        ));
+        let replay = ExternalMessage(message.0.clone());
+
+        let sequencer: PublicKey = "edpkuhEcwoLysLvodRxQLzuM3AVZvCuT6koVkUahS53mNBdE8LbuGo"
+            .try_into()
+            .unwrap();
+        let address = "tz1c8PEDNfj6UxoQM2XCyfTHM5KbGGgoqDrH";
+        context.set(SEQUENCERS_PATH.into(), Some(Sequencers(vec![sequencer])))?;
+        context.set_live_blocks(operation_live_blocks())?;
+        context.commit()?;
+        context
+            .as_mut()
+            .persist()
+            .expect("Failed to persist changes");
+
+        context.as_host().run_level(|_| {});
+        context.as_host().add_external(seal);
+        context.as_host().add_external(message);
+        context.as_host().add_external(replay);
+        context.as_host().run_level(kernel_run);
+
+        let head = context.get_head()?;
+        assert_eq!(0, head.level);
+        assert_eq!(1, head.operations.len());
+
+        let receipt = context.get_batch_receipt(head.hash.value())?;
+        assert_eq!(Some(address), receipt.batcher.as_ref().map(|a| a.value()));
+
+        let nonce: Option<u64> = context.get(sequencer_nonce_path(address))?;
+        assert_eq!(Some(1), nonce);
+        let batcher: Option<ImplicitAddress> =
+            context.get(block_batcher_path(head.hash.value()))?;
+        assert_eq!(Some(address), batcher.as_ref().map(|a| a.value()));
+        Ok(())
+    }
+
+    #[test]
+    fn forced_inclusion() -> Result<()> {
+        let mut host = MockHost::default();
+        let mut context = KernelStore::<MockHost>::attach(&mut host);
+        // unsigned operations are delayed once the allowlist is set
+        let message = ExternalMessage::from_hex(&format!("0000000000{}", SIGNED_OPERATION));
+
+        let sequencer: PublicKey = "edpkuhEcwoLysLvodRxQLzuM3AVZvCuT6koVkUahS53mNBdE8LbuGo"
+            .try_into()
+            .unwrap();
+        context.set(SEQUENCERS_PATH.into(), Some(Sequencers(vec![sequencer])))?;
+        context.set(DELAYED_TIMEOUT_PATH.into(), Some(2i64))?;
+        context.set_live_blocks(operation_live_blocks())?;
+        context.commit()?;
+        context
+            .as_mut()
+            .persist()
+            .expect("Failed to persist changes");
+
+        context.as_host().run_level(|_| {});
+        context.as_host().add_external(message);
+        context.as_host().run_level(kernel_run);
+
+        // the sequencer is silent, levels still produce (empty) blocks
+        let head = context.get_head()?;
+        assert_eq!(0, head.level);
+        assert!(head.operations.is_empty());
+
+        context.as_host().run_level(kernel_run);
+        assert!(context.get_head()?.operations.is_empty());
+
+        // the sequencer produces enough blocks to expire the operation branch
+        context.set_live_blocks(LiveBlocks(VecDeque::new()))?;
+        context.commit()?;
+        context
+            .as_mut()
+            .persist()
+            .expect("Failed to persist changes");
+
+        // the operation is force-applied once the timeout expires
+        context.as_host().run_level(kernel_run);
+        let head = context.get_head()?;
+        assert_eq!(2, head.level);
+        assert_eq!(1, head.operations.len());
+        Ok(())
+    }
+
+    #[test]
+    fn suspend_batch() -> Result<()> {
+        let mut host = MockHost::default();
+        let mut context = KernelStore::<MockHost>::attach(&mut host);
+
+        // first batch runs migrations, their balance updates are saved along with the batch
+        let head = Head::default();
+        context.set_live_blocks(operation_live_blocks())?;
+        context.commit()?;
+
+        let operation = signed_operation();
+        let (hash, opg) = parse_l2_operation(operation.as_slice())?;
+
+        // validation exhausts the budget, so the batch is suspended right away
+        let batch = begin_batch(
+            &mut context,
+            head.clone(),
+            head.timestamp + 8,
+            vec![(hash.clone(), opg)],
+            &[],
+            vec![],
+            false,
+        )?;
+        assert!(process_batch(&mut context, batch, &mut 1, 1)?.is_none());
+
+        let batch = context.get_pending_batch()?.expect("Pending batch");
+        assert_eq!(0, batch.cursor);
+        assert_eq!(hash, batch.operations[0].hash);
+        assert_eq!(8, batch.balance_updates.len());
+
+        // resumed run applies at least one operation
+        let new_head = process_batch(&mut context, batch, &mut 0, 1)?.expect("Batch applied");
+        assert_eq!(head.level + 1, new_head.level);
+        assert_eq!(vec![hash], new_head.operations);
+        assert!(context.get_pending_batch()?.is_none());
+
+        let receipt = context.get_batch_receipt(new_head.hash.value())?;
+        let migrated = receipt
+            .balance_updates
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|update| {
+                matches!(
+                    update,
+                    BalanceUpdate::Contract(Contract {
+                        origin: Some(Origin::Migration),
+                        ..
+                    })
+                )
+            })
+            .count();
+        assert_eq!(8, migrated);
+        Ok(())
+    }
+
+    #[test]
+    fn cut_batch_after_failure() -> Result<()> {
+        let mut host = MockHost::default();
+        let mut context = KernelStore::<MockHost>::attach(&mut host);
+
+        let head = Head::default();
+        context.set_live_blocks(operation_live_blocks())?;
+        context.commit()?;
+
+        let operation = signed_operation();
+        let (hash, opg) = parse_l2_operation(operation.as_slice())?;
+        let batch = begin_batch(
+            &mut context,
+            head.clone(),
+            head.timestamp + 8,
+            vec![(hash, opg)],
+            &[],
+            vec![],
+            false,
+        )?;
+        assert!(process_batch(&mut context, batch, &mut 1, 1)?.is_none());
+        context.as_mut().persist()?;
+
+        // operations the batch was resumed from are cut, the rest of it is kept
+        cut_failed_batch(&mut context)?;
+        context.clear();
+        let batch = context.get_pending_batch()?.expect("Pending batch");
+        assert!(batch.is_complete());
+        assert!(batch.operations.is_empty());
+        assert_eq!(8, batch.balance_updates.len());
+
+        // batch failing to finalize is dropped
+        cut_failed_batch(&mut context)?;
+        context.clear();
+        assert!(context.get_pending_batch()?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn keep_deposits_after_failure() -> Result<()> {
+        let mut host = MockHost::default();
+        let mut context = KernelStore::<MockHost>::attach(&mut host);
+
+        let deposit = Deposit {
+            ticketer: "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm".try_into().unwrap(),
+            receiver: "tz1Qr9uevaimfiPS6X1otehsKrwvZjX7bsyL".try_into().unwrap(),
+            amount: Mutez::from(100u32),
+            sender: "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm".try_into().unwrap(),
+            source: "tz1Qr9uevaimfiPS6X1otehsKrwvZjX7bsyL".try_into().unwrap(),
+        };
+        context.set_bridge(deposit.ticketer.clone())?;
+        context.commit()?;
+        context
+            .as_mut()
+            .persist()
+            .expect("Failed to persist changes");
+
+        // changes of the failed run are discarded, received deposits are not
+        assert_eq!(1, keep_deposits(&mut context, vec![deposit.clone()])?);
+        context.clear();
+        assert_eq!(vec![deposit], context.get_pending_deposits()?);
+
+        context.as_host().run_level(|_| {}); // Add StartOfLevel & InfoPerLevel
+        context.as_host().run_level(kernel_run);
+
+        assert!(context.get_pending_deposits()?.is_empty());
+        assert_eq!(
+            Some(Mutez::from(100u32)),
+            context.get_balance("tz1Qr9uevaimfiPS6X1otehsKrwvZjX7bsyL")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reveal_batch() -> Result<()> {
+        let mut host = MockHost::default();
+        let operation = signed_operation();
+        // page format: tag (00 — contents), 4-byte length, payload
+        // payload is a list of operations, each one prefixed with its 4-byte length
+        let mut page = vec![0u8];
+        page.extend_from_slice(&(operation.len() as u32 + 4).to_be_bytes());
+        page.extend_from_slice(&(operation.len() as u32).to_be_bytes());
+        page.extend_from_slice(operation.as_slice());
+        let root_hash = hex::encode(host.set_preimage(page).as_ref());
+        assert_eq!(
+            "00a99d6c524c70bef7de3e369c5e5f25e886e1b925dccf7876a5f4e80e8da7fe5a",
+            root_hash
+        );
+
+        let mut context = KernelStore::<MockHost>::attach(&mut host);
+        // preimage roots are accepted from sequencers only: chain_id (00000000),
+        // payload tag (05 for signed batches), nonce, timestamp, public key, kind (01 for reveals),
+        // root hash (33 bytes), signature (64 bytes) of the chain id and the fields above
+        let message = ExternalMessage::from_hex(&format!(
+            "000000000500000000000000010000000000000000\
+            0003a107bff3ce10be1d70dd18e74bc09967e4d6309ba50d5f1ddc8664125531b801{}\
+            1fd28163acbcfd4b07ec867016a57e42a4bea7a613e03bae6ab0ee7f8429721b3d6e1d9cde66102e\
+            b167b8c6e157fb90fb2943367f860879a59d206093686506",
+            root_hash
+        ));
+
+        let sequencer: PublicKey = "edpktfpdouHjAze9TeFcihdpeMng7FSCWbY4BozpSffZ9z85nyyBBB"
+            .try_into()
+            .unwrap();
+        context.set(SEQUENCERS_PATH.into(), Some(Sequencers(vec![sequencer])))?;
+        context.set_live_blocks(operation_live_blocks())?;
+        context.commit()?;
+        context
+            .as_mut()
+            .persist()
+            .expect("Failed to persist changes");
+
+        context.as_host().run_level(|_| {});
+        context.as_host().add_external(message);
+        context.as_host().run_level(kernel_run);
+
+        let head = context.get_head()?;
+        assert_eq!(0, head.level);
+        assert_eq!(1, head.operations.len());
+        Ok(())
+    }
+
+    #[test]
+    fn kernel_upgrade() -> Result<()> {
+        let mut host = MockHost::default();
+        // page format: tag (00 — contents), 4-byte length, payload
+        let mut page = vec![0u8];
+        page.extend_from_slice(&(b"fake kernel".len() as u32).to_be_bytes());
+        page.extend_from_slice(b"fake kernel");
+        host.set_preimage(page);
+
+        let mut context = KernelStore::<MockHost>::attach(&mut host);
+        let admin_key: PublicKey = "edpktfpdouHjAze9TeFcihdpeMng7FSCWbY4BozpSffZ9z85nyyBBB"
+            .try_into()
+            .unwrap();
+        context.set_admin_key(admin_key)?;
+        context.commit()?;
+        context
+            .as_mut()
+            .persist()
+            .expect("Failed to persist changes");
+
+        // chain_id (00000000), payload tag (01 for upgrades),
+        // nonce (8 bytes), root hash (33 bytes), signature (64 bytes)
+        // of the default rollup address (20 zero bytes)
+        let message = ExternalMessage::from_hex(
+            "000000000101000000000000000100bbbcee9bed14d5ce9d9024610c3ba81d79f353aecd062cb31083\
+            b79db6559e04c3e347825db399db12ab5d83f6d0c7de3076e9b44b144be411b31603b3eae00711ba0b\
+            ed78da68ccaa2c5047fb3fe10cc91a98128632751abd8958741dd2e602",
+        );
+
+        // Same upgrade signed for another rollup, posted right after the valid one
+        let bogus = ExternalMessage::from_hex(
+            "000000000101000000000000000100bbbcee9bed14d5ce9d9024610c3ba81d79f353aecd062cb31083\
+            b79db6559e044a6ec60464f8fa7a3779d3b1531dd137b728729efa7eb1b50cb4b4d569c91462445ce1\
+            b4a03ea8ff3b20c7283834194bc51ccc75e8e380786d86722bacdfe406",
+        );
+
+        context.as_host().run_level(|_| {});
+        context.as_host().add_external(message);
+        context.as_host().add_external(bogus);
+        context.as_host().run_level(kernel_run);
+
+        let kernel = Runtime::store_read_all(
+            context.as_host(),
+            &RefPath::assert_from(b"/kernel/boot.wasm"),
+        )?;
+        assert_eq!(b"fake kernel".to_vec(), kernel);
+
+        let nonce: Option<u64> = context.get(UPGRADE_NONCE_PATH.into())?;
+        assert_eq!(Some(1), nonce);
+        assert!(!context.has(PENDING_UPGRADE_PATH.into())?);
+        Ok(())
+    }
+
+    #[test]
+    fn kernel_upgrade_from_other_rollup() -> Result<()> {
+        let mut host = MockHost::default();
+        let mut page = vec![0u8];
+        page.extend_from_slice(&(b"fake kernel".len() as u32).to_be_bytes());
+        page.extend_from_slice(b"fake kernel");
+        host.set_preimage(page);
+
+        let mut context = KernelStore::<MockHost>::attach(&mut host);
+        let admin_key: PublicKey = "edpktfpdouHjAze9TeFcihdpeMng7FSCWbY4BozpSffZ9z85nyyBBB"
+            .try_into()
+            .unwrap();
+        context.set_admin_key(admin_key)?;
+        context.commit()?;
+        context
+            .as_mut()
+            .persist()
+            .expect("Failed to persist changes");
+
+        // Same upgrade, signed by the same admin key for the rollup 0102030400..00
+        let message = ExternalMessage::from_hex(
+            "000000000101000000000000000100bbbcee9bed14d5ce9d9024610c3ba81d79f353aecd062cb31083\
+            b79db6559e044a6ec60464f8fa7a3779d3b1531dd137b728729efa7eb1b50cb4b4d569c91462445ce1\
+            b4a03ea8ff3b20c7283834194bc51ccc75e8e380786d86722bacdfe406",
+        );
+
+        context.as_host().run_level(|_| {});
+        context.as_host().add_external(message);
+        context.as_host().run_level(kernel_run);
+
+        let kernel = Runtime::store_has(
+            context.as_host(),
+            &RefPath::assert_from(b"/kernel/boot.wasm"),
+        )?;
+        assert!(kernel.is_none());
+
+        let nonce: Option<u64> = context.get(UPGRADE_NONCE_PATH.into())?;
+        assert_eq!(None, nonce);
+        Ok(())
+    }
 }
@@ -0,0 +1,243 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use layered_store::{LayeredStore, StoreBackend, StoreType};
+use tezos_proto::context::{head::Head, TezosContext};
+
+use crate::{Error, Result};
+
+/// How far (in seconds) batch timestamps can go ahead of the L1 predecessor timestamp
+pub const MAX_TIMESTAMP_DRIFT: i64 = 60;
+
+const INBOX_LEVEL_PATH: &str = "/inbox/level";
+
+/// L1 level being processed, survives kernel reboots
+#[derive(Clone, Debug, PartialEq)]
+pub struct InboxLevel {
+    pub level: i32,
+    /// Timestamp of the L1 predecessor block
+    pub timestamp: i64,
+    /// Number of L2 batches produced at this level so far
+    pub batches: u32,
+}
+
+impl StoreType for InboxLevel {
+    fn from_bytes(bytes: &[u8]) -> layered_store::Result<Self> {
+        if bytes.len() != 16 {
+            return Err(layered_store::internal_error!("Invalid byte length"));
+        }
+        Ok(Self {
+            level: i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            timestamp: i64::from_be_bytes([
+                bytes[4], bytes[5], bytes[6], bytes[7], bytes[8], bytes[9], bytes[10], bytes[11],
+            ]),
+            batches: u32::from_be_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+        })
+    }
+
+    fn to_bytes(&self) -> layered_store::Result<Vec<u8>> {
+        Ok([
+            self.level.to_be_bytes().as_slice(),
+            self.timestamp.to_be_bytes().as_slice(),
+            self.batches.to_be_bytes().as_slice(),
+        ]
+        .concat())
+    }
+}
+
+/// Last L2 level produced at the given L1 level
+pub fn l1_level_path(level: i32) -> String {
+    format!("/levels/l1/{}", level)
+}
+
+/// L1 level at which the given L2 level was produced
+pub fn l2_level_path(level: i32) -> String {
+    format!("/levels/l2/{}", level)
+}
+
+/// Last L2 level produced before the level index was introduced
+pub const LEGACY_LEVEL_PATH: &str = "/levels/legacy";
+
+pub fn get_inbox_level<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+) -> Result<Option<InboxLevel>> {
+    Ok(context.get(INBOX_LEVEL_PATH.into())?)
+}
+
+pub fn set_inbox_level<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    inbox: &InboxLevel,
+) -> Result<()> {
+    context.set(INBOX_LEVEL_PATH.into(), Some(inbox.clone()))?;
+    context.commit()?;
+    Ok(())
+}
+
+/// Starts a new L1 level with an empty outbox, levels have to strictly increase
+pub fn begin_inbox_level<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    level: i32,
+) -> Result<InboxLevel> {
+    if let Some(last) = get_inbox_level(context)? {
+        if level <= last.level {
+            return Err(Error::InconsistentInboxLevel {
+                last: last.level,
+                found: level,
+            });
+        }
+    }
+
+    let inbox = InboxLevel {
+        level,
+        timestamp: 0,
+        batches: 0,
+    };
+    context.set_outbox_count(0)?;
+    set_inbox_level(context, &inbox)?;
+    Ok(inbox)
+}
+
+/// Records mapping between L1 and L2 levels for the newly produced batch
+pub fn record_batch<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    inbox: &mut InboxLevel,
+    head: &Head,
+) -> Result<()> {
+    inbox.batches += 1;
+    context.set(INBOX_LEVEL_PATH.into(), Some(inbox.clone()))?;
+    context.set(l1_level_path(inbox.level), Some(head.level as i64))?;
+    context.set(l2_level_path(head.level), Some(inbox.level as i64))?;
+    context.commit()?;
+    Ok(())
+}
+
+/// Chains produced by earlier kernel versions have one L2 block per L1 level and no index.
+/// Only the last of those blocks is indexed, the node derives the rest from it.
+pub fn index_legacy_head<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    head: &Head,
+    origination_level: u32,
+) -> Result<()> {
+    if head.level < 0 || context.has(l2_level_path(head.level))? {
+        return Ok(());
+    }
+
+    let inbox_level = head.level + origination_level as i32 + 1;
+    context.set(LEGACY_LEVEL_PATH.into(), Some(head.level as i64))?;
+    context.set(l1_level_path(inbox_level), Some(head.level as i64))?;
+    context.set(l2_level_path(head.level), Some(inbox_level as i64))?;
+    context.set_block_head(head.clone())?;
+    context.commit()?;
+    Ok(())
+}
+
+/// Checks the timestamp set by the sequencer, or picks the default one.
+/// Timestamps have to strictly increase and stay within bounds of the L1 timestamp.
+pub fn batch_timestamp(
+    inbox: &InboxLevel,
+    head: &Head,
+    block_time: i64,
+    timestamp: Option<i64>,
+) -> Result<i64> {
+    match timestamp {
+        Some(timestamp) => {
+            let min = std::cmp::max(inbox.timestamp, head.timestamp + 1);
+            let max = inbox.timestamp + MAX_TIMESTAMP_DRIFT;
+            if timestamp < min || timestamp > max {
+                return Err(Error::InvalidBatchTimestamp {
+                    min,
+                    max,
+                    found: timestamp,
+                });
+            }
+            Ok(timestamp)
+        }
+        None => Ok(std::cmp::max(
+            inbox.timestamp + block_time,
+            head.timestamp + 1,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use layered_store::EphemeralStore;
+
+    use super::*;
+
+    fn head_at(level: i32, timestamp: i64) -> Head {
+        let mut head = Head::default();
+        head.level = level;
+        head.timestamp = timestamp;
+        head
+    }
+
+    #[test]
+    fn test_inbox_levels() -> Result<()> {
+        let mut context = EphemeralStore::default();
+
+        let mut inbox = begin_inbox_level(&mut context, 10)?;
+        record_batch(&mut context, &mut inbox, &head_at(0, 0))?;
+        record_batch(&mut context, &mut inbox, &head_at(1, 0))?;
+        assert_eq!(2, get_inbox_level(&mut context)?.unwrap().batches);
+
+        let l2_level: Option<i64> = context.get(l1_level_path(10))?;
+        assert_eq!(Some(1), l2_level);
+        let l1_level: Option<i64> = context.get(l2_level_path(0))?;
+        assert_eq!(Some(10), l1_level);
+
+        assert!(matches!(
+            begin_inbox_level(&mut context, 10),
+            Err(Error::InconsistentInboxLevel {
+                last: 10,
+                found: 10
+            })
+        ));
+        assert_eq!(0, begin_inbox_level(&mut context, 12)?.batches);
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_head() -> Result<()> {
+        let mut context = EphemeralStore::default();
+
+        index_legacy_head(&mut context, &head_at(-1, 0), 100)?;
+        assert!(!context.has(LEGACY_LEVEL_PATH.into())?);
+
+        index_legacy_head(&mut context, &head_at(5, 0), 100)?;
+        let legacy: Option<i64> = context.get(LEGACY_LEVEL_PATH.into())?;
+        assert_eq!(Some(5), legacy);
+        let l1_level: Option<i64> = context.get(l2_level_path(5))?;
+        assert_eq!(Some(106), l1_level);
+        assert_eq!(Some(head_at(5, 0)), context.get_block_head(5)?);
+
+        // indexed heads are left as is
+        let mut inbox = begin_inbox_level(&mut context, 110)?;
+        record_batch(&mut context, &mut inbox, &head_at(6, 0))?;
+        index_legacy_head(&mut context, &head_at(6, 0), 100)?;
+        let legacy: Option<i64> = context.get(LEGACY_LEVEL_PATH.into())?;
+        assert_eq!(Some(5), legacy);
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_timestamp() -> Result<()> {
+        let inbox = InboxLevel {
+            level: 10,
+            timestamp: 1000,
+            batches: 1,
+        };
+        let head = head_at(0, 1002);
+
+        assert_eq!(1003, batch_timestamp(&inbox, &head, 8, Some(1003))?);
+        assert_eq!(1008, batch_timestamp(&inbox, &head, 8, None)?);
+        // not monotonic
+        assert!(batch_timestamp(&inbox, &head, 8, Some(1002)).is_err());
+        // too far from L1 timestamp
+        assert!(batch_timestamp(&inbox, &head, 8, Some(1000 + MAX_TIMESTAMP_DRIFT + 1)).is_err());
+        // default timestamp is monotonic too
+        assert_eq!(1021, batch_timestamp(&inbox, &head_at(0, 1020), 8, None)?);
+        Ok(())
+    }
+}
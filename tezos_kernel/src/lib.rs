@@ -2,9 +2,21 @@
 //
 // SPDX-License-Identifier: MIT
 
+pub mod chunks;
+pub mod commitments;
+pub mod delayed;
 pub mod error;
+#[cfg(test)]
+mod fixtures;
 pub mod kernel;
+pub mod levels;
+pub mod oracle;
+pub mod outbox;
 pub mod payload;
+pub mod sequencer;
+pub mod signature;
+pub mod sponsor;
+pub mod upgrade;
 
 pub use error::{Error, Result};
 
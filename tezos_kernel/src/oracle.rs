@@ -0,0 +1,85 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use layered_store::{LayeredStore, StoreBackend};
+use tezos_core::types::encoded::Encoded;
+use tezos_proto::{
+    context::{
+        head::Head,
+        oracle::{is_valid_oracle_key, OracleValue},
+        TezosContext,
+    },
+    executor::reveal::public_key_hash,
+};
+
+use crate::{
+    levels::{InboxLevel, MAX_TIMESTAMP_DRIFT},
+    payload::OracleUpdate,
+    sequencer::get_sequencers,
+    signature::verify_signature,
+    Error, Result,
+};
+
+/// Stores the feed values signed by a sequencer from the allowlist, they are readable
+/// starting from the next L2 block. Values that are not newer than the stored ones are skipped
+/// (this also protects against replays). Returns the number of updated feeds.
+pub fn accept_oracle_update<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    inbox: &InboxLevel,
+    head: &Head,
+    update: &OracleUpdate,
+) -> Result<usize> {
+    if !get_sequencers(context)?.contains(&update.public_key) {
+        return Err(Error::UnknownSequencer {
+            address: public_key_hash(&update.public_key)?.value().to_string(),
+        });
+    }
+
+    let signed_bytes = [head.chain_id.to_bytes()?, update.signed_bytes.clone()].concat();
+    if !verify_signature(
+        &update.public_key,
+        signed_bytes.as_slice(),
+        &update.signature,
+    )? {
+        return Err(Error::InvalidSequencerSignature);
+    }
+
+    // Values from the future would never become stale
+    let max = inbox.timestamp + MAX_TIMESTAMP_DRIFT;
+    if update.timestamp > max {
+        return Err(Error::InvalidOracleTimestamp {
+            max,
+            found: update.timestamp,
+        });
+    }
+
+    if let Some((key, _)) = update
+        .entries
+        .iter()
+        .find(|(key, _)| !is_valid_oracle_key(key))
+    {
+        return Err(Error::InvalidOracleKey { key: key.clone() });
+    }
+
+    let mut updated = 0;
+    for (key, value) in update.entries.iter() {
+        if let Some(latest) = context.get_oracle_value(key)? {
+            if latest.timestamp >= update.timestamp {
+                continue;
+            }
+        }
+        context.set_oracle_value(
+            key,
+            OracleValue {
+                value: *value,
+                timestamp: update.timestamp,
+                level: head.level + 1,
+            },
+        )?;
+        updated += 1;
+    }
+
+    context.commit()?;
+    Ok(updated)
+}
@@ -0,0 +1,141 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use kernel_io::{
+    outbox::{write_outbox, OutboxTransaction},
+    KernelStore,
+};
+use tezos_proto::context::{
+    outbox::{outbox_path, OutboxMessage, OutboxMessages},
+    TezosContext,
+};
+use tezos_smart_rollup_core::SmartRollupCore;
+
+use crate::{internal_error, levels::get_inbox_level, Result};
+
+/// Writes queued L1 calls (withdrawals and contract calls) to the outbox of the current
+/// inbox level, one message each, then deposit refunds in the room left.
+/// Calls that do not fit stay queued until the next level.
+/// Has to be called once the changes of the run are persisted: the progress is persisted
+/// after every message, so that a message is never written twice.
+/// Returns the number of messages written.
+pub fn flush_outbox<Host: SmartRollupCore>(context: &mut KernelStore<Host>) -> Result<usize> {
+    let mut queue = context.get_outbox_queue()?;
+    let mut refunds = context.get_refund_queue()?;
+    if queue.is_empty() && refunds.is_empty() {
+        return Ok(0);
+    }
+
+    let limit = context
+        .get_config()?
+        .smart_rollup_max_outbox_messages_per_level
+        .max(0) as u64;
+    let level = get_inbox_level(context)?
+        .ok_or_else(|| internal_error!("Inbox level is not started"))?
+        .level;
+    let path = outbox_path(level);
+    let mut messages: OutboxMessages = context.get(path.clone())?.unwrap_or_default();
+    let mut count = context.get_outbox_count()?;
+    let mut written = 0;
+
+    while count < limit {
+        // L2 calls were checked against the limit when queued, refunds take the room left
+        let call = match (queue.first(), refunds.first()) {
+            (Some(call), _) | (None, Some(call)) => call.clone(),
+            (None, None) => break,
+        };
+        let transaction = OutboxTransaction {
+            parameters: call.parameters.clone(),
+            destination: call.destination.clone(),
+            entrypoint: call.entrypoint.clone(),
+        };
+        if let Err(err) = write_outbox(context.as_host(), &[transaction]) {
+            context.log(format!("Outbox write failed: {}", err.format()));
+            break;
+        }
+
+        if queue.is_empty() {
+            refunds.remove(0);
+        } else {
+            queue.remove(0);
+        }
+        let index = messages.0.len() as u32;
+        messages.0.push(OutboxMessage::new(level, index, call));
+        count += 1;
+        written += 1;
+
+        context.set(path.clone(), Some(messages.clone()))?;
+        context.set_outbox_queue(queue.clone())?;
+        context.set_refund_queue(refunds.clone())?;
+        context.set_outbox_count(count)?;
+        context.commit()?;
+        context.as_mut().persist()?;
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod test {
+    use tezos_core::types::{encoded::Encoded, mutez::Mutez};
+    use tezos_proto::{context::outbox::OutboxCall, executor::withdrawal::Withdrawal};
+    use tezos_smart_rollup_mock::MockHost;
+
+    use super::*;
+    use crate::levels::begin_inbox_level;
+
+    #[test]
+    fn test_flush_outbox() -> Result<()> {
+        let mut host = MockHost::default();
+        let mut context = KernelStore::<MockHost>::attach(&mut host);
+        begin_inbox_level(&mut context, 5)?;
+
+        let withdrawal = Withdrawal {
+            ticketer: "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm".try_into().unwrap(),
+            receiver: "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU".try_into().unwrap(),
+            amount: Mutez::from(100u32),
+        };
+        let call = withdrawal.outbox_call()?;
+        let refund = OutboxCall {
+            destination: "KT18g6ejmStajqDwZZ5ZwTfu1ZKzhYq5RboW".try_into().unwrap(),
+            ..call.clone()
+        };
+        context.set_refund_queue(vec![refund])?;
+        context.set_outbox_queue(vec![call.clone(), call])?;
+
+        let mut config = context.get_config()?;
+        config.smart_rollup_max_outbox_messages_per_level = 2;
+        context.set_config(config)?;
+        context.commit()?;
+
+        // L2 calls go ahead of refunds
+        assert_eq!(2, flush_outbox(&mut context)?);
+        assert!(context.get_outbox_queue()?.is_empty());
+        assert_eq!(1, context.get_refund_queue()?.len());
+        assert_eq!(2, context.get_outbox_count()?);
+
+        let messages: Option<OutboxMessages> = context.get(outbox_path(5))?;
+        let messages = messages.expect("Outbox messages").0;
+        assert_eq!(2, messages.len());
+        assert_eq!(1, messages[1].index);
+        assert_eq!(5, messages[1].outbox_level);
+        assert_eq!(
+            "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm",
+            messages[1].destination.value()
+        );
+        assert_eq!("withdraw", messages[1].entrypoint);
+
+        // The outbox is full until the next level
+        assert_eq!(0, flush_outbox(&mut context)?);
+        begin_inbox_level(&mut context, 6)?;
+        assert_eq!(1, flush_outbox(&mut context)?);
+        assert!(context.get_refund_queue()?.is_empty());
+        let messages: Option<OutboxMessages> = context.get(outbox_path(6))?;
+        assert_eq!(
+            "KT18g6ejmStajqDwZZ5ZwTfu1ZKzhYq5RboW",
+            messages.expect("Outbox messages").0[0].destination.value()
+        );
+        Ok(())
+    }
+}
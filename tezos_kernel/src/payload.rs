@@ -2,13 +2,46 @@
 //
 // SPDX-License-Identifier: MIT
 
-use kernel_io::{error::err_into, inbox::PayloadType};
-use tezos_core::types::encoded::{Encoded, OperationHash, Signature};
+use kernel_io::{error::err_into, inbox::PayloadType, reveal::split_messages};
+use tezos_core::types::encoded::{BlockHash, Encoded, OperationHash, PublicKey, Signature};
 use tezos_operation::operations::{SignedOperation, UnsignedOperation};
+use tezos_smart_rollup_core::PREIMAGE_HASH_SIZE;
 
 use crate::error::{Error, Result};
 
 const SIGNATURE_SIZE: usize = 64;
+const NONCE_SIZE: usize = 8;
+const CHAIN_ID_SIZE: usize = 4;
+const TIMESTAMP_SIZE: usize = 8;
+const OPERATION_HASH_SIZE: usize = 32;
+const ORACLE_VALUE_SIZE: usize = 8;
+const BLOCK_HASH_SIZE: usize = 32;
+const CHUNK_HEADER_SIZE: usize = OPERATION_HASH_SIZE + 2 + 2;
+const ED25519_PUBLIC_KEY_SIZE: usize = 32;
+const PUBLIC_KEY_SIZE: usize = 33;
+
+/// Max size of a signed L2 operation, same as the L1 `max_operation_data_length`
+pub const MAX_OPERATION_SIZE: usize = 32 * 1024;
+// Inbox message limit minus the external message tag, chain id, payload tag and chunk header
+const MAX_CHUNK_DATA_SIZE: usize = 4095 - 4 - 1 - CHUNK_HEADER_SIZE;
+/// Number of full chunks needed to carry the largest operation
+pub const MAX_CHUNKS: u16 =
+    ((MAX_OPERATION_SIZE + MAX_CHUNK_DATA_SIZE - 1) / MAX_CHUNK_DATA_SIZE) as u16;
+
+const INLINE_BATCH_KIND: u8 = 0;
+const REVEAL_BATCH_KIND: u8 = 1;
+
+pub const OPERATION_TAG: u8 = 0;
+pub const UPGRADE_TAG: u8 = 1;
+pub const CHUNK_TAG: u8 = 2;
+// Tag 3 was used by standalone reveals, preimage roots are only accepted in signed batches
+pub const SEAL_TAG: u8 = 4;
+pub const SIGNED_BATCH_TAG: u8 = 5;
+pub const HEADER_SIGNATURE_TAG: u8 = 6;
+pub const COMMITMENT_TAG: u8 = 7;
+pub const COMMITTED_OPERATION_TAG: u8 = 8;
+pub const SPONSOR_SIGNATURE_TAG: u8 = 9;
+pub const ORACLE_UPDATE_TAG: u8 = 10;
 
 pub fn parse_l2_operation<'a>(bytes: &'a [u8]) -> Result<(OperationHash, SignedOperation)> {
     if bytes.len() <= SIGNATURE_SIZE {
@@ -25,16 +58,400 @@ pub fn parse_l2_operation<'a>(bytes: &'a [u8]) -> Result<(OperationHash, SignedO
     Ok((hash, opg))
 }
 
+/// Reads a tagged public key (first byte is the curve), returns it along with the remaining bytes
+pub fn split_public_key(bytes: &[u8]) -> Result<(PublicKey, &[u8])> {
+    let size = match bytes.first() {
+        Some(0) => 1 + ED25519_PUBLIC_KEY_SIZE,
+        Some(_) => 1 + PUBLIC_KEY_SIZE,
+        None => return Err(Error::UnexpectedPublicKeyLength { length: 0 }),
+    };
+    if bytes.len() < size {
+        return Err(Error::UnexpectedPublicKeyLength {
+            length: bytes.len(),
+        });
+    }
+
+    let (public_key, rest) = bytes.split_at(size);
+    Ok((PublicKey::from_bytes(public_key)?, rest))
+}
+
+/// Request to install a new kernel, signed by the admin key
+#[derive(Clone, Debug)]
+pub struct KernelUpgrade {
+    pub nonce: u64,
+    pub root_hash: [u8; PREIMAGE_HASH_SIZE],
+    pub signature: Signature,
+}
+
+impl KernelUpgrade {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != NONCE_SIZE + PREIMAGE_HASH_SIZE + SIGNATURE_SIZE {
+            return Err(Error::UnexpectedKernelUpgradeLength {
+                length: bytes.len(),
+            });
+        }
+
+        let (nonce, rest) = bytes.split_at(NONCE_SIZE);
+        let (root_hash, signature) = rest.split_at(PREIMAGE_HASH_SIZE);
+        Ok(Self {
+            nonce: u64::from_be_bytes(nonce.try_into().expect("Nonce size")),
+            root_hash: root_hash.try_into().expect("Preimage hash size"),
+            signature: Signature::from_bytes(signature)?,
+        })
+    }
+
+    /// Bytes covered by the admin signature, bound to the rollup so that an upgrade
+    /// cannot be replayed on another rollup sharing the same admin key
+    pub fn signed_bytes(&self, rollup_address: &[u8]) -> Vec<u8> {
+        [
+            &rollup_address[..CHAIN_ID_SIZE],
+            rollup_address,
+            self.nonce.to_be_bytes().as_slice(),
+            self.root_hash.as_slice(),
+        ]
+        .concat()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok([
+            self.nonce.to_be_bytes().as_slice(),
+            self.root_hash.as_slice(),
+            self.signature.to_bytes()?.as_slice(),
+        ]
+        .concat())
+    }
+}
+
+/// Part of an L2 operation that does not fit into a single inbox message
+#[derive(Clone, Debug)]
+pub struct OperationChunk {
+    /// Hash of the complete signed operation
+    pub hash: OperationHash,
+    pub index: u16,
+    pub total: u16,
+    pub data: Vec<u8>,
+}
+
+impl OperationChunk {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() <= CHUNK_HEADER_SIZE {
+            return Err(Error::UnexpectedChunkLength {
+                length: bytes.len(),
+            });
+        }
+
+        let (hash, rest) = bytes.split_at(OPERATION_HASH_SIZE);
+        let index = u16::from_be_bytes([rest[0], rest[1]]);
+        let total = u16::from_be_bytes([rest[2], rest[3]]);
+        if total > MAX_CHUNKS {
+            return Err(Error::TooManyChunks {
+                max: MAX_CHUNKS,
+                found: total,
+            });
+        }
+        if index >= total {
+            return Err(Error::InvalidChunkIndex { index, total });
+        }
+
+        Ok(Self {
+            hash: OperationHash::from_bytes(hash)?,
+            index,
+            total,
+            data: rest[4..].to_vec(),
+        })
+    }
+}
+
+pub enum BatchContents {
+    /// L2 operations, each one prefixed with its 4-byte big-endian length
+    Inline(Vec<(OperationHash, SignedOperation)>),
+    /// Root hash of preimage pages containing length-prefixed L2 operations
+    Reveal([u8; PREIMAGE_HASH_SIZE]),
+}
+
+/// L2 block contents signed by a sequencer from the allowlist
+pub struct SignedBatch {
+    pub nonce: u64,
+    /// Block timestamp, the default one is used if not set
+    pub timestamp: Option<i64>,
+    pub public_key: PublicKey,
+    pub contents: BatchContents,
+    pub signature: Signature,
+    /// Bytes covered by the sequencer signature
+    pub signed_bytes: Vec<u8>,
+}
+
+impl SignedBatch {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() <= NONCE_SIZE + TIMESTAMP_SIZE + SIGNATURE_SIZE {
+            return Err(Error::UnexpectedSignedBatchLength {
+                length: bytes.len(),
+            });
+        }
+
+        let (signed_bytes, signature) = bytes.split_at(bytes.len() - SIGNATURE_SIZE);
+        let (nonce, rest) = signed_bytes.split_at(NONCE_SIZE);
+        let (timestamp, rest) = rest.split_at(TIMESTAMP_SIZE);
+        let (public_key, rest) = split_public_key(rest)?;
+
+        let contents = match rest {
+            [INLINE_BATCH_KIND, data @ ..] => BatchContents::Inline(
+                split_messages(data)?
+                    .iter()
+                    .map(|message| parse_l2_operation(message.as_slice()))
+                    .collect::<Result<Vec<(OperationHash, SignedOperation)>>>()?,
+            ),
+            [REVEAL_BATCH_KIND, data @ ..] => match data.try_into() {
+                Ok(root_hash) => BatchContents::Reveal(root_hash),
+                Err(_) => return Err(Error::UnexpectedRevealHashLength { length: data.len() }),
+            },
+            [kind, ..] => return Err(Error::UnexpectedBatchKind { kind: *kind }),
+            [] => {
+                return Err(Error::UnexpectedSignedBatchLength {
+                    length: bytes.len(),
+                })
+            }
+        };
+
+        let timestamp = i64::from_be_bytes(timestamp.try_into().expect("Timestamp size"));
+        Ok(Self {
+            nonce: u64::from_be_bytes(nonce.try_into().expect("Nonce size")),
+            timestamp: (timestamp != 0).then_some(timestamp),
+            public_key,
+            contents,
+            signature: Signature::from_bytes(signature)?,
+            signed_bytes: signed_bytes.to_vec(),
+        })
+    }
+}
+
+/// Sequencer signature of an already produced L2 block
+pub struct HeaderSignature {
+    pub hash: BlockHash,
+    pub public_key: PublicKey,
+    /// Signature of the block hash bytes
+    pub signature: Signature,
+}
+
+impl HeaderSignature {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() <= BLOCK_HASH_SIZE + SIGNATURE_SIZE {
+            return Err(Error::UnexpectedHeaderSignatureLength {
+                length: bytes.len(),
+            });
+        }
+
+        let (hash, rest) = bytes.split_at(BLOCK_HASH_SIZE);
+        let (public_key, signature) = split_public_key(rest)?;
+        if signature.len() != SIGNATURE_SIZE {
+            return Err(Error::UnexpectedHeaderSignatureLength {
+                length: bytes.len(),
+            });
+        }
+
+        Ok(Self {
+            hash: BlockHash::from_bytes(hash)?,
+            public_key,
+            signature: Signature::from_bytes(signature)?,
+        })
+    }
+}
+
+/// Commitment to an L2 operation hash, signed by the operation source
+pub struct SignedCommitment {
+    pub hash: OperationHash,
+    pub public_key: PublicKey,
+    /// Signature of the chain id followed by the operation hash bytes
+    pub signature: Signature,
+}
+
+impl SignedCommitment {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() <= OPERATION_HASH_SIZE + SIGNATURE_SIZE {
+            return Err(Error::UnexpectedCommitmentLength {
+                length: bytes.len(),
+            });
+        }
+
+        let (hash, rest) = bytes.split_at(OPERATION_HASH_SIZE);
+        let (public_key, signature) = split_public_key(rest)?;
+        if signature.len() != SIGNATURE_SIZE {
+            return Err(Error::UnexpectedCommitmentLength {
+                length: bytes.len(),
+            });
+        }
+
+        Ok(Self {
+            hash: OperationHash::from_bytes(hash)?,
+            public_key,
+            signature: Signature::from_bytes(signature)?,
+        })
+    }
+}
+
+/// Sponsor signature of an L2 operation hash, the sponsor pays the operation fees
+pub struct SponsorSignature {
+    pub hash: OperationHash,
+    pub public_key: PublicKey,
+    /// Signature of the chain id followed by the operation hash bytes
+    pub signature: Signature,
+}
+
+impl SponsorSignature {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() <= OPERATION_HASH_SIZE + SIGNATURE_SIZE {
+            return Err(Error::UnexpectedSponsorSignatureLength {
+                length: bytes.len(),
+            });
+        }
+
+        let (hash, rest) = bytes.split_at(OPERATION_HASH_SIZE);
+        let (public_key, signature) = split_public_key(rest)?;
+        if signature.len() != SIGNATURE_SIZE {
+            return Err(Error::UnexpectedSponsorSignatureLength {
+                length: bytes.len(),
+            });
+        }
+
+        Ok(Self {
+            hash: OperationHash::from_bytes(hash)?,
+            public_key,
+            signature: Signature::from_bytes(signature)?,
+        })
+    }
+}
+
+/// Oracle feed values observed by a sequencer from the allowlist
+pub struct OracleUpdate {
+    /// Time of the observation, shared by all the values
+    pub timestamp: i64,
+    pub public_key: PublicKey,
+    pub entries: Vec<(String, u64)>,
+    pub signature: Signature,
+    /// Bytes covered by the sequencer signature
+    pub signed_bytes: Vec<u8>,
+}
+
+impl OracleUpdate {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() <= TIMESTAMP_SIZE + SIGNATURE_SIZE {
+            return Err(Error::UnexpectedOracleUpdateLength {
+                length: bytes.len(),
+            });
+        }
+
+        let (signed_bytes, signature) = bytes.split_at(bytes.len() - SIGNATURE_SIZE);
+        let (timestamp, rest) = signed_bytes.split_at(TIMESTAMP_SIZE);
+        let (public_key, mut rest) = split_public_key(rest)?;
+
+        // Entries: 1-byte key length, key (ASCII), 8-byte big-endian value
+        let mut entries: Vec<(String, u64)> = Vec::new();
+        while let [length, tail @ ..] = rest {
+            let length = *length as usize;
+            if tail.len() < length + ORACLE_VALUE_SIZE {
+                return Err(Error::UnexpectedOracleUpdateLength {
+                    length: bytes.len(),
+                });
+            }
+            let (key, tail) = tail.split_at(length);
+            let (value, tail) = tail.split_at(ORACLE_VALUE_SIZE);
+            let key = String::from_utf8(key.to_vec()).map_err(|_| Error::InvalidOracleKey {
+                key: String::from_utf8_lossy(key).to_string(),
+            })?;
+            let value = u64::from_be_bytes(value.try_into().expect("Value size"));
+            entries.push((key, value));
+            rest = tail;
+        }
+
+        Ok(Self {
+            timestamp: i64::from_be_bytes(timestamp.try_into().expect("Timestamp size")),
+            public_key,
+            entries,
+            signature: Signature::from_bytes(signature)?,
+            signed_bytes: signed_bytes.to_vec(),
+        })
+    }
+}
+
 pub enum TezosPayload {
     Operation {
         hash: OperationHash,
         opg: SignedOperation,
     },
+    Upgrade(KernelUpgrade),
+    Chunk(OperationChunk),
+    /// Closes the current L2 batch, operations that follow go to the next one
+    Seal {
+        timestamp: Option<i64>,
+    },
+    /// Complete L2 block signed by a sequencer
+    SignedBatch(SignedBatch),
+    HeaderSignature(HeaderSignature),
+    /// Hash of a signed operation, fixes its position in the execution order
+    Commitment(SignedCommitment),
+    /// Signed operation bytes matching an earlier commitment
+    CommittedOperation(Vec<u8>),
+    SponsorSignature(SponsorSignature),
+    OracleUpdate(OracleUpdate),
+}
+
+impl TezosPayload {
+    /// Messages that anyone can submit, accepted only if the kernel is permissionless
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            TezosPayload::Operation { .. } | TezosPayload::Chunk(_) | TezosPayload::Seal { .. }
+        )
+    }
 }
 
 impl PayloadType for TezosPayload {
     fn from_external_message(message: &[u8]) -> kernel_io::Result<Self> {
-        let (hash, opg) = parse_l2_operation(message).map_err(err_into)?;
-        Ok(TezosPayload::Operation { hash, opg })
+        match message {
+            [OPERATION_TAG, data @ ..] => {
+                let (hash, opg) = parse_l2_operation(data).map_err(err_into)?;
+                Ok(TezosPayload::Operation { hash, opg })
+            }
+            [UPGRADE_TAG, data @ ..] => {
+                let upgrade = KernelUpgrade::from_bytes(data).map_err(err_into)?;
+                Ok(TezosPayload::Upgrade(upgrade))
+            }
+            [CHUNK_TAG, data @ ..] => {
+                let chunk = OperationChunk::from_bytes(data).map_err(err_into)?;
+                Ok(TezosPayload::Chunk(chunk))
+            }
+            [SEAL_TAG] => Ok(TezosPayload::Seal { timestamp: None }),
+            [SEAL_TAG, data @ ..] => match data.try_into() {
+                Ok(timestamp) => Ok(TezosPayload::Seal {
+                    timestamp: Some(i64::from_be_bytes(timestamp)),
+                }),
+                Err(_) => Err(err_into(Error::UnexpectedSealLength { length: data.len() })),
+            },
+            [SIGNED_BATCH_TAG, data @ ..] => {
+                let batch = SignedBatch::from_bytes(data).map_err(err_into)?;
+                Ok(TezosPayload::SignedBatch(batch))
+            }
+            [HEADER_SIGNATURE_TAG, data @ ..] => {
+                let signature = HeaderSignature::from_bytes(data).map_err(err_into)?;
+                Ok(TezosPayload::HeaderSignature(signature))
+            }
+            [COMMITMENT_TAG, data @ ..] => {
+                let commitment = SignedCommitment::from_bytes(data).map_err(err_into)?;
+                Ok(TezosPayload::Commitment(commitment))
+            }
+            [COMMITTED_OPERATION_TAG, data @ ..] => {
+                Ok(TezosPayload::CommittedOperation(data.to_vec()))
+            }
+            [SPONSOR_SIGNATURE_TAG, data @ ..] => {
+                let signature = SponsorSignature::from_bytes(data).map_err(err_into)?;
+                Ok(TezosPayload::SponsorSignature(signature))
+            }
+            [ORACLE_UPDATE_TAG, data @ ..] => {
+                let update = OracleUpdate::from_bytes(data).map_err(err_into)?;
+                Ok(TezosPayload::OracleUpdate(update))
+            }
+            [tag, ..] => Err(err_into(Error::UnexpectedPayloadTag { tag: *tag })),
+            [] => Err(err_into("Empty external message")),
+        }
     }
 }
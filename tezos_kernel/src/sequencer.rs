@@ -0,0 +1,207 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use layered_store::{error::err_into, LayeredStore, StoreBackend, StoreType};
+use tezos_core::types::encoded::{BlockHash, ChainId, Encoded, ImplicitAddress, PublicKey};
+use tezos_proto::executor::reveal::public_key_hash;
+
+use crate::{
+    payload::{split_public_key, HeaderSignature, SignedBatch},
+    signature::verify_signature,
+    Error, Result,
+};
+
+/// Public keys of sequencers allowed to produce blocks, the kernel is permissionless if not set
+pub const SEQUENCERS_PATH: &str = "/sequencers";
+
+/// Nonce of the last batch submitted by the sequencer, protects against replays
+pub fn sequencer_nonce_path(address: &str) -> String {
+    format!("/sequencer_nonce/{}", address)
+}
+
+/// Sequencer that signed the batch of the given block
+pub fn block_batcher_path(hash: &str) -> String {
+    format!("/batchers/{}", hash)
+}
+
+/// Sequencer signature of the given block header
+pub fn block_signature_path(hash: &str) -> String {
+    format!("/signatures/{}", hash)
+}
+
+/// Allowlist of sequencer keys, stored as concatenated tagged public keys
+#[derive(Clone, Debug, Default)]
+pub struct Sequencers(pub Vec<PublicKey>);
+
+impl StoreType for Sequencers {
+    fn from_bytes(bytes: &[u8]) -> layered_store::Result<Self> {
+        let mut keys: Vec<PublicKey> = Vec::new();
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let (public_key, tail) = split_public_key(rest).map_err(err_into)?;
+            keys.push(public_key);
+            rest = tail;
+        }
+        Ok(Self(keys))
+    }
+
+    fn to_bytes(&self) -> layered_store::Result<Vec<u8>> {
+        let mut bytes: Vec<u8> = Vec::new();
+        for public_key in self.0.iter() {
+            bytes.extend_from_slice(StoreType::to_bytes(public_key)?.as_slice());
+        }
+        Ok(bytes)
+    }
+}
+
+impl Sequencers {
+    pub fn contains(&self, public_key: &PublicKey) -> bool {
+        self.0.iter().any(|key| key.value() == public_key.value())
+    }
+}
+
+pub fn get_sequencers<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+) -> Result<Sequencers> {
+    Ok(context.get(SEQUENCERS_PATH.into())?.unwrap_or_default())
+}
+
+/// Only sequencer-signed batches are accepted if the allowlist is not empty
+pub fn is_permissioned<Backend: StoreBackend>(context: &mut LayeredStore<Backend>) -> Result<bool> {
+    Ok(!get_sequencers(context)?.0.is_empty())
+}
+
+/// Checks the batch signature against the allowlist and consumes the batch nonce.
+/// The signature covers the chain id, so that a batch cannot be replayed on another rollup.
+/// Returns the address of the sequencer.
+pub fn accept_batch<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    chain_id: &ChainId,
+    batch: &SignedBatch,
+) -> Result<ImplicitAddress> {
+    let address = public_key_hash(&batch.public_key)?;
+    if !get_sequencers(context)?.contains(&batch.public_key) {
+        return Err(Error::UnknownSequencer {
+            address: address.value().to_string(),
+        });
+    }
+
+    let nonce_path = sequencer_nonce_path(address.value());
+    let last_nonce: u64 = context.get(nonce_path.clone())?.unwrap_or(0);
+    if batch.nonce <= last_nonce {
+        return Err(Error::SequencerNonceInThePast {
+            expected: last_nonce + 1,
+            found: batch.nonce,
+        });
+    }
+
+    let signed_bytes = [chain_id.to_bytes()?, batch.signed_bytes.clone()].concat();
+    if !verify_signature(&batch.public_key, signed_bytes.as_slice(), &batch.signature)? {
+        return Err(Error::InvalidSequencerSignature);
+    }
+
+    context.set(nonce_path, Some(batch.nonce))?;
+    context.commit()?;
+    Ok(address)
+}
+
+/// Stores the signature of a block header (along with the chain id),
+/// only the sequencer of the block can sign it
+pub fn accept_header_signature<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    chain_id: &ChainId,
+    header_signature: &HeaderSignature,
+) -> Result<()> {
+    let hash = header_signature.hash.value();
+    let address = public_key_hash(&header_signature.public_key)?;
+    let batcher: Option<ImplicitAddress> = context.get(block_batcher_path(hash))?;
+    if batcher.as_ref().map(|batcher| batcher.value()) != Some(address.value()) {
+        return Err(Error::UnexpectedBlockSigner {
+            hash: hash.to_string(),
+            found: address.value().to_string(),
+        });
+    }
+
+    let signed_bytes = [chain_id.to_bytes()?, header_signature.hash.to_bytes()?].concat();
+    if !verify_signature(
+        &header_signature.public_key,
+        signed_bytes.as_slice(),
+        &header_signature.signature,
+    )? {
+        return Err(Error::InvalidSequencerSignature);
+    }
+
+    context.set(
+        block_signature_path(hash),
+        Some(header_signature.signature.clone()),
+    )?;
+    context.commit()?;
+    Ok(())
+}
+
+/// Remembers the sequencer of the produced block, so that it can sign the header later
+pub fn record_batcher<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    hash: &BlockHash,
+    batcher: ImplicitAddress,
+) -> Result<()> {
+    context.set(block_batcher_path(hash.value()), Some(batcher))?;
+    context.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use layered_store::EphemeralStore;
+    use tezos_core::types::encoded::Signature;
+    use tezos_proto::context::head::ZERO_CHAIN_ID;
+
+    use super::*;
+
+    #[test]
+    fn test_header_signature() -> Result<()> {
+        let mut context = EphemeralStore::default();
+        let chain_id: ChainId = ZERO_CHAIN_ID.try_into().unwrap();
+        let hash: BlockHash = "BKiHSFY5yPf2ne3BSAWXhFKVfA7GUk484ACE9Rk2PNhS9BEYg2w"
+            .try_into()
+            .unwrap();
+        let batcher: ImplicitAddress = "tz1c8PEDNfj6UxoQM2XCyfTHM5KbGGgoqDrH".try_into().unwrap();
+        record_batcher(&mut context, &hash, batcher)?;
+
+        // block hash (32 bytes), public key (33 bytes), signature (64 bytes)
+        let signed_by_other = HeaderSignature::from_bytes(&hex::decode(
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f008139770ea87d175f56\
+            a35466c34c7ecccb8d8a91b4ee37a25df60f5b8fc9b394fb0de9515aa6f62d22bda26c8047227c951120fd\
+            7715f72f215a93a31ad78f12b6f29fabe0b12139d4c24242f6679e631c366ec9329f5e92554df88084e33705",
+        )
+        .unwrap())?;
+        assert!(matches!(
+            accept_header_signature(&mut context, &chain_id, &signed_by_other),
+            Err(Error::UnexpectedBlockSigner { .. })
+        ));
+
+        let signed_by_batcher = HeaderSignature::from_bytes(&hex::decode(
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f008a88e3dd7409f195fd\
+            52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c623623f5d5bfe1e297a3c21ffe77d2e784ca553b\
+            c3102aa19aaa91df29db5e0747eaf99472d0ef643cec0dbe591adcfe66a387745b71f9a5502422e3d83e2c06",
+        )
+        .unwrap())?;
+        assert_eq!(hash.value(), signed_by_batcher.hash.value());
+
+        // signature is bound to the chain id
+        let other_chain: ChainId = "NetXdQprcVkpaWU".try_into().unwrap();
+        assert!(matches!(
+            accept_header_signature(&mut context, &other_chain, &signed_by_batcher),
+            Err(Error::InvalidSequencerSignature)
+        ));
+        accept_header_signature(&mut context, &chain_id, &signed_by_batcher)?;
+
+        let signature: Option<Signature> = context.get(block_signature_path(hash.value()))?;
+        assert_eq!(
+            Some(signed_by_batcher.signature.value()),
+            signature.as_ref().map(|s| s.value())
+        );
+        Ok(())
+    }
+}
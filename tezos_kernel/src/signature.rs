@@ -0,0 +1,40 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use tezos_core::{
+    types::encoded::{Encoded, PublicKey, Signature},
+    Tezos,
+};
+
+use crate::Result;
+
+/// Checks the signature of the Blake2b digest of the payload (the way Tezos signs messages)
+pub fn verify_signature(
+    public_key: &PublicKey,
+    payload: &[u8],
+    signature: &Signature,
+) -> Result<bool> {
+    let crypto = Tezos::default().get_crypto();
+    let message = crypto.blake2b(payload, 32)?;
+    let signature = signature.to_bytes()?;
+
+    let valid = match public_key {
+        PublicKey::Ed25519(public_key) => crypto.verify_ed25519(
+            message.as_slice(),
+            signature.as_slice(),
+            public_key.to_bytes()?.as_slice(),
+        )?,
+        PublicKey::Secp256K1(public_key) => crypto.verify_secp256_k1(
+            message.as_slice(),
+            signature.as_slice(),
+            public_key.to_bytes()?.as_slice(),
+        )?,
+        PublicKey::P256(public_key) => crypto.verify_p256(
+            message.as_slice(),
+            signature.as_slice(),
+            public_key.to_bytes()?.as_slice(),
+        )?,
+    };
+    Ok(valid)
+}
@@ -0,0 +1,85 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use layered_store::{LayeredStore, StoreBackend};
+use tezos_core::types::encoded::{ChainId, Encoded, ImplicitAddress};
+use tezos_proto::{context::TezosContext, executor::reveal::public_key_hash};
+
+use crate::{payload::SponsorSignature, signature::verify_signature, Error, Result};
+
+/// Records the sponsor of the operation, so that the validator charges the fees from it.
+/// Only accounts with a sponsor policy can sponsor operations, the signature covers
+/// the chain id so that it cannot be replayed on another rollup.
+/// Returns the sponsor address.
+pub fn accept_sponsor_signature<Backend: StoreBackend>(
+    context: &mut LayeredStore<Backend>,
+    chain_id: &ChainId,
+    sponsor_signature: &SponsorSignature,
+) -> Result<ImplicitAddress> {
+    let sponsor = public_key_hash(&sponsor_signature.public_key)?;
+    if context.get_sponsor_policy(sponsor.value())?.is_none() {
+        return Err(Error::UnknownSponsor {
+            address: sponsor.value().to_string(),
+        });
+    }
+
+    let signed_bytes = [chain_id.to_bytes()?, sponsor_signature.hash.to_bytes()?].concat();
+    if !verify_signature(
+        &sponsor_signature.public_key,
+        &signed_bytes,
+        &sponsor_signature.signature,
+    )? {
+        return Err(Error::InvalidSponsorSignature);
+    }
+
+    context.set_sponsorship(sponsor_signature.hash.value(), Some(sponsor.clone()))?;
+    context.commit()?;
+    Ok(sponsor)
+}
+
+#[cfg(test)]
+mod test {
+    use layered_store::EphemeralStore;
+    use tezos_proto::context::sponsor::SponsorPolicy;
+
+    use super::*;
+
+    const SPONSOR: &str = "tz1Qr9uevaimfiPS6X1otehsKrwvZjX7bsyL";
+
+    #[test]
+    fn test_accept_sponsor_signature() -> Result<()> {
+        let mut context = EphemeralStore::default();
+        let chain_id: ChainId = "NetXH12Aer3be93".try_into().unwrap();
+        // hash (32 bytes), public key of tz1Qr9uevaimfiPS6X1otehsKrwvZjX7bsyL, signature
+        let signature = SponsorSignature::from_bytes(
+            &hex::decode(
+                "58f3390ad6af4b7fc96e1167449d0532da1210b69297bd9da11707f2c994c68a\
+                00ea4a6c63e29c520abef5507b132ec5f9954776aebebe7b92421eea691446d22c\
+                e92c33e87db1120e2ca2e58caa51fd66ece4427c86a01ca8d0e9a863bae0ffcc\
+                f3c4404690f38f08e5498b650849fb59326a695c8b6cbeb5c0ba9b1c6fe7d909",
+            )
+            .unwrap(),
+        )?;
+
+        assert!(matches!(
+            accept_sponsor_signature(&mut context, &chain_id, &signature),
+            Err(Error::UnknownSponsor { .. })
+        ));
+
+        context.set_sponsor_policy(SPONSOR, SponsorPolicy::default())?;
+        let other_chain: ChainId = "NetXdQprcVkpaWU".try_into().unwrap();
+        assert!(matches!(
+            accept_sponsor_signature(&mut context, &other_chain, &signature),
+            Err(Error::InvalidSponsorSignature)
+        ));
+
+        let sponsor = accept_sponsor_signature(&mut context, &chain_id, &signature)?;
+        assert_eq!(SPONSOR, sponsor.value());
+        assert_eq!(
+            Some(sponsor),
+            context.get_sponsorship(signature.hash.value())?
+        );
+        Ok(())
+    }
+}
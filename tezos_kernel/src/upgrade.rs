@@ -0,0 +1,115 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use kernel_io::{reveal::reveal_data, KernelStore, KernelStoreAsHost};
+use layered_store::{error::err_into, StoreType};
+use tezos_proto::context::TezosContext;
+use tezos_smart_rollup_core::SmartRollupCore;
+use tezos_smart_rollup_host::{path::RefPath, runtime::Runtime};
+
+use crate::{payload::KernelUpgrade, signature::verify_signature, Error, Result};
+
+/// Nonce of the last applied upgrade, protects against replays
+pub const UPGRADE_NONCE_PATH: &str = "/upgrade_nonce";
+/// Upgrade waiting for the current batch to be fully applied
+pub const PENDING_UPGRADE_PATH: &str = "/upgrade/pending";
+
+const STAGING_KERNEL_PATH: RefPath = RefPath::assert_from(b"/upgrade/boot.wasm");
+const BOOT_KERNEL_PATH: RefPath = RefPath::assert_from(b"/kernel/boot.wasm");
+
+impl StoreType for KernelUpgrade {
+    fn from_bytes(bytes: &[u8]) -> layered_store::Result<Self> {
+        KernelUpgrade::from_bytes(bytes).map_err(err_into)
+    }
+
+    fn to_bytes(&self) -> layered_store::Result<Vec<u8>> {
+        KernelUpgrade::to_bytes(self).map_err(err_into)
+    }
+}
+
+pub fn verify_upgrade<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    upgrade: &KernelUpgrade,
+) -> Result<()> {
+    let admin_key = match context.get_admin_key()? {
+        Some(value) => value,
+        None => return Err(Error::AdminKeyMissing),
+    };
+
+    let last_nonce: u64 = context.get(UPGRADE_NONCE_PATH.into())?.unwrap_or(0);
+    if upgrade.nonce <= last_nonce {
+        return Err(Error::UpgradeNonceInThePast {
+            expected: last_nonce + 1,
+            found: upgrade.nonce,
+        });
+    }
+
+    let metadata = Runtime::reveal_metadata(context.as_host());
+    let valid = verify_signature(
+        &admin_key,
+        upgrade
+            .signed_bytes(metadata.raw_rollup_address.as_slice())
+            .as_slice(),
+        &upgrade.signature,
+    )?;
+    match valid {
+        true => Ok(()),
+        false => Err(Error::InvalidUpgradeSignature),
+    }
+}
+
+/// Reveals the new kernel and installs it, the switch happens on the next reboot.
+/// The kernel is first written to a staging path, so that a partial write
+/// never corrupts the running one.
+pub fn apply_upgrade<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    upgrade: KernelUpgrade,
+) -> Result<()> {
+    verify_upgrade(context, &upgrade)?;
+
+    let kernel = reveal_data(context.as_host(), &upgrade.root_hash)?;
+    let host = context.as_host();
+    Runtime::store_write_all(host, &STAGING_KERNEL_PATH, kernel.as_slice())?;
+    Runtime::store_move(host, &STAGING_KERNEL_PATH, &BOOT_KERNEL_PATH)?;
+
+    context.set(UPGRADE_NONCE_PATH.into(), Some(upgrade.nonce))?;
+    context.commit()?;
+    Ok(())
+}
+
+/// Postpones the upgrade until the batch of the current level is applied,
+/// which might take several kernel runs. The upgrade is checked beforehand,
+/// so that a message posted by anyone else cannot replace the pending one.
+pub fn schedule_upgrade<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+    upgrade: KernelUpgrade,
+) -> Result<()> {
+    verify_upgrade(context, &upgrade)?;
+    context.set(PENDING_UPGRADE_PATH.into(), Some(upgrade))?;
+    context.commit()?;
+    Ok(())
+}
+
+/// Installs the scheduled upgrade if there is one, returns its nonce
+pub fn apply_pending_upgrade<Host: SmartRollupCore>(
+    context: &mut KernelStore<Host>,
+) -> Result<Option<u64>> {
+    let upgrade: KernelUpgrade = match context.get(PENDING_UPGRADE_PATH.into())? {
+        Some(upgrade) => upgrade,
+        None => return Ok(None),
+    };
+
+    // Rejected upgrades are discarded as well
+    context.set::<KernelUpgrade>(PENDING_UPGRADE_PATH.into(), None)?;
+    context.commit()?;
+
+    let nonce = upgrade.nonce;
+    match apply_upgrade(context, upgrade) {
+        Ok(()) => Ok(Some(nonce)),
+        Err(err) => {
+            context.rollback();
+            Err(err)
+        }
+    }
+}
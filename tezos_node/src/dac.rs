@@ -0,0 +1,124 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use std::path::Path;
+use tezos_core::internal::crypto::blake2b;
+
+use crate::{rollup::rpc_helpers::parse_operation, Result};
+
+pub const MAX_PAGE_SIZE: usize = 4096;
+pub const PREIMAGE_HASH_SIZE: usize = 33;
+
+const PAGE_HEADER_SIZE: usize = 5;
+const CONTENTS_PAGE_TAG: u8 = 0;
+const HASHES_PAGE_TAG: u8 = 1;
+const MAX_PAYLOAD_SIZE: usize = MAX_PAGE_SIZE - PAGE_HEADER_SIZE;
+
+pub type PreimageHash = [u8; PREIMAGE_HASH_SIZE];
+
+/// Preimage pages forming a Merkle tree, in the format expected by the kernel's `reveal_data`
+pub struct Preimages {
+    pub root_hash: PreimageHash,
+    pub pages: Vec<(PreimageHash, Vec<u8>)>,
+}
+
+impl Preimages {
+    pub fn from_data(data: &[u8]) -> Result<Self> {
+        let mut pages: Vec<(PreimageHash, Vec<u8>)> = Vec::new();
+
+        let mut hashes: Vec<PreimageHash> = Vec::new();
+        if data.is_empty() {
+            hashes.push(push_page(&mut pages, CONTENTS_PAGE_TAG, &[])?);
+        }
+        for chunk in data.chunks(MAX_PAYLOAD_SIZE) {
+            hashes.push(push_page(&mut pages, CONTENTS_PAGE_TAG, chunk)?);
+        }
+
+        let hashes_per_page = MAX_PAYLOAD_SIZE / PREIMAGE_HASH_SIZE;
+        while hashes.len() > 1 {
+            let mut parents: Vec<PreimageHash> = Vec::new();
+            for children in hashes.chunks(hashes_per_page) {
+                let payload = children.concat();
+                parents.push(push_page(&mut pages, HASHES_PAGE_TAG, payload.as_slice())?);
+            }
+            hashes = parents;
+        }
+
+        Ok(Self {
+            root_hash: hashes[0],
+            pages,
+        })
+    }
+
+    /// Encodes operations as a list of length-prefixed messages (see `kernel_io::reveal::reveal_messages`)
+    pub fn from_operations(operations: &[Vec<u8>]) -> Result<Self> {
+        let mut data: Vec<u8> = Vec::new();
+        for operation in operations.iter() {
+            parse_operation(operation.as_slice())?;
+            data.extend_from_slice(&(operation.len() as u32).to_be_bytes());
+            data.extend_from_slice(operation.as_slice());
+        }
+        Self::from_data(data.as_slice())
+    }
+
+    /// Writes pages to the rollup node's preimages directory, one file per page named by its hash
+    pub fn write_to(&self, dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir)?;
+        for (hash, page) in self.pages.iter() {
+            std::fs::write(dir.join(hex::encode(hash)), page)?;
+        }
+        Ok(())
+    }
+}
+
+fn push_page(
+    pages: &mut Vec<(PreimageHash, Vec<u8>)>,
+    tag: u8,
+    payload: &[u8],
+) -> Result<PreimageHash> {
+    let page = [
+        [tag].as_slice(),
+        (payload.len() as u32).to_be_bytes().as_slice(),
+        payload,
+    ]
+    .concat();
+
+    let mut hash = [0u8; PREIMAGE_HASH_SIZE];
+    hash[1..].copy_from_slice(blake2b(page.as_slice(), 32)?.as_slice());
+
+    pages.push((hash, page));
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_single_page() -> Result<()> {
+        let preimages = Preimages::from_data(b"deadbeef")?;
+        assert_eq!(1, preimages.pages.len());
+        assert_eq!(preimages.root_hash, preimages.pages[0].0);
+        assert_eq!(0u8, preimages.root_hash[0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_tree() -> Result<()> {
+        let data = vec![1u8; MAX_PAYLOAD_SIZE * 200];
+        let preimages = Preimages::from_data(data.as_slice())?;
+
+        // 200 content pages, 2 hash pages, 1 root page
+        assert_eq!(203, preimages.pages.len());
+        let (root_hash, root_page) = preimages.pages.last().unwrap();
+        assert_eq!(preimages.root_hash, *root_hash);
+        assert_eq!(HASHES_PAGE_TAG, root_page[0]);
+        assert_eq!(PAGE_HEADER_SIZE + 2 * PREIMAGE_HASH_SIZE, root_page.len());
+
+        for (_, page) in preimages.pages.iter() {
+            assert!(page.len() <= MAX_PAGE_SIZE);
+        }
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@
 //
 // SPDX-License-Identifier: MIT
 
+pub mod dac;
 pub mod error;
 pub mod rollup;
 pub mod services;
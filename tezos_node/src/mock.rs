@@ -3,15 +3,32 @@
 // SPDX-License-Identifier: MIT
 
 use actix_web::web::Data;
+use clap::Parser;
 use std::time::Duration;
 use tezos_node::{launch_node, rollup::mock_client::RollupMockClient, rollup::RollupClient};
+use tezos_proto::context::genesis::Genesis;
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(short, long)]
+    genesis: Option<String>,
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     std::env::set_var("RUST_LOG", "debug");
     env_logger::init();
 
+    let args = Args::parse();
+
     let mut client = RollupMockClient::default();
+    if let Some(path) = args.genesis {
+        let value = std::fs::read_to_string(path).expect("Failed to read genesis file");
+        let genesis = Genesis::from_json(&value).expect("Failed to parse genesis file");
+        client
+            .install_genesis(genesis)
+            .expect("Failed to install genesis");
+    }
     client
         .initialize()
         .await
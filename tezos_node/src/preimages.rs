@@ -0,0 +1,39 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use clap::Parser;
+use std::path::PathBuf;
+use tezos_node::dac::Preimages;
+
+/// Prepares preimage pages for a set of signed operations so that a sequencer can
+/// reference them by root hash in a signed batch (payload tag 05, kind 01) instead of the inbox
+#[derive(Parser, Debug)]
+struct Args {
+    /// File containing hex-encoded signed operations, one per line
+    #[arg(short, long)]
+    input: PathBuf,
+    /// Rollup node preimages directory, e.g. <data-dir>/wasm_2_0_0
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let input = std::fs::read_to_string(args.input).expect("Failed to read input file");
+    let operations: Vec<Vec<u8>> = input
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| hex::decode(line).expect("Failed to decode operation"))
+        .collect();
+
+    let preimages =
+        Preimages::from_operations(operations.as_slice()).expect("Failed to prepare preimages");
+    preimages
+        .write_to(args.output.as_path())
+        .expect("Failed to write preimages");
+
+    println!("{}", hex::encode(preimages.root_hash));
+}
@@ -10,7 +10,6 @@ pub mod rpc_client;
 pub mod rpc_helpers;
 
 use std::collections::VecDeque;
-use std::sync::{Arc, Mutex};
 
 use actix_web::web::Bytes;
 use async_trait::async_trait;
@@ -18,12 +17,18 @@ use layered_store::StoreType;
 use serde::Serialize;
 use tezos_core::types::encoded::{
     Address, BlockHash, ChainId, ContractAddress, Encoded, ImplicitAddress, OperationHash,
-    ProtocolHash, PublicKey, ScriptExprHash,
+    ProtocolHash, PublicKey, ScriptExprHash, Signature,
 };
 use tezos_core::types::{mutez::Mutez, number::Nat};
 use tezos_michelson::micheline::Micheline;
 use tezos_operation::operations::SignedOperation;
-use tezos_proto::context::{batch::BatchReceipt, head::Head, store::OperationReceipt};
+use tezos_proto::{
+    config::TezosConfig,
+    context::{
+        batch::BatchReceipt, head::Head, outbox::OutboxMessage, proof::ContextProof,
+        store::OperationReceipt,
+    },
+};
 use tezos_rpc::models::{
     block::{Block, FullHeader, Metadata},
     contract::{ContractEntrypoints, ContractInfo, ContractScript},
@@ -32,7 +37,7 @@ use tezos_rpc::models::{
 };
 use tokio::sync::mpsc::Receiver;
 
-use crate::Result;
+use crate::{Error, Result};
 pub use block_id::BlockId;
 
 #[async_trait]
@@ -43,14 +48,41 @@ pub trait RollupClient: Sync + Send {
     async fn get_version(&self) -> Result<VersionInfo>;
     async fn is_chain_synced(&self) -> Result<bool>;
     async fn inject_batch(&self, messages: Vec<Vec<u8>>) -> Result<()>;
-    fn get_ttl_blocks(&self) -> Result<Arc<Mutex<VecDeque<BlockHash>>>>;
     fn create_channel(&self) -> Result<Receiver<Result<Bytes>>>;
     async fn broadcast_to_channels(&self, data: Bytes) -> Result<()>;
     fn channels_count(&self) -> usize;
 
+    /// Several L2 blocks can share the same inbox level, so blocks other than the head
+    /// are looked up in the index maintained by the kernel rather than in a past state
     async fn get_batch_head(&self, block_id: &BlockId) -> Result<Head> {
-        let head: Head = self.store_get("/head".into(), block_id).await?;
-        Ok(head)
+        let level = match block_id {
+            BlockId::Head => return self.store_get("/head".into(), block_id).await,
+            BlockId::Level(level) => (*level).try_into()?,
+            BlockId::Offset(offset) => {
+                let head = self.get_batch_head(&BlockId::Head).await?;
+                head.level - i32::try_from(*offset)?
+            }
+            BlockId::Hash(hash) => self.get_batch_level(hash).await?,
+        };
+        match self
+            .store_get(format!("/blocks/{}", level), &BlockId::Head)
+            .await
+        {
+            Ok(head) => Ok(head),
+            // Blocks produced before the index was introduced are taken from a past state
+            Err(Error::KeyNotFound { key: _ }) => {
+                let head: Head = self
+                    .store_get("/head".into(), &BlockId::Level(level.try_into()?))
+                    .await?;
+                if head.level != level {
+                    return Err(Error::InvalidArguments {
+                        message: format!("Block {} is not available", level),
+                    });
+                }
+                Ok(head)
+            }
+            Err(err) => Err(err),
+        }
     }
 
     async fn get_batch_level(&self, hash: &BlockHash) -> Result<i32> {
@@ -65,9 +97,18 @@ pub trait RollupClient: Sync + Send {
             BlockId::Hash(hash) => hash.clone(),
             _ => self.get_batch_head(block_id).await?.hash,
         };
-        let receipt: BatchReceipt = self
+        let mut receipt: BatchReceipt = self
             .store_get(format!("/batches/{}", hash.value()), &BlockId::Head)
             .await?;
+        // Sequencer signs the header once the block is produced
+        receipt.header.signature = match self
+            .store_get::<Signature>(format!("/signatures/{}", hash.value()), &BlockId::Head)
+            .await
+        {
+            Ok(signature) => Some(signature),
+            Err(Error::KeyNotFound { key: _ }) => None,
+            Err(err) => return Err(err),
+        };
         Ok(receipt)
     }
 
@@ -93,6 +134,7 @@ pub trait TezosFacade {
     async fn get_block_metadata(&self, block_id: &BlockId) -> Result<Metadata>;
     async fn get_block_protocols(&self, block_id: &BlockId) -> Result<BlockProtocols>;
     async fn get_live_blocks(&self, block_id: &BlockId) -> Result<VecDeque<BlockHash>>;
+    async fn get_constants(&self, block_id: &BlockId) -> Result<TezosConfig>;
     async fn get_contract(&self, block_id: &BlockId, address: &Address) -> Result<ContractInfo>;
     async fn get_contract_balance(&self, block_id: &BlockId, address: &Address) -> Result<Mutez>;
     async fn get_contract_counter(
@@ -131,6 +173,8 @@ pub trait TezosFacade {
         big_map_id: i64,
         key_hash: &ScriptExprHash,
     ) -> Result<Micheline>;
+    async fn get_context_proof(&self, block_id: &BlockId, key: &str) -> Result<ContextProof>;
+    async fn get_outbox_messages(&self, block_id: &BlockId) -> Result<Vec<OutboxMessage>>;
     async fn get_operation_hash(
         &self,
         block_id: &BlockId,
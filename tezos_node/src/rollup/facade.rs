@@ -4,6 +4,7 @@
 
 use actix_web::web::Bytes;
 use async_trait::async_trait;
+use layered_store::merkle::{blake2b_256, node_path, MerkleProof, Node};
 use michelson_vm::entrypoints::collect_entrypoints;
 use std::collections::{HashMap, VecDeque};
 use tezos_core::types::encoded::{
@@ -13,6 +14,14 @@ use tezos_core::types::encoded::{
 use tezos_core::types::{mutez::Mutez, number::Nat};
 use tezos_michelson::micheline::Micheline;
 use tezos_michelson::michelson::types::Type;
+use tezos_proto::{
+    config::TezosConfig,
+    context::{
+        head::LiveBlocks,
+        outbox::{outbox_path, OutboxMessage, OutboxMessages},
+        proof::ContextProof,
+    },
+};
 use tezos_rpc::models::{
     block::{Block, FullHeader, Metadata},
     contract::{ContractEntrypoints, ContractInfo, ContractScript},
@@ -209,6 +218,66 @@ impl<T: RollupClient + Send + Sync> TezosFacade for T {
         Ok(value)
     }
 
+    async fn get_context_proof(&self, block_id: &BlockId, key: &str) -> Result<ContextProof> {
+        let receipt = self.get_batch_receipt(block_id).await?;
+        let key_hash = blake2b_256(key.as_bytes());
+
+        let mut proof = MerkleProof::default();
+        loop {
+            let node: Option<Node> = match self
+                .store_get(node_path(proof.siblings.len(), &key_hash), block_id)
+                .await
+            {
+                Ok(node) => Some(node),
+                Err(Error::KeyNotFound { key: _ }) => None,
+                Err(err) => return Err(err),
+            };
+            if proof.push(&key_hash, node) {
+                break;
+            }
+        }
+
+        let value: Option<Vec<u8>> = match self.store_get(key.into(), block_id).await {
+            Ok(value) => Some(value),
+            Err(Error::KeyNotFound { key: _ }) => None,
+            Err(err) => return Err(err),
+        };
+
+        let proof = ContextProof::new(receipt.header.context, key.into(), value.as_deref(), &proof);
+        // Rollup node keeps only the state at the end of each inbox level,
+        // so the context of earlier blocks in the same level cannot be proven
+        if proof.verify().is_err() {
+            return Err(Error::InvalidArguments {
+                message: format!("Context of block {} is not available", receipt.hash.value()),
+            });
+        }
+        Ok(proof)
+    }
+
+    async fn get_outbox_messages(&self, block_id: &BlockId) -> Result<Vec<OutboxMessage>> {
+        let receipt = self.get_batch_receipt(block_id).await?;
+        // Kernel writes the outbox at the end of each run, so messages are grouped by inbox level
+        let inbox_level: i64 = match self
+            .store_get(
+                format!("/levels/l2/{}", receipt.header.level),
+                &BlockId::Head,
+            )
+            .await
+        {
+            Ok(level) => level,
+            Err(Error::KeyNotFound { key: _ }) => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        match self
+            .store_get::<OutboxMessages>(outbox_path(inbox_level as i32), &BlockId::Head)
+            .await
+        {
+            Ok(messages) => Ok(messages.0),
+            Err(Error::KeyNotFound { key: _ }) => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+
     async fn get_operation_hash_list(
         &self,
         block_id: &BlockId,
@@ -280,12 +349,17 @@ impl<T: RollupClient + Send + Sync> TezosFacade for T {
         })
     }
 
-    async fn get_live_blocks(&self, _block_id: &BlockId) -> Result<VecDeque<BlockHash>> {
-        let live_blocks_ptr = self.get_ttl_blocks().unwrap();
-        let live_blocks = live_blocks_ptr.lock().unwrap();
-        // TODO: remove hashes after block_id?
-        //let block_hash = self.get_block_hash(block_id).await.unwrap();
-        Ok(live_blocks.clone())
+    async fn get_live_blocks(&self, block_id: &BlockId) -> Result<VecDeque<BlockHash>> {
+        let live_blocks: LiveBlocks = self.store_get("/live_blocks".into(), block_id).await?;
+        Ok(live_blocks.0)
+    }
+
+    async fn get_constants(&self, block_id: &BlockId) -> Result<TezosConfig> {
+        match self.store_get("/config".into(), block_id).await {
+            Ok(config) => Ok(config),
+            Err(Error::KeyNotFound { key: _ }) => Ok(TezosConfig::default()),
+            Err(err) => Err(err),
+        }
     }
 
     async fn get_heads_main_channel(&self) -> Result<Receiver<Result<Bytes>>> {
@@ -6,14 +6,16 @@ use actix_web::web::Bytes;
 use async_trait::async_trait;
 use layered_store::{ephemeral::EphemeralCopy, StoreType};
 use log::debug;
-use std::collections::VecDeque;
 use std::sync::Mutex;
 use std::{cell::RefCell, sync::Arc};
-use tezos_core::types::encoded::{BlockHash, ChainId, Encoded, OperationHash};
+use tezos_core::types::encoded::{ChainId, Encoded, OperationHash};
 use tezos_operation::operations::SignedOperation;
 use tezos_proto::{
     batcher::apply_batch,
-    context::{head::Head, migrations::run_migrations, TezosContext, TezosEphemeralContext},
+    context::{
+        genesis::Genesis, head::Head, migrations::run_migrations, TezosContext,
+        TezosEphemeralContext,
+    },
     executor::operation::execute_operation,
     validator::operation::{validate_operation, ValidatedOperation},
 };
@@ -33,7 +35,6 @@ const CHAIN_ID: &str = "NetXP2FfcNxFANL";
 pub struct RollupMockClient {
     context: Mutex<RefCell<TezosEphemeralContext>>,
     mempool: Mutex<RefCell<Vec<(OperationHash, SignedOperation)>>>,
-    ttl_blocks: Arc<Mutex<VecDeque<BlockHash>>>,
     channels: Arc<Mutex<Vec<Sender<Result<Bytes>>>>>,
 }
 
@@ -43,14 +44,11 @@ macro_rules! get_mut {
     };
 }
 
-const MAX_TTL_BLOCKS_COUNT: usize = 60;
-
 impl Default for RollupMockClient {
     fn default() -> Self {
         Self {
             context: Mutex::new(RefCell::new(TezosEphemeralContext::default())),
             mempool: Mutex::new(RefCell::new(Vec::new())),
-            ttl_blocks: Arc::new(Mutex::new(VecDeque::with_capacity(MAX_TTL_BLOCKS_COUNT))),
             channels: Arc::new(Mutex::new(Vec::new())),
         }
     }
@@ -69,6 +67,14 @@ impl RollupMockClient {
         Ok(())
     }
 
+    /// Installs a custom genesis document, has to be called before initialization
+    pub fn install_genesis(&self, genesis: Genesis) -> Result<()> {
+        let context = get_mut!(self.context);
+        context.set_genesis(genesis)?;
+        context.commit()?;
+        Ok(())
+    }
+
     pub fn patch(&self, func: fn(&mut TezosEphemeralContext) -> Result<()>) -> Result<()> {
         func(get_mut!(self.context))
     }
@@ -132,10 +138,6 @@ impl RollupClient for RollupMockClient {
         Ok(rx)
     }
 
-    fn get_ttl_blocks(&self) -> Result<Arc<Mutex<VecDeque<BlockHash>>>> {
-        Ok(Arc::clone(&self.ttl_blocks))
-    }
-
     async fn broadcast_to_channels(&self, data: Bytes) -> Result<()> {
         let mut channels = self.channels.lock().unwrap();
         let mut i = 0;
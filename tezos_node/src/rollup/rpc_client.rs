@@ -2,10 +2,7 @@
 //
 // SPDX-License-Identifier: MIT
 
-use std::{
-    collections::VecDeque,
-    sync::{Arc, Mutex},
-};
+use std::sync::{Arc, Mutex};
 
 use actix_web::web::{Bytes, Data};
 use async_trait::async_trait;
@@ -14,7 +11,7 @@ use layered_store::StoreType;
 use log::debug;
 use reqwest::Client;
 use serde::Deserialize;
-use tezos_core::types::encoded::{BlockHash, ChainId, Encoded, SmartRollupAddress};
+use tezos_core::types::encoded::{ChainId, Encoded, SmartRollupAddress};
 use tezos_rpc::models::{
     block::FullHeader,
     version::{AdditionalInfo, CommitInfo, NetworkVersion, Version, VersionInfo},
@@ -69,8 +66,6 @@ pub struct RollupRpcClient {
     pub base_url: String,
     client: Client,
     chain_id: Option<ChainId>,
-    origination_level: Option<u32>,
-    ttl_blocks: Arc<Mutex<VecDeque<BlockHash>>>,
     channels: Arc<Mutex<Vec<Sender<Result<Bytes>>>>>,
 }
 
@@ -81,11 +76,7 @@ impl RollupRpcClient {
         Self {
             base_url: endpoint.into(),
             client: Client::new(),
-            origination_level: None,
             chain_id: None,
-            ttl_blocks: Arc::new(Mutex::new(VecDeque::with_capacity(
-                MAX_TTL_BLOCKS_COUNT as usize,
-            ))),
             channels: Arc::new(Mutex::new(Vec::new())),
         }
     }
@@ -158,25 +149,63 @@ impl RollupRpcClient {
         }
     }
 
+    /// L1 level at which the given L2 block was produced (mapping is maintained by the kernel).
+    /// Several L2 blocks can share the same L1 level, but rollup node keeps only the state
+    /// at the end of each L1 level, so only the last of them can be queried.
+    async fn get_inbox_level(&self, level: i32) -> Result<u32> {
+        let inbox_level: i64 = match self
+            .store_get(format!("/levels/l2/{}", level), &BlockId::Head)
+            .await
+        {
+            Ok(inbox_level) => inbox_level,
+            Err(Error::KeyNotFound { key }) => {
+                // Blocks produced by earlier kernel versions are not indexed,
+                // there is one such block per L1 level up to the last indexed one
+                let legacy: i64 = match self
+                    .store_get("/levels/legacy".into(), &BlockId::Head)
+                    .await
+                {
+                    Ok(legacy) => legacy,
+                    Err(Error::KeyNotFound { key: _ }) => return Err(Error::KeyNotFound { key }),
+                    Err(err) => return Err(err),
+                };
+                if level as i64 > legacy || level < 0 {
+                    return Err(Error::KeyNotFound { key });
+                }
+                let legacy_inbox_level: i64 = self
+                    .store_get(format!("/levels/l2/{}", legacy), &BlockId::Head)
+                    .await?;
+                return Ok((legacy_inbox_level - legacy + level as i64).try_into()?);
+            }
+            Err(err) => return Err(err),
+        };
+        let last_level: i64 = self
+            .store_get(format!("/levels/l1/{}", inbox_level), &BlockId::Head)
+            .await?;
+        if last_level != level as i64 {
+            return Err(Error::InvalidArguments {
+                message: format!("State of block {} is not available", level),
+            });
+        }
+        Ok(inbox_level.try_into()?)
+    }
+
     async fn convert_block_id(&self, block_id: &BlockId) -> Result<String> {
         match block_id {
             BlockId::Head => Ok("head".into()),
             BlockId::Level(level) => {
-                let origination_level = self
-                    .origination_level
-                    .ok_or(internal_error!(Misc, "Origination level yet unknown"))?;
-                Ok((level + origination_level).to_string())
+                let inbox_level = self.get_inbox_level(*level as i32).await?;
+                Ok(inbox_level.to_string())
             }
             BlockId::Offset(offset) => {
-                let state_head = self.get_tezos_level().await?;
-                Ok((state_head - offset).to_string())
+                let head = self.get_batch_head(&BlockId::Head).await?;
+                let inbox_level = self.get_inbox_level(head.level - *offset as i32).await?;
+                Ok(inbox_level.to_string())
             }
             BlockId::Hash(hash) => {
                 let level = self.get_batch_level(hash).await?;
-                let origination_level = self
-                    .origination_level
-                    .ok_or(internal_error!(Misc, "Origination level yet unknown"))?;
-                Ok(((level as u32) + origination_level).to_string())
+                let inbox_level = self.get_inbox_level(level).await?;
+                Ok(inbox_level.to_string())
             }
         }
     }
@@ -195,19 +224,9 @@ impl RollupClient for RollupRpcClient {
         let state_level = self.get_state_level(&BlockId::Head).await?;
         debug!("PVM state level: {}", state_level);
 
-        let head_level: u32 = self
-            .get_batch_head(&BlockId::Head)
-            .await?
-            .level
-            .try_into()?;
+        let head_level = self.get_batch_head(&BlockId::Head).await?.level;
         debug!("Chain head level: {}", head_level);
 
-        self.origination_level = Some(state_level - head_level);
-        debug!(
-            "Rollup origination level: {}",
-            self.origination_level.as_ref().unwrap()
-        );
-
         Ok(())
     }
 
@@ -301,10 +320,6 @@ impl RollupClient for RollupRpcClient {
         Ok(rx)
     }
 
-    fn get_ttl_blocks(&self) -> Result<Arc<Mutex<VecDeque<BlockHash>>>> {
-        Ok(Arc::clone(&self.ttl_blocks))
-    }
-
     async fn broadcast_to_channels(&self, data: Bytes) -> Result<()> {
         let mut channels = self.channels.lock().unwrap();
         let mut i = 0;
@@ -359,17 +374,6 @@ pub fn run_block_updater<T: RollupClient + 'static>(client: &Data<T>) -> () {
                     .await
                     .unwrap();
 
-                {
-                    let ttl_blocks_ptr = client.get_ttl_blocks().unwrap();
-                    let mut ttl_blocks: std::sync::MutexGuard<'_, VecDeque<BlockHash>> =
-                        ttl_blocks_ptr.lock().unwrap();
-
-                    if ttl_blocks.len() == MAX_TTL_BLOCKS_COUNT as usize {
-                        ttl_blocks.pop_front();
-                    }
-                    ttl_blocks.push_back(batch_receipt.hash.clone());
-                }
-
                 curr_level += 1;
 
                 if client.channels_count() == 0 {
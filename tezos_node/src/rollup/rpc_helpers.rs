@@ -38,13 +38,63 @@ pub fn parse_operation(payload: &[u8]) -> Result<(OperationHash, SignedOperation
     Ok((hash, opg))
 }
 
+// Inbox message limit minus the external message tag
+const MAX_EXTERNAL_MESSAGE_SIZE: usize = 4095;
+// Largest operation the kernel reassembles from chunks, see tezos_kernel payload format
+const MAX_OPERATION_SIZE: usize = 32 * 1024;
+const OPERATION_TAG: u8 = 0;
+const CHUNK_TAG: u8 = 2;
+
+/// Wraps operation into one or several external messages, see tezos_kernel payload format
+pub fn make_external_messages(
+    prefix: &[u8],
+    hash: &OperationHash,
+    payload: Vec<u8>,
+) -> Result<Vec<Vec<u8>>> {
+    if payload.len() > MAX_OPERATION_SIZE {
+        return Err(Error::InvalidArguments {
+            message: format!("Operation size exceeds {} bytes", MAX_OPERATION_SIZE),
+        });
+    }
+
+    if prefix.len() + 1 + payload.len() <= MAX_EXTERNAL_MESSAGE_SIZE {
+        return Ok(vec![[prefix, &[OPERATION_TAG], payload.as_slice()].concat()]);
+    }
+
+    let hash = hash.to_bytes()?;
+    let header_size = prefix.len() + 1 + hash.len() + 4;
+    let chunks = payload.chunks(MAX_EXTERNAL_MESSAGE_SIZE - header_size);
+    let total: u16 = chunks
+        .len()
+        .try_into()
+        .map_err(|_| Error::InvalidArguments {
+            message: "Payload too large".into(),
+        })?;
+
+    let messages = chunks
+        .enumerate()
+        .map(|(index, data)| {
+            [
+                prefix,
+                &[CHUNK_TAG],
+                hash.as_slice(),
+                (index as u16).to_be_bytes().as_slice(),
+                total.to_be_bytes().as_slice(),
+                data,
+            ]
+            .concat()
+        })
+        .collect();
+    Ok(messages)
+}
+
 #[async_trait]
 impl TezosHelpers for RollupRpcClient {
     async fn inject_operation(&self, payload: Vec<u8>) -> Result<OperationHash> {
         let (hash, _) = parse_operation(payload.as_slice())?;
         let chain_id = self.get_chain_id().await?;
-        let message = [chain_id.to_bytes()?, payload].concat();
-        self.inject_batch(vec![message]).await?;
+        let messages = make_external_messages(chain_id.to_bytes()?.as_slice(), &hash, payload)?;
+        self.inject_batch(messages).await?;
         Ok(hash)
     }
 
@@ -69,3 +119,34 @@ impl TezosHelpers for RollupRpcClient {
         .await?
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_make_external_messages() -> Result<()> {
+        let hash: OperationHash =
+            "ooKPP7mUdw5fKbXsPPReekQz6LWGpG4yLYg3NEDK6ESd4wCJq7x".try_into()?;
+
+        let messages = make_external_messages(&[0u8; 4], &hash, vec![1u8; 100])?;
+        assert_eq!(1, messages.len());
+        assert_eq!(OPERATION_TAG, messages[0][4]);
+
+        let messages = make_external_messages(&[0u8; 4], &hash, vec![1u8; 10000])?;
+        assert_eq!(3, messages.len());
+        for (index, message) in messages.iter().enumerate() {
+            assert!(message.len() <= MAX_EXTERNAL_MESSAGE_SIZE);
+            assert_eq!(CHUNK_TAG, message[4]);
+            assert_eq!(hash.to_bytes()?.as_slice(), &message[5..37]);
+            assert_eq!((index as u16).to_be_bytes(), message[37..39]);
+            assert_eq!(3u16.to_be_bytes(), message[39..41]);
+        }
+
+        assert!(matches!(
+            make_external_messages(&[0u8; 4], &hash, vec![1u8; MAX_OPERATION_SIZE + 1]),
+            Err(Error::InvalidArguments { .. })
+        ));
+        Ok(())
+    }
+}
@@ -11,8 +11,13 @@ pub mod shell;
 
 use crate::rollup::{RollupClient, TezosFacade, TezosHelpers};
 use crate::services::{
-    blocks::{block, block_hash, block_header, block_metadata, block_protocols, live_blocks},
-    context::{big_map_value, big_map_value_normalized, constants, delegate, delegates},
+    blocks::{
+        block, block_hash, block_header, block_metadata, block_protocols, live_blocks,
+        outbox_messages,
+    },
+    context::{
+        big_map_value, big_map_value_normalized, constants, context_proof, delegate, delegates,
+    },
     contracts::{
         contract, contract_balance, contract_counter, contract_delegate, contract_entrypoints,
         contract_public_key, contract_raw_json_bytes_stub, contract_script,
@@ -88,6 +93,10 @@ pub fn config<T: RollupClient + TezosFacade + TezosHelpers + 'static>(cfg: &mut
             "/chains/main/blocks/{block_id}/live_blocks",
             get().to(live_blocks::<T>),
         )
+        .route(
+            "/chains/main/blocks/{block_id}/outbox",
+            get().to(outbox_messages::<T>),
+        )
         .route("/chains/main/blocks/{block_id}", get().to(block::<T>))
         .route("/monitor/bootstrapped", get().to(bootstrap_info::<T>))
         .route("/monitor/heads/main", get().to(heads_main::<T>))
@@ -101,7 +110,7 @@ pub fn config<T: RollupClient + TezosFacade + TezosHelpers + 'static>(cfg: &mut
         )
         .route(
             "/chains/main/blocks/{block_id}/context/constants",
-            get().to(constants),
+            get().to(constants::<T>),
         )
         .route(
             "/chains/main/blocks/{block_id}/context/big_maps/{big_map_id}/{key_hash}",
@@ -111,6 +120,10 @@ pub fn config<T: RollupClient + TezosFacade + TezosHelpers + 'static>(cfg: &mut
             "/chains/main/blocks/{block_id}/context/big_maps/{big_map_id}/{key_hash}/normalized",
             post().to(big_map_value_normalized::<T>),
         )
+        .route(
+            "/chains/main/blocks/{block_id}/context/proof/{key:.*}",
+            get().to(context_proof::<T>),
+        )
         .route(
             "/chains/main/blocks/{block_id}/context/contracts/{contract_id}/manager_key",
             get().to(contract_public_key::<T>),
@@ -125,6 +125,16 @@ pub async fn live_blocks<T: TezosFacade>(
     Ok(json_response!(value))
 }
 
+pub async fn outbox_messages<T: TezosFacade>(
+    client: Data<T>,
+    path: Path<(String,)>,
+) -> Result<impl Responder> {
+    let value = client
+        .get_outbox_messages(&path.0.as_str().try_into()?)
+        .await?;
+    Ok(json_response!(value))
+}
+
 pub async fn block<T: TezosFacade>(
     client: Data<T>,
     path: Path<(String,)>,
@@ -157,10 +167,14 @@ pub async fn heads_main<T: TezosFacade>(client: Data<T>) -> Result<impl Responde
 mod test {
     use actix_web::{test, web::Data, App};
     use tezos_core::types::encoded::{BlockHash, Encoded};
-    use tezos_proto::context::{
-        batch::{BatchHeader, BatchReceipt},
-        head::Head,
-        TezosContext,
+    use tezos_proto::{
+        context::{
+            batch::{BatchHeader, BatchReceipt},
+            head::Head,
+            outbox::{outbox_path, OutboxMessage, OutboxMessages},
+            TezosContext,
+        },
+        executor::withdrawal::Withdrawal,
     };
     use tezos_rpc::models::block::FullHeader;
 
@@ -198,7 +212,9 @@ mod test {
                 context: "CoVnr5Sy57UkHt1Aqmw62KyLUYxVmKCyp45HY7MXW8sFemT3Uf6i"
                     .try_into()
                     .unwrap(),
+                signature: None,
             },
+            batcher: None,
         }
     }
 
@@ -256,6 +272,53 @@ mod test {
         Ok(())
     }
 
+    #[actix_web::test]
+    async fn test_block_header_by_level() -> Result<()> {
+        let client = RollupMockClient::default();
+        client.patch(|context| {
+            // two blocks produced at the same inbox level, the second one is the head
+            let hashes = [
+                "BM4iF1PGVN74h1kvqUtY26boVKpZuJFvpQRN34JLYSkQ9G3jBnn",
+                "BLTssqiWSEK2Vp1HWYfb4hF58Tt1rWyznr8jcBSk9c32o3te5xc",
+            ];
+            for (level, hash) in hashes.iter().enumerate() {
+                let mut head = Head::default();
+                head.level = level as i32 + 1;
+                head.hash = (*hash).try_into().unwrap();
+
+                let mut receipt = get_test_batch_receipt();
+                receipt.hash = head.hash.clone();
+                receipt.header.level = head.level;
+
+                context.set_batch_receipt(receipt).unwrap();
+                context.set_block_head(head.clone()).unwrap();
+                context.set_head(head).unwrap();
+            }
+            Ok(())
+        })?;
+
+        let app = test::init_service(
+            App::new()
+                .configure(config::<RollupMockClient>)
+                .app_data(Data::new(client)),
+        )
+        .await;
+
+        for uri in [
+            "/chains/main/blocks/1/header",
+            "/chains/main/blocks/head~1/header",
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let res: FullHeader = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(1, res.level);
+            assert_eq!(
+                "BM4iF1PGVN74h1kvqUtY26boVKpZuJFvpQRN34JLYSkQ9G3jBnn",
+                res.hash.value()
+            );
+        }
+        Ok(())
+    }
+
     #[actix_web::test]
     async fn test_block_header_shell() -> Result<()> {
         let client = RollupMockClient::default();
@@ -322,4 +385,49 @@ mod test {
         );
         Ok(())
     }
+
+    #[actix_web::test]
+    async fn test_outbox_messages() -> Result<()> {
+        let client = RollupMockClient::default();
+        client.patch(|context| {
+            context.set_head(Head::default()).unwrap();
+            context.set_batch_receipt(get_test_batch_receipt()).unwrap();
+            context
+                .set("/levels/l2/3113764".into(), Some(42i64))
+                .unwrap();
+            let withdrawal = Withdrawal {
+                ticketer: "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm".try_into().unwrap(),
+                receiver: "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU".try_into().unwrap(),
+                amount: 100u32.into(),
+            };
+            context
+                .set(
+                    outbox_path(42),
+                    Some(OutboxMessages(vec![OutboxMessage::new(
+                        42,
+                        0,
+                        withdrawal.outbox_call().unwrap(),
+                    )])),
+                )
+                .unwrap();
+            Ok(())
+        })?;
+
+        let app = test::init_service(
+            App::new()
+                .configure(config::<RollupMockClient>)
+                .app_data(Data::new(client)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/chains/main/blocks/head/outbox")
+            .to_request();
+        let res: Vec<OutboxMessage> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(1, res.len());
+        assert_eq!(42, res[0].outbox_level);
+        assert_eq!(0, res[0].index);
+        assert_eq!("withdraw", res[0].entrypoint);
+        Ok(())
+    }
 }
@@ -8,12 +8,15 @@ use actix_web::{
     HttpResponse, Responder, Result,
 };
 use tezos_core::types::encoded::ScriptExprHash;
-use tezos_proto::config::Config;
 
 use crate::{json_response, rollup::TezosFacade, Error};
 
-pub async fn constants() -> Result<impl Responder> {
-    Ok(json_response!(Config::default().tezos))
+pub async fn constants<T: TezosFacade>(
+    client: Data<T>,
+    path: Path<(String,)>,
+) -> Result<impl Responder> {
+    let value = client.get_constants(&path.0.as_str().try_into()?).await?;
+    Ok(json_response!(value))
 }
 
 pub async fn delegates() -> Result<impl Responder> {
@@ -42,3 +45,54 @@ pub async fn big_map_value_normalized<T: TezosFacade>(
     // TODO: handle unparsing mode
     big_map_value(client, path).await
 }
+
+pub async fn context_proof<T: TezosFacade>(
+    client: Data<T>,
+    path: Path<(String, String)>,
+) -> Result<impl Responder> {
+    let key = format!("/context/{}", path.1);
+    let value = client
+        .get_context_proof(&path.0.as_str().try_into()?, &key)
+        .await?;
+    Ok(json_response!(value))
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::{test, web::Data, App};
+    use tezos_proto::context::proof::ContextProof;
+
+    use crate::{
+        rollup::{mock_client::RollupMockClient, RollupClient},
+        services::config,
+        Result,
+    };
+
+    #[actix_web::test]
+    async fn test_balance_proof() -> Result<()> {
+        let mut client = RollupMockClient::default();
+        client.initialize().await?;
+
+        let app = test::init_service(
+            App::new()
+                .configure(config::<RollupMockClient>)
+                .app_data(Data::new(client)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/chains/main/blocks/head/context/proof/contracts/tz1grSQDByRpnVs7sPtaprNZRp531ZKz6Jmm/balance")
+            .to_request();
+        let res: ContextProof = test::call_and_read_body_json(&app, req).await;
+        assert!(res.value.is_some());
+        res.verify()?;
+
+        let req = test::TestRequest::get()
+            .uri("/chains/main/blocks/head/context/proof/contracts/tz1N2386URdGRJDbMuEEn757Y5HBPg5XwtmQ/balance")
+            .to_request();
+        let res: ContextProof = test::call_and_read_body_json(&app, req).await;
+        assert!(res.value.is_none());
+        res.verify()?;
+        Ok(())
+    }
+}
@@ -6,25 +6,33 @@ use michelson_vm::interpreter::InterpreterContext;
 use tezos_core::{
     internal::coder::Encoder,
     internal::crypto::blake2b,
-    types::encoded::{BlockHash, BlockPayloadHash, Encoded, OperationHash, OperationListListHash},
+    types::encoded::{
+        BlockHash, BlockPayloadHash, ContextHash, Encoded, OperationHash, OperationListListHash,
+    },
 };
 use tezos_operation::{
     block_header, internal::coder::operation_content_bytes_coder::OperationContentBytesCoder,
     operations::SignedOperation,
 };
-use tezos_rpc::models::operation::Operation as OperationReceipt;
+use tezos_rpc::models::balance_update::BalanceUpdate;
 
 use crate::{
     config::*,
     context::{
-        batch::{BatchHeader, BatchReceipt},
+        batch::{BatchHeader, BatchReceipt, PendingBatch},
         head::Head,
         migrations::run_migrations,
         TezosContext,
     },
-    executor::operation::execute_operation,
+    executor::{
+        balance_updates::BalanceUpdates,
+        deposit::{apply_deposit, refund_deposit, Deposit},
+        operation::apply_operation,
+        scheduler::execute_scheduled_calls,
+    },
+    internal_error,
     validator::{batch::validate_batch, operation::ValidOperation},
-    Result,
+    Error, Result,
 };
 
 pub fn block_hash(header: BatchHeader) -> Result<BlockHash> {
@@ -34,7 +42,12 @@ pub fn block_hash(header: BatchHeader) -> Result<BlockHash> {
     Ok(BlockHash::from_bytes(&hash)?)
 }
 
-fn naive_header(prev_head: Head, operations: &Vec<ValidOperation>) -> Result<BatchHeader> {
+fn naive_header(
+    prev_head: Head,
+    timestamp: i64,
+    context_hash: ContextHash,
+    operations: &Vec<ValidOperation>,
+) -> Result<BatchHeader> {
     let operation_hashes: Vec<OperationHash> = operations.iter().map(|o| o.hash.clone()).collect();
     Ok(BatchHeader {
         level: prev_head.level + 1,
@@ -46,56 +59,196 @@ fn naive_header(prev_head: Head, operations: &Vec<ValidOperation>) -> Result<Bat
             vec![],
             operation_hashes,
         ])?,
-        context: "CoVhFUFQvxrbdDDemLCSXU4DLMfsVdvrbgCCXcCKBWqPAQ1gK8cL".try_into()?, // TODO
-        timestamp: prev_head.timestamp + BLOCK_TIME,
+        context: context_hash,
+        timestamp,
+        signature: None,
     })
 }
 
-pub fn apply_batch(
+/// Runs pending migrations, credits L1 deposits, executes due scheduled calls
+/// and validates operations, operations are not applied yet.
+/// Timestamp of the new block is exposed to contracts as `NOW`.
+/// Branches of the `forced` operations are not checked, they were checked when the
+/// operations were queued.
+pub fn begin_batch(
     context: &mut (impl TezosContext + InterpreterContext),
     prev_head: Head,
+    timestamp: i64,
     batch_payload: Vec<(OperationHash, SignedOperation)>,
+    forced: &[OperationHash],
+    deposits: Vec<Deposit>,
     atomic: bool,
-) -> Result<Head> {
+) -> Result<PendingBatch> {
     context.check_no_pending_changes()?;
 
-    let balance_updates = run_migrations(context, &prev_head)?;
-    let operations = validate_batch(context, batch_payload, atomic)?;
+    let balance_updates: Vec<BalanceUpdate> =
+        run_migrations(context, &prev_head)?.unwrap_or_default();
+
+    // Deposits go first, so that the funds can be spent in the same block
+    let mut applied_deposits: Vec<Deposit> = Vec::with_capacity(deposits.len());
+    for deposit in deposits.into_iter() {
+        match apply_deposit(context, &deposit) {
+            Ok(()) => {
+                context.commit()?;
+                applied_deposits.push(deposit);
+            }
+            Err(err @ Error::Internal(_)) => return Err(err),
+            Err(err) => {
+                context.rollback();
+                context.log(format!("Deposit rejected: {}", err.format()));
+                refund_deposit(context, &deposit)?;
+                context.commit()?;
+            }
+        }
+    }
+
+    // Scheduled calls go before user operations and see the timestamp of the new block
+    context.set_batch_timestamp(Some(timestamp))?;
+    context.commit()?;
+    let block_fees = execute_scheduled_calls(context, prev_head.level + 1)?;
+
+    let mut steps: usize = 0;
+    let operations = validate_batch(context, batch_payload, forced, atomic, &mut steps)?;
+    context.commit()?;
+
+    Ok(PendingBatch {
+        prev_head,
+        timestamp,
+        operations,
+        cursor: 0,
+        block_fees,
+        batcher: None,
+        deposits: applied_deposits,
+        balance_updates,
+        steps,
+    })
+}
+
+/// Applies the next operation of the batch and stores its receipt.
+/// Returns the number of operation contents processed.
+pub fn apply_next_operation(
+    context: &mut (impl TezosContext + InterpreterContext),
+    batch: &mut PendingBatch,
+) -> Result<usize> {
+    let opg = match batch.operations.get(batch.cursor) {
+        Some(opg) => opg,
+        None => return Ok(0),
+    };
+
+    let (receipt, paid_fees) = apply_operation(context, opg)?;
+    let size = opg.origin.contents.len();
+    context.set_operation_receipt(receipt)?;
+    context.commit()?;
+
+    batch.block_fees += paid_fees;
+    batch.cursor += 1;
+    Ok(size)
+}
+
+pub fn finalize_batch(
+    context: &mut (impl TezosContext + InterpreterContext),
+    batch: PendingBatch,
+) -> Result<Head> {
+    if !batch.is_complete() {
+        return Err(internal_error!(
+            "Batch is not complete: {}/{}",
+            batch.cursor,
+            batch.operations.len()
+        ));
+    }
+
+    let PendingBatch {
+        prev_head,
+        timestamp,
+        operations,
+        block_fees,
+        batcher,
+        deposits,
+        mut balance_updates,
+        ..
+    } = batch;
     let chain_id = prev_head.chain_id.clone();
 
-    let mut operation_receipts: Vec<OperationReceipt> = Vec::with_capacity(operations.len());
-    for opg in operations.iter() {
-        operation_receipts.push(execute_operation(context, opg)?);
+    for deposit in deposits.iter() {
+        balance_updates.append(&mut BalanceUpdates::deposit(
+            deposit.receiver.value(),
+            &deposit.amount,
+        ));
     }
 
-    // TODO: fees to batch producer (balance updates + update balance)
+    if block_fees > 0u32.into() {
+        // Fees go to the sequencer that signed the batch if any
+        let batcher = match &batcher {
+            Some(batcher) => batcher.clone(),
+            None => context.get_batcher()?.ok_or(Error::BatcherNotInitialized)?,
+        };
+        BalanceUpdates::credit(context, batcher.value(), &block_fees)?;
+        balance_updates.append(&mut BalanceUpdates::block_fees(
+            batcher.value(),
+            &block_fees,
+        ));
+    }
 
-    let header = naive_header(prev_head, &operations)?;
+    // Context hash commits to the state after all the operations are applied
+    context.commit()?;
+    let context_hash = context.get_context_hash()?;
+
+    let header = naive_header(prev_head, timestamp, context_hash, &operations)?;
     let hash = block_hash(header.clone())?;
+
+    let mut live_blocks = context.get_live_blocks()?;
+    let ttl = context.get_config()?.max_operations_time_to_live;
+    live_blocks.push(hash.clone(), usize::try_from(ttl).unwrap_or(0));
+    context.set_live_blocks(live_blocks)?;
+
     let receipt = BatchReceipt {
         chain_id: chain_id.clone(),
         protocol: PROTOCOL.try_into().unwrap(),
         hash: hash.clone(),
         header: header.clone(),
-        balance_updates,
+        balance_updates: if balance_updates.is_empty() {
+            None
+        } else {
+            Some(balance_updates)
+        },
+        batcher,
     };
     context.set_batch_receipt(receipt)?;
 
-    let mut opg_hashes: Vec<OperationHash> = Vec::with_capacity(operation_receipts.len());
-    for opg_receipt in operation_receipts {
-        opg_hashes.push(opg_receipt.hash.clone().unwrap());
-        context.set_operation_receipt(opg_receipt)?;
-    }
-
     let head = Head::new(
         chain_id,
         header.level,
         hash.clone(),
         header.timestamp,
-        opg_hashes,
+        operations.into_iter().map(|opg| opg.hash).collect(),
     );
     context.set_head(head.clone())?;
+    // Several blocks can be produced per inbox level, so they are indexed by L2 level
+    context.set_block_head(head.clone())?;
+    context.set_batch_timestamp(None)?;
     context.commit()?;
 
     Ok(head)
 }
+
+pub fn apply_batch(
+    context: &mut (impl TezosContext + InterpreterContext),
+    prev_head: Head,
+    batch_payload: Vec<(OperationHash, SignedOperation)>,
+    atomic: bool,
+) -> Result<Head> {
+    let timestamp = prev_head.timestamp + context.get_config()?.block_time();
+    let mut batch = begin_batch(
+        context,
+        prev_head,
+        timestamp,
+        batch_payload,
+        &[],
+        vec![],
+        atomic,
+    )?;
+    while !batch.is_complete() {
+        apply_next_operation(context, &mut batch)?;
+    }
+    finalize_batch(context, batch)
+}
@@ -2,6 +2,7 @@
 //
 // SPDX-License-Identifier: MIT
 
+use michelson_vm::InterpreterLimits;
 use serde::{Deserialize, Serialize};
 use tezos_core::types::number::Nat;
 
@@ -28,6 +29,36 @@ pub struct DalParametric {
     pub number_of_shards: i32,
 }
 
+/// Rollup-specific resource limits, not part of the L1 constants
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default = "RollupLimits::default")]
+pub struct RollupLimits {
+    pub max_stack_size: usize,
+    pub max_call_depth: usize,
+    pub max_pack_size: usize,
+    pub max_internal_operation_depth: usize,
+    /// Michelson instructions executed per contract call (or wallet validation)
+    pub max_steps: usize,
+    /// Scheduled calls executed per block, the rest is postponed to the next level
+    pub max_scheduled_calls: usize,
+    /// Minimal fee of a scheduled call execution, in mutez
+    pub min_scheduled_fee: u64,
+}
+
+impl RollupLimits {
+    pub fn default() -> Self {
+        Self {
+            max_stack_size: 10000,
+            max_call_depth: 128,
+            max_pack_size: 50000,
+            max_internal_operation_depth: 64,
+            max_steps: 1000000,
+            max_scheduled_calls: 100,
+            min_scheduled_fee: 1000,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TezosConfig {
     pub proof_of_work_nonce_size: i32,
@@ -113,9 +144,28 @@ pub struct TezosConfig {
     pub zk_rollup_enable: bool,
     pub zk_rollup_origination_size: i32,
     pub zk_rollup_min_pending_to_process: i32,
+    #[serde(default = "RollupLimits::default")]
+    pub rollup_limits: RollupLimits,
 }
 
 impl TezosConfig {
+    /// L2 block time in seconds, used unless the sequencer sets batch timestamps explicitly
+    pub fn block_time(&self) -> i64 {
+        self.minimal_block_delay
+            .to_string()
+            .parse()
+            .unwrap_or(BLOCK_TIME)
+    }
+
+    pub fn interpreter_limits(&self) -> InterpreterLimits {
+        InterpreterLimits {
+            max_stack_size: self.rollup_limits.max_stack_size,
+            max_call_depth: self.rollup_limits.max_call_depth,
+            max_pack_size: self.rollup_limits.max_pack_size,
+            max_steps: self.rollup_limits.max_steps,
+        }
+    }
+
     pub fn default() -> Self {
         Self {
             proof_of_work_nonce_size: 8,
@@ -155,7 +205,7 @@ impl TezosConfig {
             liquidity_baking_subsidy: 1250000u64.into(),
             liquidity_baking_toggle_ema_threshold: 1000000000,
             max_operations_time_to_live: 240,
-            minimal_block_delay: 15u64.into(),
+            minimal_block_delay: (BLOCK_TIME as u64).into(),
             delay_increment_per_round: 8u64.into(),
             consensus_committee_size: 7000,
             consensus_threshold: 4667,
@@ -216,6 +266,7 @@ impl TezosConfig {
             zk_rollup_enable: false,
             zk_rollup_origination_size: 4000,
             zk_rollup_min_pending_to_process: 10,
+            rollup_limits: RollupLimits::default(),
         }
     }
 }
@@ -225,6 +276,7 @@ pub struct Config {
     pub max_block_header_length: i32,
     pub max_operations_list_length: i32,
     pub pow_nonce: String,
+    pub batcher: String,
 }
 
 impl Config {
@@ -234,6 +286,7 @@ impl Config {
             max_block_header_length: 2048,
             max_operations_list_length: 1024,
             pow_nonce: "deadbeef".into(),
+            batcher: "tz1KqTpEZ7Yob7QbPE4Hy4Wo8fHG8LhKxZSx".into(), // Bootstrap 1 from Tezos sandbox
         }
     }
 }
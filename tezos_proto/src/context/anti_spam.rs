@@ -0,0 +1,113 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+use tezos_core::types::mutez::Mutez;
+use tezos_operation::operations::OperationContent;
+
+use crate::Result;
+
+/// Minimal fee per operation content kind, in mutez
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MinimalFees {
+    #[serde(default)]
+    pub reveal: u64,
+    #[serde(default)]
+    pub transaction: u64,
+    #[serde(default)]
+    pub origination: u64,
+}
+
+impl MinimalFees {
+    pub fn get(&self, content: &OperationContent) -> Result<Mutez> {
+        let fee = match content {
+            OperationContent::Reveal(_) => self.reveal,
+            OperationContent::Transaction(_) => self.transaction,
+            OperationContent::Origination(_) => self.origination,
+            _ => 0,
+        };
+        Ok(Mutez::try_from(fee)?)
+    }
+}
+
+/// Limits protecting the rollup from spam when operations are cheap (zero fees or sponsored).
+/// Applied to injected operations only, simulations are not affected.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AntiSpamPolicy {
+    /// Maximum number of operations per account within `quota_levels` levels, unlimited if zero
+    #[serde(default)]
+    pub quota_operations: u32,
+    /// Length of the quota window, in L2 levels
+    #[serde(default)]
+    pub quota_levels: i32,
+    #[serde(default)]
+    pub minimal_fees: MinimalFees,
+    /// Number of leading zero bits the operation hash must have, disabled if zero.
+    /// Since gas is not metered, the gas limit can be used as a nonce.
+    #[serde(default)]
+    pub pow_zero_bits: u32,
+}
+
+impl AntiSpamPolicy {
+    /// Index of the quota window the level belongs to
+    pub fn quota_window(&self, level: i32) -> i32 {
+        level / self.quota_levels.max(1)
+    }
+}
+
+/// Number of operations an account has sent within a quota window
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OperationQuota {
+    pub window: i32,
+    pub count: u32,
+}
+
+impl OperationQuota {
+    pub fn count_at(&self, window: i32) -> u32 {
+        match self.window == window {
+            true => self.count,
+            false => 0,
+        }
+    }
+
+    pub fn add(self, count: u32, window: i32) -> Self {
+        Self {
+            window,
+            count: self.count_at(window) + count,
+        }
+    }
+}
+
+/// Number of leading zero bits, used to check the proof of work
+pub fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in bytes.iter() {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_quota_window() {
+        let policy = AntiSpamPolicy {
+            quota_operations: 2,
+            quota_levels: 10,
+            ..Default::default()
+        };
+        let quota = OperationQuota::default().add(1, policy.quota_window(15));
+        assert_eq!(1, quota.count_at(1));
+        assert_eq!(2, quota.clone().add(1, 1).count);
+        assert_eq!(1, quota.add(1, policy.quota_window(20)).count);
+
+        assert_eq!(12, leading_zero_bits(&[0, 0x08, 0]));
+        assert_eq!(0, leading_zero_bits(&[0xff]));
+    }
+}
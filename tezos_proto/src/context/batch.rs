@@ -2,8 +2,12 @@
 //
 // SPDX-License-Identifier: MIT
 
-use tezos_core::types::encoded::{
-    BlockHash, BlockPayloadHash, ChainId, ContextHash, OperationListListHash, ProtocolHash,
+use tezos_core::types::{
+    encoded::{
+        BlockHash, BlockPayloadHash, ChainId, ContextHash, ImplicitAddress, OperationListListHash,
+        ProtocolHash, Signature,
+    },
+    mutez::Mutez,
 };
 use tezos_operation::block_header;
 use tezos_rpc::models::{
@@ -17,7 +21,10 @@ use tezos_rpc::models::{
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
-use crate::config::Config;
+use crate::{
+    config::Config, context::head::Head, executor::deposit::Deposit,
+    validator::operation::ValidOperation,
+};
 
 pub const ZERO_SIGNATURE: &str =
     "sigMzJ4GVAvXEd2RjsKGfG2H9QvqTSKCZsuB2KiHbZRGFz72XgF6KaKADznh674fQgBatxw3xdHqTtMHUZAGRprxy64wg1aq";
@@ -36,7 +43,9 @@ pub struct BatchHeader {
     pub operations_hash: OperationListListHash,
     pub payload_hash: BlockPayloadHash,
     pub context: ContextHash,
-    // pub signature: Option<Signature>
+    /// Sequencer signature of the block hash, submitted after the block is produced
+    #[serde(default)]
+    pub signature: Option<Signature>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -46,7 +55,34 @@ pub struct BatchReceipt {
     pub hash: BlockHash,
     pub header: BatchHeader,
     pub balance_updates: Option<Vec<BalanceUpdate>>,
-    // pub batcher: ImplicitAddress
+    /// Sequencer that signed the batch, `None` for unsigned batches
+    #[serde(default)]
+    pub batcher: Option<ImplicitAddress>,
+}
+
+/// Batch which application can be split across several kernel runs
+#[derive(Clone, Debug)]
+pub struct PendingBatch {
+    pub prev_head: Head,
+    pub timestamp: i64,
+    pub operations: Vec<ValidOperation>,
+    /// Number of operations already applied
+    pub cursor: usize,
+    pub block_fees: Mutez,
+    /// Sequencer that signed the batch, receives block fees
+    pub batcher: Option<ImplicitAddress>,
+    /// L1 deposits credited at the beginning of the batch
+    pub deposits: Vec<Deposit>,
+    /// Produced by migrations, only contract credits are expected
+    pub balance_updates: Vec<BalanceUpdate>,
+    /// Instructions executed by wallet validations when the batch began, not persisted
+    pub steps: usize,
+}
+
+impl PendingBatch {
+    pub fn is_complete(&self) -> bool {
+        self.cursor >= self.operations.len()
+    }
 }
 
 impl From<BatchHeader> for block_header::BlockHeader {
@@ -67,9 +103,10 @@ impl From<BatchHeader> for block_header::BlockHeader {
                 .expect("Failed to convert pow nonce"),
             proto: 0,
             seed_nonce_hash: None,
+            // Block hash is computed over the unsigned header
             signature: ZERO_SIGNATURE
                 .try_into()
-                .expect("Failed to convert signature"), // TODO: sign with builtin key?
+                .expect("Failed to convert signature"),
             timestamp: ts2dt!(header.timestamp),
             validation_pass: 4,
         }
@@ -93,7 +130,7 @@ impl From<BatchHeader> for Header {
             proof_of_work_nonce: config.pow_nonce,
             proto: 0,
             seed_nonce_hash: None,
-            signature: None,
+            signature: header.signature,
             timestamp: ts2dt!(header.timestamp),
             validation_pass: 4,
         }
@@ -104,7 +141,7 @@ impl From<BatchReceipt> for Metadata {
     fn from(receipt: BatchReceipt) -> Self {
         let config = Config::default();
         Self {
-            baker: None, // TODO: + signature
+            baker: receipt.batcher,
             balance_updates: receipt.balance_updates,
             // derived
             protocol: receipt.protocol.to_owned(),
@@ -181,7 +218,7 @@ impl From<BatchReceipt> for FullHeader {
             proto: 0,
             protocol: receipt.protocol,
             seed_nonce_hash: None,
-            signature: None,
+            signature: receipt.header.signature,
             timestamp: ts2dt!(receipt.header.timestamp),
             validation_pass: 4,
         }
@@ -0,0 +1,182 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+use tezos_core::types::{
+    encoded::{Encoded, PublicKey},
+    mutez::Mutez,
+};
+use tezos_michelson::micheline::Micheline;
+use tezos_rpc::models::balance_update::{BalanceUpdate, Contract, Kind, Origin};
+
+use crate::{
+    config::{Config, TezosConfig},
+    context::{anti_spam::AntiSpamPolicy, sponsor::SponsorPolicy, TezosContext},
+    executor::{outbox::contains_ticket, reveal::public_key_hash},
+    internal_error, Result,
+};
+
+/// Genesis document compiled into the kernel, used unless another one is installed at `/genesis`
+pub const DEFAULT_GENESIS: &str = include_str!("../../genesis.json");
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GenesisAccount {
+    pub address: String,
+    pub balance: u64,
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GenesisContract {
+    pub address: String,
+    pub balance: u64,
+    /// Hex-encoded binary Micheline
+    pub code: String,
+    /// Hex-encoded binary Micheline
+    pub storage: String,
+}
+
+/// L1 contract that L2 contracts can call through the outbox
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GenesisL1Contract {
+    pub address: String,
+    /// Hex-encoded binary Micheline
+    pub parameter_type: String,
+}
+
+/// Account paying fees of the operations it signs off, within its policy
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GenesisSponsor {
+    pub address: String,
+    #[serde(default)]
+    pub policy: SponsorPolicy,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Genesis {
+    pub accounts: Vec<GenesisAccount>,
+    #[serde(default)]
+    pub contracts: Vec<GenesisContract>,
+    #[serde(default)]
+    pub batcher: Option<String>,
+    /// Public key authorized to sign kernel upgrades
+    #[serde(default)]
+    pub admin: Option<String>,
+    /// L1 contracts whose tickets can be deposited to the rollup
+    #[serde(default)]
+    pub bridges: Vec<String>,
+    #[serde(default)]
+    pub l1_contracts: Vec<GenesisL1Contract>,
+    /// System contract answering oracle requests
+    #[serde(default)]
+    pub oracle: Option<String>,
+    /// System contract registering scheduled calls
+    #[serde(default)]
+    pub scheduler: Option<String>,
+    #[serde(default)]
+    pub sponsors: Vec<GenesisSponsor>,
+    #[serde(default)]
+    pub anti_spam: Option<AntiSpamPolicy>,
+    #[serde(default)]
+    pub config: Option<TezosConfig>,
+}
+
+impl Genesis {
+    pub fn default() -> Self {
+        Self::from_json(DEFAULT_GENESIS).expect("Failed to parse built-in genesis")
+    }
+
+    pub fn from_json(value: &str) -> Result<Self> {
+        Ok(serde_json_wasm::from_str(value)?)
+    }
+
+    pub fn apply(self, context: &mut impl TezosContext) -> Result<Vec<BalanceUpdate>> {
+        let mut updates: Vec<BalanceUpdate> =
+            Vec::with_capacity(self.accounts.len() + self.contracts.len());
+
+        for account in self.accounts.into_iter() {
+            context.set_balance(&account.address, Mutez::try_from(account.balance)?)?;
+
+            if let Some(value) = account.public_key {
+                let public_key = PublicKey::try_from(value.as_str())?;
+                if public_key_hash(&public_key)?.value() != account.address {
+                    return Err(internal_error!(
+                        "Public key {} does not match {}",
+                        value,
+                        account.address
+                    ));
+                }
+                context.set_public_key(&account.address, public_key)?;
+            }
+
+            updates.push(migration_update(account.address, account.balance));
+        }
+
+        for contract in self.contracts.into_iter() {
+            let code = Micheline::from_bytes(hex::decode(&contract.code)?.as_slice())?;
+            let storage = Micheline::from_bytes(hex::decode(&contract.storage)?.as_slice())?;
+
+            context.set_balance(&contract.address, Mutez::try_from(contract.balance)?)?;
+            context.set_contract_code(&contract.address, code)?;
+            context.set_contract_storage(&contract.address, storage)?;
+
+            updates.push(migration_update(contract.address, contract.balance));
+        }
+
+        let batcher = self.batcher.unwrap_or(Config::default().batcher);
+        context.set_batcher(batcher.as_str().try_into()?)?;
+
+        if let Some(admin) = self.admin {
+            context.set_admin_key(PublicKey::try_from(admin.as_str())?)?;
+        }
+
+        for bridge in self.bridges.into_iter() {
+            context.set_bridge(bridge.as_str().try_into()?)?;
+        }
+
+        for contract in self.l1_contracts.into_iter() {
+            let parameter_type =
+                Micheline::from_bytes(hex::decode(&contract.parameter_type)?.as_slice())?;
+            if context.is_bridge(&contract.address)? || contains_ticket(&parameter_type) {
+                return Err(internal_error!(
+                    "L1 contract {} cannot accept tickets",
+                    contract.address
+                ));
+            }
+            context.set_l1_contract(&contract.address, parameter_type)?;
+        }
+
+        if let Some(oracle) = self.oracle {
+            context.set_oracle(oracle.as_str().try_into()?)?;
+        }
+
+        if let Some(scheduler) = self.scheduler {
+            context.set_scheduler(scheduler.as_str().try_into()?)?;
+        }
+
+        for sponsor in self.sponsors.into_iter() {
+            context.set_sponsor_policy(&sponsor.address, sponsor.policy)?;
+        }
+
+        if let Some(policy) = self.anti_spam {
+            context.set_anti_spam_policy(policy)?;
+        }
+
+        if let Some(config) = self.config {
+            context.set_config(config)?;
+        }
+
+        Ok(updates)
+    }
+}
+
+fn migration_update(contract: String, amount: u64) -> BalanceUpdate {
+    BalanceUpdate::Contract(Contract {
+        kind: Kind::Contract,
+        change: amount.to_string(),
+        contract,
+        origin: Some(Origin::Migration),
+    })
+}
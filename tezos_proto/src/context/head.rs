@@ -2,6 +2,8 @@
 //
 // SPDX-License-Identifier: MIT
 
+use std::collections::VecDeque;
+
 use serde::{Deserialize, Serialize};
 use tezos_core::types::encoded::{BlockHash, ChainId, Encoded, OperationHash};
 
@@ -56,3 +58,48 @@ impl std::fmt::Display for Head {
         ))
     }
 }
+
+/// Rolling window of the most recent batch hashes, used to validate operation branches
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiveBlocks(pub VecDeque<BlockHash>);
+
+impl LiveBlocks {
+    pub fn default() -> Self {
+        Self(VecDeque::new())
+    }
+
+    /// Appends the hash, dropping the oldest ones beyond capacity (the last hash is always kept)
+    pub fn push(&mut self, hash: BlockHash, capacity: usize) {
+        while self.0.len() >= capacity.max(1) {
+            self.0.pop_front();
+        }
+        self.0.push_back(hash);
+    }
+
+    pub fn contains(&self, hash: &BlockHash) -> bool {
+        self.0.contains(hash)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_live_blocks_capacity() {
+        let hash: BlockHash = ZERO_BLOCK_HASH.try_into().unwrap();
+        let other: BlockHash = "BKiHSFY5yPf2ne3BSAWXhFKVfA7GUk484ACE9Rk2PNhS9BEYg2w"
+            .try_into()
+            .unwrap();
+
+        let mut live_blocks = LiveBlocks::default();
+        live_blocks.push(hash.clone(), 2);
+        live_blocks.push(other.clone(), 2);
+        assert!(live_blocks.contains(&hash) && live_blocks.contains(&other));
+
+        // Zero capacity keeps the most recent hash only
+        live_blocks.push(hash.clone(), 0);
+        assert_eq!(1, live_blocks.0.len());
+        assert!(live_blocks.contains(&hash));
+    }
+}
@@ -2,60 +2,122 @@
 //
 // SPDX-License-Identifier: MIT
 
-use tezos_core::types::mutez::Mutez;
-use tezos_rpc::models::balance_update::{BalanceUpdate, Contract, Kind, Origin};
+use tezos_core::types::encoded::ImplicitAddress;
+use tezos_rpc::models::balance_update::BalanceUpdate;
 
 use crate::{
-    context::{head::Head, TezosContext},
+    config::Config,
+    context::{genesis::Genesis, head::Head, TezosContext},
     Result,
 };
 
-const SEED_ACCOUNTS: [&str; 8] = [
-    "tz1grSQDByRpnVs7sPtaprNZRp531ZKz6Jmm", // Pytezos built-in key
-    "tz1VSUr8wwNhLAzempoch5d6hLRiTh8Cjcjb", // Alice from Flextesa
-    "tz1TGu6TN5GSez2ndXXeDX6LgUDvLzPLqgYV", // Activator from Tezos sandbox
-    "tz1KqTpEZ7Yob7QbPE4Hy4Wo8fHG8LhKxZSx", // Bootstrap 1 from Tezos sandbox
-    "tz1gjaF81ZRRvdzjobyfVNsAeSC6PScjfQwN", // Bootstrap 2 from Tezos sandbox
-    "tz1faswCTDciRzE4oJ9jn2Vm2dvjeyA9fUzU", // Bootstrap 3 from Tezos sandbox
-    "tz1b7tUupMgCNw2cCLpKTkSD1NZzB5TkP2sv", // Bootstrap 4 from Tezos sandbox
-    "tz1ddb9NMYHZi5UzPdzTZMYQQZoMub195zgv", // Bootstrap 5 from Tezos sandbox
-];
-const SEED_BALANCE: u64 = 40_000_000_000_000u64;
-
-pub fn genesis_migration(context: &mut impl TezosContext) -> Result<Vec<BalanceUpdate>> {
-    let mut updates: Vec<BalanceUpdate> = Vec::with_capacity(SEED_ACCOUNTS.len());
-    let balance = Mutez::try_from(SEED_BALANCE).unwrap();
-
-    for account in SEED_ACCOUNTS.into_iter() {
-        context.set_balance(&account, balance)?;
-        updates.push(BalanceUpdate::Contract(Contract {
-            kind: Kind::Contract,
-            change: SEED_BALANCE.to_string(),
-            contract: account.to_string(),
-            origin: Some(Origin::Migration),
-        }));
-    }
+pub struct Migration<Context> {
+    pub version: u64,
+    pub name: &'static str,
+    pub apply: fn(&mut Context) -> Result<Vec<BalanceUpdate>>,
+}
 
-    context.commit()?;
-    Ok(updates)
+/// Ordered list of state migrations, every step is applied exactly once.
+/// New steps must be appended with an incremented version.
+pub fn registry<Context: TezosContext>() -> Vec<Migration<Context>> {
+    vec![
+        Migration {
+            version: 1,
+            name: "genesis",
+            apply: genesis_migration,
+        },
+        Migration {
+            version: 2,
+            name: "batcher",
+            apply: batcher_migration,
+        },
+    ]
 }
 
-pub fn run_migrations(
-    context: &mut impl TezosContext,
+pub fn genesis_migration<Context: TezosContext>(
+    context: &mut Context,
+) -> Result<Vec<BalanceUpdate>> {
+    let genesis = match context.get_genesis()? {
+        Some(genesis) => genesis,
+        None => Genesis::default(),
+    };
+    genesis.apply(context)
+}
+
+/// Rollups originated before fees were paid out have no batcher set
+pub fn batcher_migration<Context: TezosContext>(
+    context: &mut Context,
+) -> Result<Vec<BalanceUpdate>> {
+    if context.get_batcher()?.is_none() {
+        let batcher = ImplicitAddress::try_from(Config::default().batcher.as_str())?;
+        context.set_batcher(batcher)?;
+    }
+    Ok(vec![])
+}
+
+pub fn run_migrations<Context: TezosContext>(
+    context: &mut Context,
     head: &Head,
 ) -> Result<Option<Vec<BalanceUpdate>>> {
     context.check_no_pending_changes()?;
-    match head.level {
-        -1 => Ok(Some(genesis_migration(context)?)),
-        _ => Ok(None),
+
+    let current = match context.get_state_version()? {
+        Some(version) => version,
+        // State created before versioning was introduced already has genesis applied
+        None if head.level >= 0 => 1,
+        None => 0,
+    };
+
+    let mut updates: Option<Vec<BalanceUpdate>> = None;
+    for migration in registry::<Context>().into_iter() {
+        if migration.version <= current {
+            continue;
+        }
+
+        let mut step_updates = (migration.apply)(context)?;
+        context.set_state_version(migration.version)?;
+        context.commit()?;
+        context.log(format!(
+            "Migration #{} applied: {}",
+            migration.version, migration.name
+        ));
+
+        updates
+            .get_or_insert_with(Vec::new)
+            .append(&mut step_updates);
     }
+
+    Ok(updates)
 }
 
 #[cfg(test)]
 mod test {
+    use tezos_core::types::{encoded::Encoded, mutez::Mutez};
+
     use super::*;
     use crate::{context::TezosEphemeralContext, Result};
 
+    fn latest_version() -> u64 {
+        registry::<TezosEphemeralContext>()
+            .last()
+            .expect("At least one migration")
+            .version
+    }
+
+    /// Snapshot of a state produced by the kernel before migrations were versioned:
+    /// genesis applied, several batches produced, no batcher and no version stored
+    fn legacy_snapshot(context: &mut TezosEphemeralContext) -> Result<Head> {
+        context.set_balance(
+            "tz1grSQDByRpnVs7sPtaprNZRp531ZKz6Jmm",
+            Mutez::try_from(12345u64).unwrap(),
+        )?;
+        let mut head = Head::default();
+        head.level = 42;
+        context.set_head(head.clone())?;
+        context.commit()?;
+        Ok(head)
+    }
+
     #[test]
     fn test_seed_acconuts() -> Result<()> {
         let mut context = TezosEphemeralContext::default();
@@ -69,7 +131,85 @@ mod test {
         let balance = context
             .get_balance(&"tz1grSQDByRpnVs7sPtaprNZRp531ZKz6Jmm")?
             .expect("Seed balance");
-        assert_eq!(Mutez::try_from(SEED_BALANCE).unwrap(), balance);
+        assert_eq!(Mutez::try_from(40_000_000_000_000u64).unwrap(), balance);
+
+        let batcher = context.get_batcher()?.expect("Batcher address");
+        assert_eq!(Config::default().batcher, batcher.into_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_installed_genesis() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+
+        let genesis = Genesis::from_json(
+            r#"{
+                "accounts": [
+                    {
+                        "address": "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU",
+                        "balance": 1000,
+                        "public_key": "edpktipCJ3SkjvtdcrwELhvupnyYJSmqoXu3kdzK1vL6fT5cY8FTEa"
+                    }
+                ],
+                "contracts": [
+                    {
+                        "address": "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm",
+                        "balance": 0,
+                        "code": "02000000170500036c0501036c050202000000080317053d036d0342",
+                        "storage": "030b"
+                    }
+                ]
+            }"#,
+        )?;
+        context.set_genesis(genesis)?;
+        context.commit()?;
+
+        let head = context.get_head()?;
+        let updates = run_migrations(&mut context, &head)?.expect("Genesis balance updates");
+        assert_eq!(2, updates.len());
+
+        assert!(context.has_public_key("tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU")?);
+        assert!(context
+            .get_contract_code("KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm")?
+            .is_some());
+        assert!(context
+            .get_balance("tz1grSQDByRpnVs7sPtaprNZRp531ZKz6Jmm")?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrations_run_once() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+
+        let head = context.get_head()?;
+        assert!(run_migrations(&mut context, &head)?.is_some());
+        assert_eq!(Some(latest_version()), context.get_state_version()?);
+
+        let head = context.get_head()?;
+        assert!(run_migrations(&mut context, &head)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_snapshot_upgrade() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+        let head = legacy_snapshot(&mut context)?;
+
+        let updates = run_migrations(&mut context, &head)?.expect("Pending migrations");
+        assert!(updates.is_empty(), "Genesis must not be applied twice");
+        assert_eq!(Some(latest_version()), context.get_state_version()?);
+
+        let balance = context
+            .get_balance("tz1grSQDByRpnVs7sPtaprNZRp531ZKz6Jmm")?
+            .expect("Balance");
+        assert_eq!(Mutez::try_from(12345u64).unwrap(), balance);
+
+        let batcher = context.get_batcher()?.expect("Batcher address");
+        assert_eq!(Config::default().batcher, batcher.into_string());
 
         Ok(())
     }
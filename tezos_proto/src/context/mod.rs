@@ -2,11 +2,41 @@
 //
 // SPDX-License-Identifier: MIT
 
+pub mod anti_spam;
 pub mod batch;
+pub mod genesis;
 pub mod head;
 pub mod migrations;
+pub mod oracle;
+pub mod outbox;
+pub mod proof;
+pub mod scheduler;
+pub mod sponsor;
 pub mod store;
 pub mod tezos;
 
 pub use tezos::TezosContext;
 pub type TezosEphemeralContext = layered_store::EphemeralStore;
+
+/// State derived from block application or written after the block header is built:
+/// heads, receipts, live blocks, the pending batch and queued outbox messages
+pub const BOOKKEEPING_PREFIXES: &[&str] = &[
+    "/head",
+    "/blocks/",
+    "/batches/",
+    "/operations/",
+    "/scheduler/receipts/",
+    "/live_blocks",
+    "/batch_timestamp",
+    "/pending_batch",
+    "/pending_deposits",
+    "/outbox",
+];
+
+/// Merkle filter of the context (see `LayeredStore::set_merkle_filter`):
+/// every key except the bookkeeping ones is committed to the context hash
+pub fn is_merkle_key(key: &str) -> bool {
+    !BOOKKEEPING_PREFIXES
+        .iter()
+        .any(|prefix| key.starts_with(prefix))
+}
@@ -0,0 +1,57 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+
+/// Maximum length of an oracle feed key, e.g. `XTZ-USD`
+pub const ORACLE_KEY_MAX_LENGTH: usize = 32;
+
+/// Feed keys are part of the storage path: only ASCII letters, digits, `-` and `.` are allowed
+pub fn is_valid_oracle_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= ORACLE_KEY_MAX_LENGTH
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+/// Latest value of an oracle feed posted by the sequencer
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OracleValue {
+    /// Price scaled by the feed precision (up to the sequencer, e.g. 6 decimals)
+    pub value: u64,
+    /// Time of the observation, in seconds
+    pub timestamp: i64,
+    /// L2 level at which the value was received
+    pub level: i32,
+}
+
+impl OracleValue {
+    /// Seconds elapsed since the observation, zero if it is ahead of `now`
+    pub fn age(&self, now: i64) -> i64 {
+        (now - self.timestamp).max(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_oracle_value() {
+        assert!(is_valid_oracle_key("XTZ-USD"));
+        assert!(is_valid_oracle_key("btc.eur"));
+        assert!(!is_valid_oracle_key(""));
+        assert!(!is_valid_oracle_key("XTZ/USD"));
+        assert!(!is_valid_oracle_key(&"A".repeat(ORACLE_KEY_MAX_LENGTH + 1)));
+
+        let value = OracleValue {
+            value: 1_000_000,
+            timestamp: 100,
+            level: 1,
+        };
+        assert_eq!(20, value.age(120));
+        assert_eq!(0, value.age(90));
+    }
+}
@@ -0,0 +1,48 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+use tezos_core::types::encoded::ContractAddress;
+use tezos_michelson::micheline::Micheline;
+
+/// Outbox messages written by the kernel at the given inbox level
+pub fn outbox_path(level: i32) -> String {
+    format!("/outbox/{}", level)
+}
+
+/// L1 contract call queued by the executor until the kernel writes it to the outbox
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutboxCall {
+    pub destination: ContractAddress,
+    pub entrypoint: String,
+    pub parameters: Micheline,
+}
+
+/// L1 contract call written to the rollup outbox. It can be executed once the commitment
+/// of the outbox level is cemented, the execution proof is provided by the rollup node:
+/// `/global/block/cemented/helpers/proofs/outbox/{outbox_level}/messages?index={index}`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OutboxMessage {
+    pub outbox_level: i32,
+    /// Position of the message in the outbox level
+    pub index: u32,
+    pub destination: ContractAddress,
+    pub entrypoint: String,
+    pub parameters: Micheline,
+}
+
+impl OutboxMessage {
+    pub fn new(outbox_level: i32, index: u32, call: OutboxCall) -> Self {
+        Self {
+            outbox_level,
+            index,
+            destination: call.destination,
+            entrypoint: call.entrypoint,
+            parameters: call.parameters,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutboxMessages(pub Vec<OutboxMessage>);
@@ -0,0 +1,153 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use layered_store::merkle::{Hash, MerkleProof};
+use serde::{Deserialize, Serialize};
+use tezos_core::types::encoded::{ContextHash, Encoded};
+
+use crate::{Error, Result};
+
+/// Value of a context key (e.g. `/context/contracts/{address}/balance`) along with
+/// the Merkle proof of its inclusion or non-inclusion against the block context hash
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ContextProof {
+    pub context_hash: ContextHash,
+    pub key: String,
+    /// Raw value in the storage encoding, `None` if the key is not set
+    pub value: Option<String>,
+    /// Sibling hashes from the root down to the key position
+    pub siblings: Vec<String>,
+    /// Key and value hashes of another leaf occupying the key position
+    pub leaf: Option<(String, String)>,
+}
+
+fn decode_hash(value: &str) -> Result<Hash> {
+    hex::decode(value)?
+        .try_into()
+        .map_err(|_| Error::InvalidContextProof)
+}
+
+impl ContextProof {
+    pub fn new(
+        context_hash: ContextHash,
+        key: String,
+        value: Option<&[u8]>,
+        proof: &MerkleProof,
+    ) -> Self {
+        Self {
+            context_hash,
+            key,
+            value: value.map(hex::encode),
+            siblings: proof.siblings.iter().map(hex::encode).collect(),
+            leaf: proof
+                .leaf
+                .as_ref()
+                .map(|(key_hash, value_hash)| (hex::encode(key_hash), hex::encode(value_hash))),
+        }
+    }
+
+    pub fn merkle_proof(&self) -> Result<MerkleProof> {
+        let siblings = self
+            .siblings
+            .iter()
+            .map(|hash| decode_hash(hash))
+            .collect::<Result<Vec<Hash>>>()?;
+        let leaf = match &self.leaf {
+            Some((key_hash, value_hash)) => {
+                Some((decode_hash(key_hash)?, decode_hash(value_hash)?))
+            }
+            None => None,
+        };
+        Ok(MerkleProof { siblings, leaf })
+    }
+
+    pub fn verify(&self) -> Result<()> {
+        let value = match &self.value {
+            Some(value) => Some(hex::decode(value)?),
+            None => None,
+        };
+        verify_context_proof(
+            &self.context_hash,
+            &self.key,
+            value.as_deref(),
+            &self.merkle_proof()?,
+        )
+    }
+}
+
+/// Checks that the key has the given value (`None` for absent keys) in the context
+/// committed to by the block header. Depends on hashing only, so it can run in wasm.
+pub fn verify_context_proof(
+    context_hash: &ContextHash,
+    key: &str,
+    value: Option<&[u8]>,
+    proof: &MerkleProof,
+) -> Result<()> {
+    let root: Hash = context_hash
+        .to_bytes()?
+        .try_into()
+        .map_err(|_| Error::InvalidContextProof)?;
+    match proof.verify(&root, key, value) {
+        true => Ok(()),
+        false => Err(Error::InvalidContextProof),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use layered_store::StoreType;
+    use tezos_core::types::mutez::Mutez;
+
+    use super::*;
+    use crate::context::{head::Head, is_merkle_key, TezosContext, TezosEphemeralContext};
+
+    #[test]
+    fn test_balance_proof() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+        context.set_balance("tz1Ne4yzDRQPd5HFz6sTaCYCNHwFubT2MWsB", 1000u32.into())?;
+        context.set_balance("tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU", 2000u32.into())?;
+        context.commit()?;
+        let context_hash = context.get_context_hash()?;
+
+        let key = "/context/contracts/tz1Ne4yzDRQPd5HFz6sTaCYCNHwFubT2MWsB/balance";
+        let balance: Mutez = 1000u32.into();
+        let value = StoreType::to_bytes(&balance)?;
+        let proof = ContextProof::new(
+            context_hash.clone(),
+            key.into(),
+            Some(value.as_slice()),
+            &context.merkle_proof(key)?,
+        );
+        proof.verify()?;
+
+        let mut forged = proof.clone();
+        forged.value = Some(hex::encode(StoreType::to_bytes(&Mutez::from(2000u32))?));
+        assert!(matches!(forged.verify(), Err(Error::InvalidContextProof)));
+
+        let key = "/context/contracts/tz1RjtZUVeLhADFHDL8UwDZA6vjWWhojpu5w/balance";
+        let proof = ContextProof::new(context_hash, key.into(), None, &context.merkle_proof(key)?);
+        proof.verify()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_bookkeeping_not_committed() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+        context.set_merkle_filter(is_merkle_key);
+        context.set_balance("tz1Ne4yzDRQPd5HFz6sTaCYCNHwFubT2MWsB", 1000u32.into())?;
+        context.commit()?;
+        let context_hash = context.get_context_hash()?;
+
+        // Heads are written after the context hash is put into the header
+        context.set_head(Head::default())?;
+        context.commit()?;
+        assert_eq!(context_hash, context.get_context_hash()?);
+
+        // Consensus state outside of /context is committed as well
+        context.set_batcher("tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU".try_into().unwrap())?;
+        context.commit()?;
+        assert_ne!(context_hash, context.get_context_hash()?);
+        Ok(())
+    }
+}
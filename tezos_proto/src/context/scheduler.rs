@@ -0,0 +1,81 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+use tezos_core::types::{
+    encoded::{Address, ContractAddress, ImplicitAddress},
+    mutez::Mutez,
+};
+use tezos_michelson::micheline::Micheline;
+use tezos_operation::operations::{Entrypoint, Parameters, Transaction};
+
+use crate::Result;
+
+/// Contract call registered via the scheduler, executed by the kernel at the beginning of batches
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledCall {
+    /// Registrant of the call: `SENDER` of the executed transactions, refunded once the call is over
+    pub owner: Address,
+    /// Account that signed the registration, `SOURCE` of the executed transactions
+    pub source: ImplicitAddress,
+    pub destination: ContractAddress,
+    pub entrypoint: String,
+    pub parameters: Micheline,
+    /// Level of the next execution
+    pub level: i32,
+    /// Number of levels between executions, the call is executed once if zero
+    pub period: i32,
+    /// Paid to the sequencer for every execution, in mutez
+    pub fee: u64,
+    /// Remaining prepaid budget, in mutez
+    pub budget: u64,
+    /// Number of times a failed execution is retried (at the next level) before cancellation
+    pub retries: u32,
+    /// Failed executions in a row
+    #[serde(default)]
+    pub failures: u32,
+}
+
+impl ScheduledCall {
+    /// Transaction executed on behalf of the owner, no tez are sent along
+    pub fn transaction(&self) -> Result<Transaction> {
+        Ok(Transaction {
+            source: self.source.clone(),
+            counter: 0u32.into(),
+            fee: Mutez::try_from(self.fee)?,
+            gas_limit: 0u32.into(),
+            storage_limit: 0u32.into(),
+            amount: 0u32.into(),
+            destination: self.destination.clone().into(),
+            parameters: Some(Parameters {
+                entrypoint: Entrypoint::from_str(&self.entrypoint),
+                value: self.parameters.clone(),
+            }),
+        })
+    }
+}
+
+/// Identifiers of the calls due at a level, in order of execution
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledQueue(pub Vec<u64>);
+
+/// Outcome of a scheduled call execution
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledCallReceipt {
+    pub id: u64,
+    pub destination: ContractAddress,
+    pub entrypoint: String,
+    pub applied: bool,
+    #[serde(default)]
+    pub errors: Vec<String>,
+    /// Paid from the prepaid budget, in mutez
+    pub fee: u64,
+    /// Level of the next execution, `None` if the call is completed or cancelled
+    #[serde(default)]
+    pub next_level: Option<i32>,
+}
+
+/// Scheduled calls executed at the given level
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledCallReceipts(pub Vec<ScheduledCallReceipt>);
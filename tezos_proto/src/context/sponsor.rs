@@ -0,0 +1,87 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+use tezos_core::types::{encoded::Encoded, mutez::Mutez};
+use tezos_operation::operations::OperationContent;
+
+use crate::Result;
+
+/// Length of the sponsor budget period, in seconds
+pub const SPONSOR_BUDGET_PERIOD: i64 = 86400;
+
+/// Conditions under which an account pays fees for the operations it signed off
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SponsorPolicy {
+    /// Transaction destinations that can be called, any destination if empty
+    #[serde(default)]
+    pub destinations: Vec<String>,
+    /// Maximum amount of fees paid per day (in mutez), unlimited if not set
+    #[serde(default)]
+    pub daily_budget: Option<u64>,
+}
+
+impl SponsorPolicy {
+    /// Returns the destination that cannot be sponsored, if any.
+    /// Reveals are always allowed, originations only if any destination is.
+    pub fn refused_destination(&self, content: &OperationContent) -> Option<String> {
+        if self.destinations.is_empty() {
+            return None;
+        }
+        match content {
+            OperationContent::Reveal(_) => None,
+            OperationContent::Transaction(transaction) => {
+                let destination = transaction.destination.value();
+                match self.destinations.iter().any(|d| d == destination) {
+                    true => None,
+                    false => Some(destination.into()),
+                }
+            }
+            _ => Some("origination".into()),
+        }
+    }
+
+    /// Fees that can still be paid during the period the timestamp belongs to
+    pub fn remaining_budget(
+        &self,
+        spending: &SponsorSpending,
+        timestamp: i64,
+    ) -> Result<Option<Mutez>> {
+        let budget = match self.daily_budget {
+            Some(budget) => Mutez::try_from(budget)?,
+            None => return Ok(None),
+        };
+        let spent = spending.spent_at(timestamp)?;
+        if spent >= budget {
+            return Ok(Some(0u32.into()));
+        }
+        Ok(Some(budget - spent))
+    }
+}
+
+/// Fees paid by a sponsor during the current period
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SponsorSpending {
+    /// Index of the period (timestamp divided by `SPONSOR_BUDGET_PERIOD`)
+    pub period: i64,
+    /// Paid fees, in mutez
+    pub spent: u64,
+}
+
+impl SponsorSpending {
+    pub fn spent_at(&self, timestamp: i64) -> Result<Mutez> {
+        match self.period == timestamp / SPONSOR_BUDGET_PERIOD {
+            true => Ok(Mutez::try_from(self.spent)?),
+            false => Ok(0u32.into()),
+        }
+    }
+
+    pub fn add(self, fees: &Mutez, timestamp: i64) -> Result<Self> {
+        let spent: i64 = (self.spent_at(timestamp)? + *fees).try_into()?;
+        Ok(Self {
+            period: timestamp / SPONSOR_BUDGET_PERIOD,
+            spent: spent as u64,
+        })
+    }
+}
@@ -2,10 +2,32 @@
 //
 // SPDX-License-Identifier: MIT
 
-use layered_store::{error::err_into, Result, StoreType};
-use tezos_rpc::models::operation::Operation;
+use layered_store::{error::err_into, internal_error, Result, StoreType};
+use tezos_core::types::{
+    encoded::{Address, ContractAddress, Encoded, ImplicitAddress, OperationHash, Signature},
+    mutez::Mutez,
+    number::Nat,
+};
+use tezos_michelson::micheline::Micheline;
+use tezos_operation::operations::{SignedOperation, UnsignedOperation};
+use tezos_rpc::models::{
+    balance_update::{BalanceUpdate, Contract, Kind, Origin},
+    operation::Operation,
+};
 
-use crate::{context::batch::BatchReceipt, context::head::Head};
+use crate::{
+    config::TezosConfig,
+    context::anti_spam::{AntiSpamPolicy, OperationQuota},
+    context::batch::{BatchReceipt, PendingBatch},
+    context::genesis::Genesis,
+    context::head::{Head, LiveBlocks},
+    context::oracle::OracleValue,
+    context::outbox::{OutboxCall, OutboxMessages},
+    context::scheduler::{ScheduledCall, ScheduledCallReceipts, ScheduledQueue},
+    context::sponsor::{SponsorPolicy, SponsorSpending},
+    executor::deposit::Deposit,
+    validator::operation::ValidOperation,
+};
 
 impl StoreType for Head {
     fn from_bytes(bytes: &[u8]) -> Result<Self> {
@@ -17,6 +39,116 @@ impl StoreType for Head {
     }
 }
 
+impl StoreType for LiveBlocks {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json_wasm::de::from_slice(bytes).map_err(err_into)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json_wasm::ser::to_vec(self).map_err(err_into)
+    }
+}
+
+impl StoreType for Genesis {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json_wasm::de::from_slice(bytes).map_err(err_into)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json_wasm::ser::to_vec(self).map_err(err_into)
+    }
+}
+
+impl StoreType for AntiSpamPolicy {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json_wasm::de::from_slice(bytes).map_err(err_into)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json_wasm::ser::to_vec(self).map_err(err_into)
+    }
+}
+
+impl StoreType for OperationQuota {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json_wasm::de::from_slice(bytes).map_err(err_into)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json_wasm::ser::to_vec(self).map_err(err_into)
+    }
+}
+
+impl StoreType for SponsorPolicy {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json_wasm::de::from_slice(bytes).map_err(err_into)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json_wasm::ser::to_vec(self).map_err(err_into)
+    }
+}
+
+impl StoreType for SponsorSpending {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json_wasm::de::from_slice(bytes).map_err(err_into)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json_wasm::ser::to_vec(self).map_err(err_into)
+    }
+}
+
+impl StoreType for OracleValue {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json_wasm::de::from_slice(bytes).map_err(err_into)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json_wasm::ser::to_vec(self).map_err(err_into)
+    }
+}
+
+impl StoreType for ScheduledCall {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json_wasm::de::from_slice(bytes).map_err(err_into)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json_wasm::ser::to_vec(self).map_err(err_into)
+    }
+}
+
+impl StoreType for ScheduledQueue {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json_wasm::de::from_slice(bytes).map_err(err_into)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json_wasm::ser::to_vec(self).map_err(err_into)
+    }
+}
+
+impl StoreType for ScheduledCallReceipts {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json_wasm::de::from_slice(bytes).map_err(err_into)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json_wasm::ser::to_vec(self).map_err(err_into)
+    }
+}
+
+impl StoreType for TezosConfig {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json_wasm::de::from_slice(bytes).map_err(err_into)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json_wasm::ser::to_vec(self).map_err(err_into)
+    }
+}
+
 impl StoreType for BatchReceipt {
     fn from_bytes(_bytes: &[u8]) -> Result<Self> {
         #[cfg(not(target_arch = "wasm32"))]
@@ -35,6 +167,16 @@ impl StoreType for BatchReceipt {
     }
 }
 
+impl StoreType for OutboxMessages {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json_wasm::de::from_slice(bytes).map_err(err_into)
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json_wasm::ser::to_vec(self).map_err(err_into)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct OperationReceipt(pub Operation);
 
@@ -53,3 +195,273 @@ impl StoreType for OperationReceipt {
         serde_json_wasm::ser::to_vec(&self.0).map_err(err_into)
     }
 }
+
+fn write_field(bytes: &mut Vec<u8>, field: &[u8]) {
+    bytes.extend_from_slice(&(field.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(field);
+}
+
+fn read_field<'a>(bytes: &mut &'a [u8]) -> Result<&'a [u8]> {
+    if bytes.len() < 4 {
+        return Err(internal_error!("Unexpected end of input"));
+    }
+    let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    match bytes[4..].get(..length) {
+        Some(field) => {
+            *bytes = &bytes[4 + length..];
+            Ok(field)
+        }
+        None => Err(internal_error!("Unexpected end of input")),
+    }
+}
+
+fn write_operation(bytes: &mut Vec<u8>, opg: &ValidOperation) -> Result<()> {
+    write_field(bytes, StoreType::to_bytes(&opg.hash)?.as_slice());
+    write_field(
+        bytes,
+        opg.origin.to_forged_bytes().map_err(err_into)?.as_slice(),
+    );
+    write_field(
+        bytes,
+        Encoded::to_bytes(&opg.origin.signature)
+            .map_err(err_into)?
+            .as_slice(),
+    );
+    write_field(bytes, StoreType::to_bytes(&opg.source)?.as_slice());
+    match &opg.wallet {
+        Some(wallet) => write_field(bytes, StoreType::to_bytes(wallet)?.as_slice()),
+        None => write_field(bytes, &[]),
+    }
+    match &opg.sponsor {
+        Some(sponsor) => write_field(bytes, StoreType::to_bytes(sponsor)?.as_slice()),
+        None => write_field(bytes, &[]),
+    }
+    write_field(bytes, StoreType::to_bytes(&opg.total_fees)?.as_slice());
+    write_field(bytes, StoreType::to_bytes(&opg.total_spent)?.as_slice());
+    write_field(bytes, StoreType::to_bytes(&opg.last_counter)?.as_slice());
+    Ok(())
+}
+
+fn read_operation(bytes: &mut &[u8]) -> Result<ValidOperation> {
+    let hash = <OperationHash as StoreType>::from_bytes(read_field(bytes)?)?;
+    let unsigned_op = UnsignedOperation::from_forged_bytes(read_field(bytes)?).map_err(err_into)?;
+    let signature = Signature::from_bytes(read_field(bytes)?).map_err(err_into)?;
+    let source = <ImplicitAddress as StoreType>::from_bytes(read_field(bytes)?)?;
+    let wallet = match read_field(bytes)? {
+        [] => None,
+        wallet => Some(<ContractAddress as StoreType>::from_bytes(wallet)?),
+    };
+    let sponsor = match read_field(bytes)? {
+        [] => None,
+        sponsor => Some(<ImplicitAddress as StoreType>::from_bytes(sponsor)?),
+    };
+    Ok(ValidOperation {
+        hash,
+        origin: SignedOperation::from(unsigned_op, signature),
+        source,
+        wallet,
+        sponsor,
+        total_fees: <Mutez as StoreType>::from_bytes(read_field(bytes)?)?,
+        total_spent: <Mutez as StoreType>::from_bytes(read_field(bytes)?)?,
+        last_counter: <Nat as StoreType>::from_bytes(read_field(bytes)?)?,
+    })
+}
+
+fn write_deposit(bytes: &mut Vec<u8>, deposit: &Deposit) -> Result<()> {
+    write_field(bytes, StoreType::to_bytes(&deposit.ticketer)?.as_slice());
+    write_field(bytes, deposit.receiver.value().as_bytes());
+    write_field(bytes, StoreType::to_bytes(&deposit.amount)?.as_slice());
+    write_field(bytes, StoreType::to_bytes(&deposit.sender)?.as_slice());
+    write_field(bytes, StoreType::to_bytes(&deposit.source)?.as_slice());
+    Ok(())
+}
+
+fn read_deposit(bytes: &mut &[u8]) -> Result<Deposit> {
+    let ticketer = <ContractAddress as StoreType>::from_bytes(read_field(bytes)?)?;
+    let receiver = std::str::from_utf8(read_field(bytes)?).map_err(err_into)?;
+    Ok(Deposit {
+        ticketer,
+        receiver: Address::try_from(receiver).map_err(err_into)?,
+        amount: <Mutez as StoreType>::from_bytes(read_field(bytes)?)?,
+        sender: <ContractAddress as StoreType>::from_bytes(read_field(bytes)?)?,
+        source: <ImplicitAddress as StoreType>::from_bytes(read_field(bytes)?)?,
+    })
+}
+
+/// L1 deposits read from the inbox but not yet credited
+#[derive(Clone, Debug, Default)]
+pub struct PendingDeposits(pub Vec<Deposit>);
+
+impl StoreType for PendingDeposits {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut bytes = bytes;
+        let mut deposits: Vec<Deposit> = Vec::new();
+        while !bytes.is_empty() {
+            deposits.push(read_deposit(&mut bytes)?);
+        }
+        Ok(Self(deposits))
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes: Vec<u8> = Vec::new();
+        for deposit in self.0.iter() {
+            write_deposit(&mut bytes, deposit)?;
+        }
+        Ok(bytes)
+    }
+}
+
+// Balance updates cannot be deserialized from JSON inside the kernel (see `BatchReceipt`),
+// so the contract credits produced by migrations are stored field by field
+fn write_balance_update(bytes: &mut Vec<u8>, update: &BalanceUpdate) -> Result<()> {
+    match update {
+        BalanceUpdate::Contract(Contract {
+            change,
+            contract,
+            origin: Some(Origin::Migration),
+            ..
+        }) => {
+            write_field(bytes, contract.as_bytes());
+            write_field(bytes, change.as_bytes());
+            Ok(())
+        }
+        _ => Err(internal_error!("Unexpected migration balance update")),
+    }
+}
+
+fn read_balance_update(bytes: &mut &[u8]) -> Result<BalanceUpdate> {
+    let contract = std::str::from_utf8(read_field(bytes)?).map_err(err_into)?;
+    let change = std::str::from_utf8(read_field(bytes)?).map_err(err_into)?;
+    Ok(BalanceUpdate::Contract(Contract {
+        kind: Kind::Contract,
+        change: change.into(),
+        contract: contract.into(),
+        origin: Some(Origin::Migration),
+    }))
+}
+
+/// Leading byte of the pending batch encoding, must be bumped whenever the layout changes
+/// so that a batch suspended by a previous kernel is not misread after an upgrade
+pub const PENDING_BATCH_VERSION: u8 = 1;
+
+impl StoreType for PendingBatch {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut bytes = match bytes.split_first() {
+            Some((&PENDING_BATCH_VERSION, rest)) => rest,
+            Some((version, _)) => {
+                return Err(internal_error!(
+                    "Unsupported pending batch version {}",
+                    version
+                ))
+            }
+            None => return Err(internal_error!("Unexpected end of input")),
+        };
+        let prev_head = Head::from_bytes(read_field(&mut bytes)?)?;
+        let timestamp = i64::from_bytes(read_field(&mut bytes)?)?;
+        let cursor = u64::from_bytes(read_field(&mut bytes)?)? as usize;
+        let block_fees = <Mutez as StoreType>::from_bytes(read_field(&mut bytes)?)?;
+        let batcher = match read_field(&mut bytes)? {
+            [] => None,
+            field => Some(<ImplicitAddress as StoreType>::from_bytes(field)?),
+        };
+        let mut deposits: Vec<Deposit> = Vec::new();
+        for _ in 0..u64::from_bytes(read_field(&mut bytes)?)? {
+            deposits.push(read_deposit(&mut bytes)?);
+        }
+        let mut balance_updates: Vec<BalanceUpdate> = Vec::new();
+        for _ in 0..u64::from_bytes(read_field(&mut bytes)?)? {
+            balance_updates.push(read_balance_update(&mut bytes)?);
+        }
+
+        let mut operations: Vec<ValidOperation> = Vec::new();
+        while !bytes.is_empty() {
+            operations.push(read_operation(&mut bytes)?);
+        }
+
+        Ok(Self {
+            prev_head,
+            timestamp,
+            operations,
+            cursor,
+            block_fees,
+            batcher,
+            deposits,
+            balance_updates,
+            // Validation is counted by the run that began the batch
+            steps: 0,
+        })
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes: Vec<u8> = vec![PENDING_BATCH_VERSION];
+        write_field(&mut bytes, self.prev_head.to_bytes()?.as_slice());
+        write_field(&mut bytes, self.timestamp.to_bytes()?.as_slice());
+        write_field(&mut bytes, (self.cursor as u64).to_bytes()?.as_slice());
+        write_field(
+            &mut bytes,
+            StoreType::to_bytes(&self.block_fees)?.as_slice(),
+        );
+        match &self.batcher {
+            Some(batcher) => write_field(&mut bytes, StoreType::to_bytes(batcher)?.as_slice()),
+            None => write_field(&mut bytes, &[]),
+        }
+        write_field(
+            &mut bytes,
+            (self.deposits.len() as u64).to_bytes()?.as_slice(),
+        );
+        for deposit in self.deposits.iter() {
+            write_deposit(&mut bytes, deposit)?;
+        }
+        write_field(
+            &mut bytes,
+            (self.balance_updates.len() as u64).to_bytes()?.as_slice(),
+        );
+        for update in self.balance_updates.iter() {
+            write_balance_update(&mut bytes, update)?;
+        }
+        for opg in self.operations.iter() {
+            write_operation(&mut bytes, opg)?;
+        }
+        Ok(bytes)
+    }
+}
+
+fn write_outbox_call(bytes: &mut Vec<u8>, call: &OutboxCall) -> Result<()> {
+    write_field(bytes, StoreType::to_bytes(&call.destination)?.as_slice());
+    write_field(bytes, call.entrypoint.as_bytes());
+    write_field(bytes, StoreType::to_bytes(&call.parameters)?.as_slice());
+    Ok(())
+}
+
+fn read_outbox_call(bytes: &mut &[u8]) -> Result<OutboxCall> {
+    let destination = <ContractAddress as StoreType>::from_bytes(read_field(bytes)?)?;
+    let entrypoint = std::str::from_utf8(read_field(bytes)?).map_err(err_into)?;
+    Ok(OutboxCall {
+        destination,
+        entrypoint: entrypoint.into(),
+        parameters: <Micheline as StoreType>::from_bytes(read_field(bytes)?)?,
+    })
+}
+
+/// L1 contract calls waiting to be written to the outbox
+#[derive(Clone, Debug, Default)]
+pub struct OutboxQueue(pub Vec<OutboxCall>);
+
+impl StoreType for OutboxQueue {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut bytes = bytes;
+        let mut calls: Vec<OutboxCall> = Vec::new();
+        while !bytes.is_empty() {
+            calls.push(read_outbox_call(&mut bytes)?);
+        }
+        Ok(Self(calls))
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes: Vec<u8> = Vec::new();
+        for call in self.0.iter() {
+            write_outbox_call(&mut bytes, call)?;
+        }
+        Ok(bytes)
+    }
+}
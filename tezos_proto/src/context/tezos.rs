@@ -4,7 +4,7 @@
 
 use layered_store::{LayeredStore, StoreBackend};
 use tezos_core::types::{
-    encoded::{Encoded, PublicKey},
+    encoded::{ContextHash, ContractAddress, Encoded, ImplicitAddress, PublicKey},
     mutez::Mutez,
     number::Nat,
 };
@@ -12,14 +12,38 @@ use tezos_michelson::micheline::Micheline;
 use tezos_rpc::models::operation::Operation;
 
 use crate::{
-    context::{batch::BatchReceipt, head::Head, store::OperationReceipt},
+    config::TezosConfig,
+    context::{
+        anti_spam::{AntiSpamPolicy, OperationQuota},
+        batch::{BatchReceipt, PendingBatch},
+        genesis::Genesis,
+        head::{Head, LiveBlocks},
+        oracle::OracleValue,
+        outbox::OutboxCall,
+        scheduler::{ScheduledCall, ScheduledCallReceipt, ScheduledCallReceipts, ScheduledQueue},
+        sponsor::{SponsorPolicy, SponsorSpending},
+        store::{OperationReceipt, OutboxQueue, PendingDeposits},
+    },
     error::err_into,
+    executor::{
+        deposit::Deposit, oracle::oracle_parameter_type, scheduler::scheduler_parameter_type,
+    },
     Error, Result,
 };
 
 pub trait TezosContext {
     fn get_head(&mut self) -> Result<Head>;
     fn set_head(&mut self, head: Head) -> Result<()>;
+    fn get_block_head(&mut self, level: i32) -> Result<Option<Head>>;
+    fn set_block_head(&mut self, head: Head) -> Result<()>;
+    fn get_state_version(&mut self) -> Result<Option<u64>>;
+    fn set_state_version(&mut self, version: u64) -> Result<()>;
+    fn get_genesis(&mut self) -> Result<Option<Genesis>>;
+    fn set_genesis(&mut self, genesis: Genesis) -> Result<()>;
+    fn get_config(&mut self) -> Result<TezosConfig>;
+    fn set_config(&mut self, config: TezosConfig) -> Result<()>;
+    fn get_live_blocks(&mut self) -> Result<LiveBlocks>;
+    fn set_live_blocks(&mut self, live_blocks: LiveBlocks) -> Result<()>;
     fn get_balance(&mut self, address: &str) -> Result<Option<Mutez>>;
     fn set_balance(&mut self, address: &str, balance: Mutez) -> Result<()>;
     fn get_counter(&mut self, address: &str) -> Result<Nat>;
@@ -31,10 +55,60 @@ pub trait TezosContext {
     fn get_contract_code(&mut self, address: &str) -> Result<Option<Micheline>>;
     fn get_contract_storage(&mut self, address: &str) -> Result<Option<Micheline>>;
     fn set_contract_storage(&mut self, address: &str, storage: Micheline) -> Result<()>;
+    fn get_batcher(&mut self) -> Result<Option<ImplicitAddress>>;
+    fn get_admin_key(&mut self) -> Result<Option<PublicKey>>;
+    fn set_admin_key(&mut self, public_key: PublicKey) -> Result<()>;
+    fn set_batcher(&mut self, address: ImplicitAddress) -> Result<()>;
+    fn is_bridge(&self, ticketer: &str) -> Result<bool>;
+    fn set_bridge(&mut self, ticketer: ContractAddress) -> Result<()>;
+    fn get_l1_contract(&mut self, address: &str) -> Result<Option<Micheline>>;
+    fn set_l1_contract(&mut self, address: &str, parameter_type: Micheline) -> Result<()>;
+    fn get_outbox_queue(&mut self) -> Result<Vec<OutboxCall>>;
+    fn set_outbox_queue(&mut self, queue: Vec<OutboxCall>) -> Result<()>;
+    fn get_refund_queue(&mut self) -> Result<Vec<OutboxCall>>;
+    fn set_refund_queue(&mut self, queue: Vec<OutboxCall>) -> Result<()>;
+    fn get_outbox_count(&mut self) -> Result<u64>;
+    fn set_outbox_count(&mut self, count: u64) -> Result<()>;
+    fn get_oracle(&mut self) -> Result<Option<ContractAddress>>;
+    fn set_oracle(&mut self, address: ContractAddress) -> Result<()>;
+    fn get_oracle_value(&mut self, key: &str) -> Result<Option<OracleValue>>;
+    fn set_oracle_value(&mut self, key: &str, value: OracleValue) -> Result<()>;
+    fn get_scheduler(&mut self) -> Result<Option<ContractAddress>>;
+    fn set_scheduler(&mut self, address: ContractAddress) -> Result<()>;
+    fn get_scheduled_call(&mut self, id: u64) -> Result<Option<ScheduledCall>>;
+    fn set_scheduled_call(&mut self, id: u64, call: Option<ScheduledCall>) -> Result<()>;
+    fn get_scheduled_queue(&mut self, level: i32) -> Result<Vec<u64>>;
+    fn set_scheduled_queue(&mut self, level: i32, queue: Vec<u64>) -> Result<()>;
+    fn get_scheduled_count(&mut self) -> Result<u64>;
+    fn set_scheduled_count(&mut self, count: u64) -> Result<()>;
+    fn get_scheduled_receipts(&mut self, level: i32) -> Result<Vec<ScheduledCallReceipt>>;
+    fn set_scheduled_receipts(
+        &mut self,
+        level: i32,
+        receipts: Vec<ScheduledCallReceipt>,
+    ) -> Result<()>;
+    fn get_sponsorship(&mut self, hash: &str) -> Result<Option<ImplicitAddress>>;
+    fn set_sponsorship(&mut self, hash: &str, sponsor: Option<ImplicitAddress>) -> Result<()>;
+    fn get_sponsor_policy(&mut self, address: &str) -> Result<Option<SponsorPolicy>>;
+    fn set_sponsor_policy(&mut self, address: &str, policy: SponsorPolicy) -> Result<()>;
+    fn get_sponsor_spending(&mut self, address: &str) -> Result<SponsorSpending>;
+    fn set_sponsor_spending(&mut self, address: &str, spending: SponsorSpending) -> Result<()>;
+    fn get_anti_spam_policy(&mut self) -> Result<Option<AntiSpamPolicy>>;
+    fn set_anti_spam_policy(&mut self, policy: AntiSpamPolicy) -> Result<()>;
+    fn get_operation_quota(&mut self, address: &str) -> Result<OperationQuota>;
+    fn set_operation_quota(&mut self, address: &str, quota: OperationQuota) -> Result<()>;
     fn set_batch_receipt(&mut self, receipt: BatchReceipt) -> Result<()>;
     fn get_batch_receipt(&mut self, hash: &str) -> Result<BatchReceipt>;
+    fn has_batch_receipt(&self, hash: &str) -> Result<bool>;
+    fn get_pending_batch(&mut self) -> Result<Option<PendingBatch>>;
+    fn set_pending_batch(&mut self, batch: Option<PendingBatch>) -> Result<()>;
+    fn get_pending_deposits(&mut self) -> Result<Vec<Deposit>>;
+    fn set_pending_deposits(&mut self, deposits: Vec<Deposit>) -> Result<()>;
+    fn get_batch_timestamp(&mut self) -> Result<Option<i64>>;
+    fn set_batch_timestamp(&mut self, timestamp: Option<i64>) -> Result<()>;
     fn set_operation_receipt(&mut self, receipt: Operation) -> Result<()>;
     fn get_operation_receipt(&mut self, hash: &str) -> Result<Operation>;
+    fn get_context_hash(&self) -> Result<ContextHash>;
     fn check_no_pending_changes(&self) -> Result<()>;
     fn commit(&mut self) -> Result<()>;
     fn rollback(&mut self);
@@ -54,6 +128,56 @@ impl<Backend: StoreBackend> TezosContext for LayeredStore<Backend> {
         self.set("/head".into(), Some(head)).map_err(err_into)
     }
 
+    fn get_block_head(&mut self, level: i32) -> Result<Option<Head>> {
+        self.get(format!("/blocks/{}", level)).map_err(err_into)
+    }
+
+    fn set_block_head(&mut self, head: Head) -> Result<()> {
+        self.set(format!("/blocks/{}", head.level), Some(head))
+            .map_err(err_into)
+    }
+
+    fn get_state_version(&mut self) -> Result<Option<u64>> {
+        self.get("/version".into()).map_err(err_into)
+    }
+
+    fn set_state_version(&mut self, version: u64) -> Result<()> {
+        self.set("/version".into(), Some(version)).map_err(err_into)
+    }
+
+    fn get_genesis(&mut self) -> Result<Option<Genesis>> {
+        self.get("/genesis".into()).map_err(err_into)
+    }
+
+    fn set_genesis(&mut self, genesis: Genesis) -> Result<()> {
+        self.set("/genesis".into(), Some(genesis)).map_err(err_into)
+    }
+
+    fn get_config(&mut self) -> Result<TezosConfig> {
+        match self.get("/config".into()) {
+            Ok(Some(config)) => Ok(config),
+            Ok(None) => Ok(TezosConfig::default()),
+            Err(err) => Err(err_into(err)),
+        }
+    }
+
+    fn set_config(&mut self, config: TezosConfig) -> Result<()> {
+        self.set("/config".into(), Some(config)).map_err(err_into)
+    }
+
+    fn get_live_blocks(&mut self) -> Result<LiveBlocks> {
+        match self.get("/live_blocks".into()) {
+            Ok(Some(live_blocks)) => Ok(live_blocks),
+            Ok(None) => Ok(LiveBlocks::default()),
+            Err(err) => Err(err_into(err)),
+        }
+    }
+
+    fn set_live_blocks(&mut self, live_blocks: LiveBlocks) -> Result<()> {
+        self.set("/live_blocks".into(), Some(live_blocks))
+            .map_err(err_into)
+    }
+
     fn get_balance(&mut self, address: &str) -> Result<Option<Mutez>> {
         self.get(format!("/context/contracts/{}/balance", address))
             .map_err(err_into)
@@ -102,6 +226,245 @@ impl<Backend: StoreBackend> TezosContext for LayeredStore<Backend> {
             .map_err(err_into)
     }
 
+    fn get_batcher(&mut self) -> Result<Option<ImplicitAddress>> {
+        self.get("/batcher".into()).map_err(err_into)
+    }
+
+    fn set_batcher(&mut self, address: ImplicitAddress) -> Result<()> {
+        self.set("/batcher".into(), Some(address)).map_err(err_into)
+    }
+
+    fn get_admin_key(&mut self) -> Result<Option<PublicKey>> {
+        self.get("/admin".into()).map_err(err_into)
+    }
+
+    fn set_admin_key(&mut self, public_key: PublicKey) -> Result<()> {
+        self.set("/admin".into(), Some(public_key))
+            .map_err(err_into)
+    }
+
+    fn is_bridge(&self, ticketer: &str) -> Result<bool> {
+        self.has(format!("/bridges/{}", ticketer)).map_err(err_into)
+    }
+
+    fn set_bridge(&mut self, ticketer: ContractAddress) -> Result<()> {
+        self.set(format!("/bridges/{}", ticketer.value()), Some(ticketer))
+            .map_err(err_into)
+    }
+
+    fn get_l1_contract(&mut self, address: &str) -> Result<Option<Micheline>> {
+        self.get(format!("/l1_contracts/{}", address))
+            .map_err(err_into)
+    }
+
+    fn set_l1_contract(&mut self, address: &str, parameter_type: Micheline) -> Result<()> {
+        // Also exposed as the contract type, so that `CONTRACT` can typecheck the L1 proxy
+        self.set(
+            format!("/context/contracts/{}/entrypoints", address),
+            Some(parameter_type.clone()),
+        )
+        .map_err(err_into)?;
+        self.set(format!("/l1_contracts/{}", address), Some(parameter_type))
+            .map_err(err_into)
+    }
+
+    fn get_outbox_queue(&mut self) -> Result<Vec<OutboxCall>> {
+        match self.get::<OutboxQueue>("/outbox_queue".into()) {
+            Ok(Some(queue)) => Ok(queue.0),
+            Ok(None) => Ok(Vec::new()),
+            Err(err) => Err(err_into(err)),
+        }
+    }
+
+    fn set_outbox_queue(&mut self, queue: Vec<OutboxCall>) -> Result<()> {
+        let value = match queue.is_empty() {
+            true => None,
+            false => Some(OutboxQueue(queue)),
+        };
+        self.set("/outbox_queue".into(), value).map_err(err_into)
+    }
+
+    fn get_refund_queue(&mut self) -> Result<Vec<OutboxCall>> {
+        match self.get::<OutboxQueue>("/outbox_refunds".into()) {
+            Ok(Some(queue)) => Ok(queue.0),
+            Ok(None) => Ok(Vec::new()),
+            Err(err) => Err(err_into(err)),
+        }
+    }
+
+    fn set_refund_queue(&mut self, queue: Vec<OutboxCall>) -> Result<()> {
+        let value = match queue.is_empty() {
+            true => None,
+            false => Some(OutboxQueue(queue)),
+        };
+        self.set("/outbox_refunds".into(), value).map_err(err_into)
+    }
+
+    fn get_outbox_count(&mut self) -> Result<u64> {
+        Ok(self
+            .get("/outbox_count".into())
+            .map_err(err_into)?
+            .unwrap_or(0))
+    }
+
+    fn set_outbox_count(&mut self, count: u64) -> Result<()> {
+        self.set("/outbox_count".into(), Some(count))
+            .map_err(err_into)
+    }
+
+    fn get_oracle(&mut self) -> Result<Option<ContractAddress>> {
+        self.get("/oracle/address".into()).map_err(err_into)
+    }
+
+    fn set_oracle(&mut self, address: ContractAddress) -> Result<()> {
+        // Also exposed as the contract type, so that contracts can request values via `CONTRACT`
+        self.set(
+            format!("/context/contracts/{}/entrypoints", address.value()),
+            Some(oracle_parameter_type()),
+        )
+        .map_err(err_into)?;
+        self.set("/oracle/address".into(), Some(address))
+            .map_err(err_into)
+    }
+
+    fn get_oracle_value(&mut self, key: &str) -> Result<Option<OracleValue>> {
+        self.get(format!("/context/oracle/{}", key))
+            .map_err(err_into)
+    }
+
+    fn set_oracle_value(&mut self, key: &str, value: OracleValue) -> Result<()> {
+        // Feeds are read by contracts, so they are covered by the context hash
+        self.set(format!("/context/oracle/{}", key), Some(value))
+            .map_err(err_into)
+    }
+
+    fn get_scheduler(&mut self) -> Result<Option<ContractAddress>> {
+        self.get("/scheduler/address".into()).map_err(err_into)
+    }
+
+    fn set_scheduler(&mut self, address: ContractAddress) -> Result<()> {
+        // Also exposed as the contract type, so that contracts can register calls via `CONTRACT`
+        self.set(
+            format!("/context/contracts/{}/entrypoints", address.value()),
+            Some(scheduler_parameter_type()),
+        )
+        .map_err(err_into)?;
+        self.set("/scheduler/address".into(), Some(address))
+            .map_err(err_into)
+    }
+
+    fn get_scheduled_call(&mut self, id: u64) -> Result<Option<ScheduledCall>> {
+        self.get(format!("/scheduler/calls/{}", id))
+            .map_err(err_into)
+    }
+
+    fn set_scheduled_call(&mut self, id: u64, call: Option<ScheduledCall>) -> Result<()> {
+        self.set(format!("/scheduler/calls/{}", id), call)
+            .map_err(err_into)
+    }
+
+    fn get_scheduled_queue(&mut self, level: i32) -> Result<Vec<u64>> {
+        match self.get::<ScheduledQueue>(format!("/scheduler/queue/{}", level)) {
+            Ok(Some(queue)) => Ok(queue.0),
+            Ok(None) => Ok(Vec::new()),
+            Err(err) => Err(err_into(err)),
+        }
+    }
+
+    fn set_scheduled_queue(&mut self, level: i32, queue: Vec<u64>) -> Result<()> {
+        let value = match queue.is_empty() {
+            true => None,
+            false => Some(ScheduledQueue(queue)),
+        };
+        self.set(format!("/scheduler/queue/{}", level), value)
+            .map_err(err_into)
+    }
+
+    fn get_scheduled_count(&mut self) -> Result<u64> {
+        Ok(self
+            .get("/scheduler/count".into())
+            .map_err(err_into)?
+            .unwrap_or(0))
+    }
+
+    fn set_scheduled_count(&mut self, count: u64) -> Result<()> {
+        self.set("/scheduler/count".into(), Some(count))
+            .map_err(err_into)
+    }
+
+    fn get_scheduled_receipts(&mut self, level: i32) -> Result<Vec<ScheduledCallReceipt>> {
+        match self.get::<ScheduledCallReceipts>(format!("/scheduler/receipts/{}", level)) {
+            Ok(Some(receipts)) => Ok(receipts.0),
+            Ok(None) => Ok(Vec::new()),
+            Err(err) => Err(err_into(err)),
+        }
+    }
+
+    fn set_scheduled_receipts(
+        &mut self,
+        level: i32,
+        receipts: Vec<ScheduledCallReceipt>,
+    ) -> Result<()> {
+        self.set(
+            format!("/scheduler/receipts/{}", level),
+            Some(ScheduledCallReceipts(receipts)),
+        )
+        .map_err(err_into)
+    }
+
+    fn get_sponsorship(&mut self, hash: &str) -> Result<Option<ImplicitAddress>> {
+        self.get(format!("/sponsorships/{}", hash))
+            .map_err(err_into)
+    }
+
+    fn set_sponsorship(&mut self, hash: &str, sponsor: Option<ImplicitAddress>) -> Result<()> {
+        self.set(format!("/sponsorships/{}", hash), sponsor)
+            .map_err(err_into)
+    }
+
+    fn get_sponsor_policy(&mut self, address: &str) -> Result<Option<SponsorPolicy>> {
+        self.get(format!("/sponsors/{}/policy", address))
+            .map_err(err_into)
+    }
+
+    fn set_sponsor_policy(&mut self, address: &str, policy: SponsorPolicy) -> Result<()> {
+        self.set(format!("/sponsors/{}/policy", address), Some(policy))
+            .map_err(err_into)
+    }
+
+    fn get_sponsor_spending(&mut self, address: &str) -> Result<SponsorSpending> {
+        Ok(self
+            .get(format!("/sponsors/{}/spending", address))
+            .map_err(err_into)?
+            .unwrap_or_default())
+    }
+
+    fn set_sponsor_spending(&mut self, address: &str, spending: SponsorSpending) -> Result<()> {
+        self.set(format!("/sponsors/{}/spending", address), Some(spending))
+            .map_err(err_into)
+    }
+
+    fn get_anti_spam_policy(&mut self) -> Result<Option<AntiSpamPolicy>> {
+        self.get("/anti_spam/policy".into()).map_err(err_into)
+    }
+
+    fn set_anti_spam_policy(&mut self, policy: AntiSpamPolicy) -> Result<()> {
+        self.set("/anti_spam/policy".into(), Some(policy))
+            .map_err(err_into)
+    }
+
+    fn get_operation_quota(&mut self, address: &str) -> Result<OperationQuota> {
+        Ok(self
+            .get(format!("/anti_spam/quotas/{}", address))
+            .map_err(err_into)?
+            .unwrap_or_default())
+    }
+
+    fn set_operation_quota(&mut self, address: &str, quota: OperationQuota) -> Result<()> {
+        self.set(format!("/anti_spam/quotas/{}", address), Some(quota))
+            .map_err(err_into)
+    }
+
     fn set_batch_receipt(&mut self, receipt: BatchReceipt) -> Result<()> {
         self.set(
             format!("/batches/{}", receipt.hash.value()).into(),
@@ -116,6 +479,44 @@ impl<Backend: StoreBackend> TezosContext for LayeredStore<Backend> {
             .ok_or(Error::BatchNotFound { hash: hash.into() })
     }
 
+    fn has_batch_receipt(&self, hash: &str) -> Result<bool> {
+        self.has(format!("/batches/{}", hash)).map_err(err_into)
+    }
+
+    fn get_pending_batch(&mut self) -> Result<Option<PendingBatch>> {
+        self.get("/pending_batch".into()).map_err(err_into)
+    }
+
+    fn set_pending_batch(&mut self, batch: Option<PendingBatch>) -> Result<()> {
+        self.set("/pending_batch".into(), batch).map_err(err_into)
+    }
+
+    fn get_pending_deposits(&mut self) -> Result<Vec<Deposit>> {
+        match self.get::<PendingDeposits>("/pending_deposits".into()) {
+            Ok(Some(deposits)) => Ok(deposits.0),
+            Ok(None) => Ok(Vec::new()),
+            Err(err) => Err(err_into(err)),
+        }
+    }
+
+    fn set_pending_deposits(&mut self, deposits: Vec<Deposit>) -> Result<()> {
+        let value = match deposits.is_empty() {
+            true => None,
+            false => Some(PendingDeposits(deposits)),
+        };
+        self.set("/pending_deposits".into(), value)
+            .map_err(err_into)
+    }
+
+    fn get_batch_timestamp(&mut self) -> Result<Option<i64>> {
+        self.get("/batch_timestamp".into()).map_err(err_into)
+    }
+
+    fn set_batch_timestamp(&mut self, timestamp: Option<i64>) -> Result<()> {
+        self.set("/batch_timestamp".into(), timestamp)
+            .map_err(err_into)
+    }
+
     fn set_operation_receipt(&mut self, receipt: Operation) -> Result<()> {
         self.set(
             format!(
@@ -157,6 +558,11 @@ impl<Backend: StoreBackend> TezosContext for LayeredStore<Backend> {
         .map_err(err_into)
     }
 
+    fn get_context_hash(&self) -> Result<ContextHash> {
+        let root = self.merkle_root().map_err(err_into)?;
+        Ok(ContextHash::from_bytes(root.as_slice())?)
+    }
+
     fn check_no_pending_changes(&self) -> Result<()> {
         if self.has_pending_changes() {
             Err(layered_store::Error::ContextUnstagedError.into())
@@ -48,7 +48,9 @@ pub enum Error {
     Internal(InternalError),
     OperationKindUnsupported,
     BalanceNotInitialized,
+    BatcherNotInitialized,
     BalanceTooLow { balance: Mutez },
+    BalanceOverflow { balance: Mutez },
     ContractCodeMissing { address: String },
     ContractStorageMissing { address: String },
     InconsistentSources,
@@ -59,6 +61,15 @@ pub enum Error {
     CounterInThePast { counter: String },
     BatchNotFound { hash: String },
     OperationNotFound { hash: String },
+    InvalidContextProof,
+    InvalidDepositPayload,
+    UntrustedTicketer { ticketer: String },
+    InvalidWithdrawalParameter,
+    InvalidL1CallParameter,
+    InvalidSchedulerParameter,
+    InvalidOracleParameter,
+    StaleOracleValue { key: String },
+    OutboxLimitExceeded { limit: i32 },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -91,6 +102,7 @@ impl_from_error!(serde_json_wasm::de::Error, Encoding);
 impl_from_error!(ibig::error::OutOfBoundsError, Encoding);
 impl_from_error!(ibig::error::ParseError, Encoding);
 impl_from_error!(chrono::ParseError, Encoding);
+impl_from_error!(hex::FromHexError, Encoding);
 impl_from_error!(&str, Encoding);
 
 impl From<michelson_vm::Error> for Error {
@@ -3,10 +3,20 @@
 // SPDX-License-Identifier: MIT
 
 use tezos_core::types::mutez::Mutez;
-use tezos_rpc::models::balance_update::{BalanceUpdate, Contract, Kind, Origin};
+use tezos_rpc::models::balance_update::{
+    Accumulator, BalanceUpdate, Category, Contract, Kind, Origin,
+};
 
 use crate::{context::TezosContext, Error, Result};
 
+fn checked_add(balance: Mutez, amount: &Mutez) -> Result<Mutez> {
+    let (a, b): (i64, i64) = (balance.try_into()?, (*amount).try_into()?);
+    match a.checked_add(b) {
+        Some(res) => Ok(Mutez::try_from(res as u64)?),
+        None => Err(Error::BalanceOverflow { balance }),
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BalanceUpdates {
     balance_updates: Vec<BalanceUpdate>,
@@ -26,10 +36,37 @@ impl BalanceUpdates {
     pub fn fee(source: &str, amount: &Mutez) -> Vec<BalanceUpdate> {
         let mut res = Self::new();
         res.push_contract_update(source, format!("-{}", amount));
-        // TODO: res.push_contract_update(producer, amount.to_string());
+        res.push_block_fees_update(amount.to_string());
+        res.balance_updates
+    }
+
+    pub fn block_fees(batcher: &str, amount: &Mutez) -> Vec<BalanceUpdate> {
+        let mut res = Self::new();
+        res.push_block_fees_update(format!("-{}", amount));
+        res.push_contract_update(batcher, amount.to_string());
         res.balance_updates
     }
 
+    /// Deposits are minted on L2, so there is no debited counterpart
+    pub fn deposit(receiver: &str, amount: &Mutez) -> Vec<BalanceUpdate> {
+        vec![BalanceUpdate::Contract(Contract {
+            kind: Kind::Contract,
+            change: amount.to_string(),
+            contract: receiver.to_string(),
+            origin: Some(Origin::Migration),
+        })]
+    }
+
+    fn push_block_fees_update(&mut self, change: String) {
+        self.balance_updates
+            .push(BalanceUpdate::Accumulator(Accumulator {
+                kind: Kind::Accumulator,
+                change,
+                category: Category::BlockFees,
+                origin: Some(Origin::Block),
+            }));
+    }
+
     fn push_contract_update(&mut self, contract: &str, change: String) {
         self.balance_updates.push(BalanceUpdate::Contract(Contract {
             kind: Kind::Contract,
@@ -60,7 +97,7 @@ impl BalanceUpdates {
             }
 
             src_balance -= *amount;
-            dst_balance += *amount;
+            dst_balance = checked_add(dst_balance, amount)?;
 
             context.set_balance(source, src_balance.clone())?;
             context.set_balance(destination, dst_balance.clone())?;
@@ -72,6 +109,29 @@ impl BalanceUpdates {
         Ok((src_balance, dst_balance))
     }
 
+    /// Withdrawn tez leave L2, so there is no credited counterpart
+    pub fn burn(
+        &mut self,
+        context: &mut impl TezosContext,
+        source: &str,
+        amount: &Mutez,
+    ) -> Result<Mutez> {
+        let balance = Self::reserve(context, source, amount)?;
+        self.push_contract_update(source, format!("-{}", amount));
+        Ok(balance)
+    }
+
+    pub fn credit(
+        context: &mut impl TezosContext,
+        destination: &str,
+        amount: &Mutez,
+    ) -> Result<Mutez> {
+        let dst_balance = context.get_balance(destination)?.unwrap_or(0u32.into());
+        let dst_balance = checked_add(dst_balance, amount)?;
+        context.set_balance(destination, dst_balance.clone())?;
+        Ok(dst_balance)
+    }
+
     pub fn reserve(context: &mut impl TezosContext, source: &str, amount: &Mutez) -> Result<Mutez> {
         let mut src_balance = context
             .get_balance(source)?
@@ -99,3 +159,30 @@ impl Into<Option<Vec<BalanceUpdate>>> for BalanceUpdates {
         None
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::TezosEphemeralContext;
+
+    #[test]
+    fn test_credit_overflow() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+        let address = "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU";
+        let max = Mutez::try_from(i64::MAX as u64)?;
+
+        context.set_balance(address, Mutez::from(1u32))?;
+        assert_eq!(
+            Mutez::from(2u32),
+            BalanceUpdates::credit(&mut context, address, &Mutez::from(1u32))?
+        );
+
+        context.set_balance(address, max)?;
+        assert!(matches!(
+            BalanceUpdates::credit(&mut context, address, &Mutez::from(1u32)),
+            Err(Error::BalanceOverflow { .. })
+        ));
+        assert_eq!(Some(max), context.get_balance(address)?);
+        Ok(())
+    }
+}
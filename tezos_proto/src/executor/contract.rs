@@ -16,7 +16,10 @@ use tezos_operation::operations::{
     Entrypoint, OperationContent, Origination, Parameters, Transaction,
 };
 
-use crate::{config, context::TezosContext, Error, Result};
+use crate::{
+    context::{head::Head, TezosContext},
+    Error, Result,
+};
 
 #[derive(Debug, From)]
 pub enum ContractOutput {
@@ -24,6 +27,14 @@ pub enum ContractOutput {
     Return(ScriptReturn),
 }
 
+/// Timestamp of the block being produced, falls back to the next slot after head (e.g. for simulations)
+pub fn block_timestamp(context: &mut impl TezosContext, head: &Head) -> Result<i64> {
+    match context.get_batch_timestamp()? {
+        Some(timestamp) => Ok(timestamp),
+        None => Ok(head.timestamp + context.get_config()?.block_time()),
+    }
+}
+
 pub fn deploy_contract(
     context: &mut (impl TezosContext + InterpreterContext),
     origination: &Origination,
@@ -31,6 +42,7 @@ pub fn deploy_contract(
     balance: Mutez,
 ) -> Result<ContractOutput> {
     let head = context.get_head()?;
+    let now = block_timestamp(context, &head)?;
     let script = MichelsonScript::try_from(origination.script.code.clone())?;
 
     let scope = OperationScope {
@@ -38,13 +50,14 @@ pub fn deploy_contract(
         balance,
         chain_id: head.chain_id,
         level: head.level + 1,
-        now: head.timestamp + config::BLOCK_TIME,
+        now,
         parameters: None,
         self_address,
         self_type: script.get_type(),
         sender: origination.source.clone().into(),
         source: origination.source.clone().into(),
         storage: origination.script.storage.clone(),
+        limits: context.get_config()?.interpreter_limits(),
     };
 
     match script.originate(&scope, context) {
@@ -53,7 +66,8 @@ pub fn deploy_contract(
             context.set_contract_storage(scope.self_address.value(), ret.storage.clone())?;
             Ok(ret.into())
         }
-        Err(err) => Err(err.into()),
+        Err(err @ michelson_vm::Error::Internal(_)) => Err(err.into()),
+        Err(err) => Ok(err.into()),
     }
 }
 
@@ -77,6 +91,7 @@ pub fn execute_contract(
             })?;
 
     let head = context.get_head()?;
+    let now = block_timestamp(context, &head)?;
     let script = MichelsonScript::try_from(code)?;
 
     let scope = OperationScope {
@@ -84,7 +99,7 @@ pub fn execute_contract(
         balance,
         chain_id: head.chain_id,
         level: head.level + 1,
-        now: head.timestamp + config::BLOCK_TIME,
+        now,
         parameters: match &transaction.parameters {
             Some(params) => Some((params.entrypoint.to_str().into(), params.value.clone())),
             None => None,
@@ -94,6 +109,7 @@ pub fn execute_contract(
         sender: sender.unwrap_or(transaction.source.clone().into()),
         source: transaction.source.clone(),
         storage: initial_storage,
+        limits: context.get_config()?.interpreter_limits(),
     };
 
     match script.call(&scope, context) {
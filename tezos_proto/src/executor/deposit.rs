@@ -0,0 +1,191 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use michelson_vm::types::StackItem;
+use tezos_core::types::{
+    encoded::{Address, ContractAddress, Encoded, ImplicitAddress},
+    mutez::Mutez,
+};
+use tezos_michelson::{
+    micheline::Micheline,
+    michelson::{types, types::Type},
+};
+
+use crate::{
+    context::{outbox::OutboxCall, TezosContext},
+    executor::{
+        balance_updates::BalanceUpdates,
+        withdrawal::{Withdrawal, WITHDRAW_ENTRYPOINT},
+    },
+    Error, Result,
+};
+
+/// Tez bridged from L1: the bridge contract locks tez and sends a ticket to the rollup
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deposit {
+    /// L1 contract that issued the ticket, has to be allowlisted
+    pub ticketer: ContractAddress,
+    /// L2 account credited with the ticket amount
+    pub receiver: Address,
+    pub amount: Mutez,
+    /// L1 contract that sent the ticket, gets it back if the deposit is rejected
+    pub sender: ContractAddress,
+    /// L1 account that signed the transfer, receives the refund
+    pub source: ImplicitAddress,
+}
+
+/// Type of both deposit and withdrawal parameters, `pair (address %receiver) (ticket unit)`.
+/// Tickets are represented as `Pair ticketer (Pair contents amount)` in L1 messages,
+/// the amount is read as mutez so that it fits into the balance type.
+pub fn ticket_transfer_type() -> Type {
+    types::pair(vec![
+        types::address(),
+        types::pair(vec![
+            types::address(),
+            types::pair(vec![types::unit(), types::mutez()]),
+        ]),
+    ])
+}
+
+fn unpair(item: StackItem) -> Result<(StackItem, StackItem)> {
+    match item {
+        StackItem::Pair(pair) => Ok(pair.unpair()),
+        _ => Err(Error::InvalidDepositPayload),
+    }
+}
+
+fn into_address(item: StackItem) -> Result<Address> {
+    match item {
+        StackItem::Address(address) => Ok(address.unwrap()),
+        _ => Err(Error::InvalidDepositPayload),
+    }
+}
+
+impl Deposit {
+    /// Parses the rollup call parameter (see `ticket_transfer_type`)
+    pub fn from_micheline(
+        payload: Micheline,
+        sender: ContractAddress,
+        source: ImplicitAddress,
+    ) -> Result<Self> {
+        let item = StackItem::from_micheline(payload, &ticket_transfer_type())
+            .map_err(|_| Error::InvalidDepositPayload)?;
+
+        let (receiver, ticket) = unpair(item)?;
+        let (ticketer, ticket) = unpair(ticket)?;
+        let (_, amount) = unpair(ticket)?;
+
+        let ticketer = match into_address(ticketer)? {
+            Address::Originated(ticketer) => ticketer,
+            Address::Implicit(_) => return Err(Error::InvalidDepositPayload),
+        };
+        let amount: Mutez = match amount {
+            StackItem::Mutez(amount) => amount.try_into()?,
+            _ => return Err(Error::InvalidDepositPayload),
+        };
+
+        Ok(Self {
+            ticketer,
+            receiver: into_address(receiver)?,
+            amount,
+            sender,
+            source,
+        })
+    }
+
+    /// Call of the sender `withdraw` entrypoint returning the ticket to the transfer source,
+    /// same format as the bridge withdrawals
+    pub fn refund_call(&self) -> Result<OutboxCall> {
+        let withdrawal = Withdrawal {
+            ticketer: self.ticketer.clone(),
+            receiver: Address::Implicit(self.source.clone()),
+            amount: self.amount,
+        };
+        Ok(OutboxCall {
+            destination: self.sender.clone(),
+            entrypoint: WITHDRAW_ENTRYPOINT.into(),
+            parameters: withdrawal.parameters()?,
+        })
+    }
+}
+
+/// Credits the receiver if the ticket comes from an allowlisted bridge
+pub fn apply_deposit(context: &mut impl TezosContext, deposit: &Deposit) -> Result<()> {
+    if !context.is_bridge(deposit.ticketer.value())? {
+        return Err(Error::UntrustedTicketer {
+            ticketer: deposit.ticketer.value().into(),
+        });
+    }
+
+    BalanceUpdates::credit(context, deposit.receiver.value(), &deposit.amount)?;
+    Ok(())
+}
+
+/// Queues the refund of a rejected deposit. Unlike L2 calls, refunds are not subject
+/// to the outbox limit: the ticket is already held by the rollup and must not be lost.
+/// Refunds are kept in a separate queue, so that they do not count against the L2 calls,
+/// and take the room left in the outbox (the ones that do not fit go to the next levels).
+pub fn refund_deposit(context: &mut impl TezosContext, deposit: &Deposit) -> Result<()> {
+    let mut queue = context.get_refund_queue()?;
+    queue.push(deposit.refund_call()?);
+    context.set_refund_queue(queue)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::TezosEphemeralContext;
+
+    #[test]
+    fn test_deposit() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+
+        // Pair "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU" (Pair "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm" (Pair Unit 100))
+        let payload = Micheline::from_bytes(
+            hex::decode(
+                "07070100000024747a315633644853434a6e575052647a446d5a47435a61544d7569546d627450616b\
+                6d55070701000000244b54314d6a6a636236746d53734c6d37436233445351737a65506a666368504d\
+                3455786d0707030b00a401",
+            )?
+            .as_slice(),
+        )?;
+        let deposit = Deposit::from_micheline(
+            payload,
+            "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm".try_into()?,
+            "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU".try_into()?,
+        )?;
+        assert_eq!(
+            "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm",
+            deposit.ticketer.value()
+        );
+        assert_eq!(
+            "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU",
+            deposit.receiver.value()
+        );
+        assert_eq!(Mutez::from(100u32), deposit.amount);
+
+        assert!(matches!(
+            apply_deposit(&mut context, &deposit),
+            Err(Error::UntrustedTicketer { .. })
+        ));
+
+        refund_deposit(&mut context, &deposit)?;
+        assert!(context.get_outbox_queue()?.is_empty());
+        let queue = context.get_refund_queue()?;
+        assert_eq!(1, queue.len());
+        assert_eq!(
+            "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm",
+            queue[0].destination.value()
+        );
+        assert_eq!(WITHDRAW_ENTRYPOINT, queue[0].entrypoint);
+
+        context.set_bridge(deposit.ticketer.clone())?;
+        apply_deposit(&mut context, &deposit)?;
+        assert_eq!(
+            Some(Mutez::from(100u32)),
+            context.get_balance("tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU")?
+        );
+        Ok(())
+    }
+}
@@ -4,10 +4,15 @@
 
 pub mod balance_updates;
 pub mod contract;
+pub mod deposit;
 pub mod lazy_diff;
 pub mod operation;
+pub mod oracle;
 pub mod origination;
+pub mod outbox;
 pub mod result;
 pub mod reveal;
 pub mod rpc_errors;
+pub mod scheduler;
 pub mod transaction;
+pub mod withdrawal;
@@ -3,7 +3,10 @@
 // SPDX-License-Identifier: MIT
 
 use michelson_vm::interpreter::InterpreterContext;
-use tezos_core::types::encoded::Encoded;
+use tezos_core::types::{
+    encoded::{Address, Encoded},
+    mutez::Mutez,
+};
 use tezos_operation::operations::OperationContent;
 use tezos_rpc::models::operation::Operation as OperationReceipt;
 
@@ -15,20 +18,34 @@ use crate::{
         balance_updates::BalanceUpdates, origination::execute_origination, reveal::execute_reveal,
         transaction::execute_transaction,
     },
-    validator::operation::ValidOperation,
+    validator::{anti_spam::record_operation, operation::ValidOperation},
 };
 
 pub fn execute_operation(
     context: &mut (impl TezosContext + InterpreterContext),
     opg: &ValidOperation,
 ) -> Result<OperationReceipt> {
+    apply_operation(context, opg).map(|(receipt, _)| receipt)
+}
+
+/// Executes operation group and returns its receipt along with the fees actually paid
+pub fn apply_operation(
+    context: &mut (impl TezosContext + InterpreterContext),
+    opg: &ValidOperation,
+) -> Result<(OperationReceipt, Mutez)> {
     context.check_no_pending_changes()?;
 
     let mut failed_idx: Option<usize> = None;
     let mut origination_index: i32 = 0;
     let mut results = Vec::new();
 
-    BalanceUpdates::reserve(context, opg.source.value(), &opg.total_fees)?;
+    BalanceUpdates::reserve(context, opg.fee_payer(), &opg.total_fees)?;
+    if opg.sponsor.is_some() && context.get_balance(opg.payer())?.is_none() {
+        // Sponsored accounts can have no balance at all
+        context.set_balance(opg.payer(), 0u32.into())?;
+    }
+    // Transactions of a contract wallet are sent by the wallet
+    let sender: Option<Address> = opg.wallet.clone().map(|wallet| wallet.into());
 
     for (i, content) in opg.origin.contents.iter().enumerate() {
         let skip = failed_idx.is_some();
@@ -42,7 +59,7 @@ pub fn execute_operation(
                 skip,
             )?,
             OperationContent::Transaction(transaction) => {
-                execute_transaction(context, transaction, None, skip)?
+                execute_transaction(context, transaction, sender.clone(), 0, skip)?
             }
             _ => return Err(Error::OperationKindUnsupported),
         };
@@ -56,28 +73,42 @@ pub fn execute_operation(
         results.push(result);
     }
 
-    if let Some(stop) = failed_idx {
+    let paid_fees = if let Some(stop) = failed_idx {
         results[0..stop].iter_mut().for_each(|r| r.backtrack());
 
-        let total_fees = opg.origin.contents[0..=stop].iter().map(|c| c.fee()).sum();
-        BalanceUpdates::reserve(context, opg.source.value(), &total_fees)?;
+        let total_fees: Mutez = opg.origin.contents[0..=stop].iter().map(|c| c.fee()).sum();
+        BalanceUpdates::reserve(context, opg.fee_payer(), &total_fees)?;
+        total_fees
     } else {
         // all applied, no rollbacks
-        context.set_counter(opg.source.value(), opg.last_counter.clone())?;
+        context.set_counter(opg.payer(), opg.last_counter.clone())?;
+        opg.total_fees
+    };
+
+    if let Some(sponsor) = &opg.sponsor {
+        let timestamp = context.get_head()?.timestamp;
+        let spending = context.get_sponsor_spending(sponsor.value())?;
+        context.set_sponsor_spending(sponsor.value(), spending.add(&paid_fees, timestamp)?)?;
+        context.set_sponsorship(opg.hash.value(), None)?;
     }
+    record_operation(context, opg.payer())?;
 
     context.commit()?;
     context.log(format!("Operation included: {}", opg.hash.value()));
 
     let head = context.get_head()?;
-    Ok(OperationReceipt {
+    let receipt = OperationReceipt {
         protocol: Some(PROTOCOL.try_into()?),
         chain_id: Some(head.chain_id),
         hash: Some(opg.hash.to_owned()),
         branch: opg.origin.branch.clone(),
         signature: Some(opg.origin.signature.clone()),
-        contents: results.into_iter().map(|r| r.into()).collect(),
-    })
+        contents: results
+            .into_iter()
+            .map(|r| r.into_receipt(opg.fee_payer()))
+            .collect(),
+    };
+    Ok((receipt, paid_fees))
 }
 
 #[cfg(test)]
@@ -148,6 +179,8 @@ mod test {
             ),
             last_counter: 4u32.into(),
             source: source.try_into()?,
+            wallet: None,
+            sponsor: None,
             total_fees: 3000u32.into(),
             total_spent: 0u32.into(), // <-- not true, fot the sake of the test
         };
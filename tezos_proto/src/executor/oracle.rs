@@ -0,0 +1,234 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use ibig::UBig;
+use michelson_vm::types::{
+    InternalContent, NatItem, PairItem, StackItem, StringItem, TimestampItem,
+};
+use tezos_core::types::encoded::Address;
+use tezos_michelson::{
+    micheline::Micheline,
+    michelson::types::{self, Type},
+};
+use tezos_operation::operations::{OperationContent, Transaction};
+
+use crate::{
+    context::{oracle::is_valid_oracle_key, TezosContext},
+    executor::{
+        contract::{block_timestamp, expand_content},
+        scheduler::prim,
+    },
+    Error, Result,
+};
+
+/// Entrypoint of the oracle requesting the latest value of a feed
+pub const GET_ENTRYPOINT: &str = "get";
+
+/// Parameter of the callback: `pair (string %key) (nat %value) (timestamp %updated)`
+pub fn oracle_callback_type() -> Type {
+    types::pair(vec![
+        types::string(),
+        types::pair(vec![types::nat(), types::timestamp()]),
+    ])
+}
+
+/// Parameter type of the oracle:
+/// `pair %get (string %key) (nat %max_age) (contract %callback (pair string nat timestamp))`
+pub fn oracle_parameter_type() -> Micheline {
+    let callback = prim(
+        "pair",
+        vec![
+            prim("string", vec![], Some("key")),
+            prim(
+                "pair",
+                vec![
+                    prim("nat", vec![], Some("value")),
+                    prim("timestamp", vec![], Some("updated")),
+                ],
+                None,
+            ),
+        ],
+        None,
+    );
+    prim(
+        "pair",
+        vec![
+            prim("string", vec![], Some("key")),
+            prim(
+                "pair",
+                vec![
+                    prim("nat", vec![], Some("max_age")),
+                    prim("contract", vec![callback], Some("callback")),
+                ],
+                None,
+            ),
+        ],
+        Some(GET_ENTRYPOINT),
+    )
+}
+
+/// Handles a call of the oracle system contract, returns the callback transaction
+/// carrying the feed value (the oracle is its sender).
+/// Fails if the feed has no value or if it is older than `max_age` seconds (unless zero).
+pub fn execute_oracle_request(
+    context: &mut impl TezosContext,
+    transaction: &Transaction,
+) -> Result<Transaction> {
+    if transaction.amount != 0u32.into() {
+        return Err(Error::InvalidOracleParameter);
+    }
+    let value = match &transaction.parameters {
+        Some(params) if params.entrypoint.to_str() == GET_ENTRYPOINT => params.value.clone(),
+        _ => return Err(Error::InvalidOracleParameter),
+    };
+    let ty: Type = oracle_parameter_type().try_into()?;
+    let mut items = match StackItem::from_micheline(value, &ty) {
+        Ok(StackItem::Pair(pair)) => pair.into_items(3)?.into_iter(),
+        _ => return Err(Error::InvalidOracleParameter),
+    };
+
+    let key = match items.next() {
+        Some(StackItem::String(key)) => key.unwrap(),
+        _ => return Err(Error::InvalidOracleParameter),
+    };
+    if !is_valid_oracle_key(&key) {
+        return Err(Error::InvalidOracleParameter);
+    }
+    let max_age: usize = match items.next() {
+        Some(StackItem::Nat(nat)) => nat.try_into().map_err(|_| Error::InvalidOracleParameter)?,
+        _ => return Err(Error::InvalidOracleParameter),
+    };
+    let callback = match items.next() {
+        Some(StackItem::Contract(contract)) => match contract.into_components().0 {
+            Address::Originated(kt) => Address::Originated(kt),
+            Address::Implicit(_) => return Err(Error::InvalidOracleParameter),
+        },
+        _ => return Err(Error::InvalidOracleParameter),
+    };
+
+    let head = context.get_head()?;
+    let now = block_timestamp(context, &head)?;
+    let value = match context.get_oracle_value(&key)? {
+        Some(value) if max_age == 0 || value.age(now) <= max_age as i64 => value,
+        _ => return Err(Error::StaleOracleValue { key }),
+    };
+
+    let item: StackItem = PairItem::from_items(vec![
+        StringItem::from(key).into(),
+        NatItem::from(UBig::from(value.value)).into(),
+        TimestampItem::new(value.timestamp)?.into(),
+    ])?
+    .into();
+    let content = expand_content(InternalContent::Transaction {
+        destination: callback,
+        parameter: item.into_micheline(&oracle_callback_type())?,
+        amount: 0u32.into(),
+        source: transaction.source.clone(),
+    });
+    match content {
+        OperationContent::Transaction(callback) => Ok(callback),
+        _ => Err(Error::OperationKindUnsupported),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tezos_core::types::{encoded::ContractAddress, mutez::Mutez};
+    use tezos_operation::operations::{Entrypoint, Parameters};
+
+    use super::*;
+    use crate::{
+        context::{oracle::OracleValue, TezosEphemeralContext},
+        executor::transaction::execute_transaction,
+    };
+
+    // parameter (pair string nat timestamp); storage nat; code { CAR; CDR; CAR; NIL operation; PAIR }
+    const CONSUMER_CODE: &str = r#"[
+        {"prim":"parameter","args":[{"prim":"pair","args":[{"prim":"string"},
+            {"prim":"pair","args":[{"prim":"nat"},{"prim":"timestamp"}]}]}]},
+        {"prim":"storage","args":[{"prim":"nat"}]},
+        {"prim":"code","args":[[
+            {"prim":"CAR"},
+            {"prim":"CDR"},
+            {"prim":"CAR"},
+            {"prim":"NIL","args":[{"prim":"operation"}]},
+            {"prim":"PAIR"}]]}
+    ]"#;
+
+    fn request(source: &str, oracle: &ContractAddress, max_age: u32) -> Result<Transaction> {
+        let value: Micheline = serde_json::from_str(&format!(
+            r#"{{"prim":"Pair","args":[{{"string":"XTZ-USD"}},
+                {{"prim":"Pair","args":[{{"int":"{}"}},
+                {{"string":"KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi"}}]}}]}}"#,
+            max_age
+        ))
+        .unwrap();
+        Ok(Transaction {
+            source: source.try_into()?,
+            counter: 1u32.into(),
+            fee: 0u32.into(),
+            gas_limit: 0u32.into(),
+            storage_limit: 0u32.into(),
+            amount: 0u32.into(),
+            destination: oracle.clone().into(),
+            parameters: Some(Parameters {
+                entrypoint: Entrypoint::from_str(GET_ENTRYPOINT),
+                value,
+            }),
+        })
+    }
+
+    #[test]
+    fn test_oracle_request() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+
+        let source = "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU";
+        let oracle = ContractAddress::try_from("KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm")?;
+        let consumer = "KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi";
+        let code: Micheline = serde_json::from_str(CONSUMER_CODE).unwrap();
+        let storage: Micheline = serde_json::from_str(r#"{"int":"0"}"#).unwrap();
+        context.set_oracle(oracle.clone())?;
+        context.set_balance(source, Mutez::from(10000u32))?;
+        context.set_contract_code(consumer, code)?;
+        context.set_contract_storage(consumer, storage)?;
+        context.set_batch_timestamp(Some(1000))?;
+        context.commit()?;
+
+        // No value posted yet
+        assert!(
+            !execute_transaction(&mut context, &request(source, &oracle, 0)?, None, 0, false)?.ok()
+        );
+
+        context.set_oracle_value(
+            "XTZ-USD",
+            OracleValue {
+                value: 1_250_000,
+                timestamp: 940,
+                level: 0,
+            },
+        )?;
+        context.commit()?;
+
+        // Value is 60 seconds old
+        assert!(!execute_transaction(
+            &mut context,
+            &request(source, &oracle, 30)?,
+            None,
+            0,
+            false
+        )?
+        .ok());
+
+        let res =
+            execute_transaction(&mut context, &request(source, &oracle, 60)?, None, 0, false)?;
+        assert!(res.ok());
+        context.commit()?;
+        assert_eq!(
+            Some(serde_json::from_str(r#"{"int":"1250000"}"#).unwrap()),
+            context.get_contract_storage(consumer)?
+        );
+        assert_eq!(Some(Mutez::from(10000u32)), context.get_balance(source)?);
+        Ok(())
+    }
+}
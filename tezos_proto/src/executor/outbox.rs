@@ -0,0 +1,195 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use michelson_vm::{entrypoints::search_entrypoint, types::StackItem};
+use tezos_core::types::encoded::{ContractAddress, Encoded};
+use tezos_michelson::{
+    micheline::Micheline,
+    michelson::{
+        data::{self, Data},
+        types::Type,
+    },
+};
+use tezos_operation::operations::Transaction;
+
+use crate::{context::outbox::OutboxCall, context::TezosContext, Error, Result};
+
+/// Queues the call until the kernel writes it to the outbox.
+/// Fails if the outbox of the current level cannot fit it: the limit is shared
+/// between the messages already written and the L2 calls still queued
+/// (deposit refunds are queued separately and only take the room left).
+pub fn queue_outbox_call(context: &mut impl TezosContext, call: OutboxCall) -> Result<()> {
+    let limit = context
+        .get_config()?
+        .smart_rollup_max_outbox_messages_per_level;
+    let mut queue = context.get_outbox_queue()?;
+    let count = context.get_outbox_count()? + queue.len() as u64;
+    if count >= limit.max(0) as u64 {
+        return Err(Error::OutboxLimitExceeded { limit });
+    }
+    queue.push(call);
+    context.set_outbox_queue(queue)
+}
+
+/// Tells whether the type mentions `ticket` anywhere: such L1 contracts cannot be
+/// called from L2, otherwise L2 code could forge the tickets held by the rollup.
+pub fn contains_ticket(ty: &Micheline) -> bool {
+    let mut pending = vec![ty.clone()];
+    while let Some(node) = pending.pop() {
+        match node {
+            Micheline::Literal(_) => {}
+            Micheline::PrimitiveApplication(prim) => {
+                if prim.prim() == "ticket" {
+                    return true;
+                }
+                pending.extend(prim.into_args().unwrap_or_default())
+            }
+            Micheline::Sequence(seq) => pending.extend(seq.into_values()),
+        }
+    }
+    false
+}
+
+/// Forwards the transaction to a declared L1 contract through the outbox.
+/// The parameter is typechecked against the declared L1 parameter type,
+/// tez cannot be sent along since they are not locked on L1.
+pub fn execute_l1_call(
+    context: &mut impl TezosContext,
+    transaction: &Transaction,
+    destination: ContractAddress,
+    parameter_type: Micheline,
+) -> Result<OutboxCall> {
+    if transaction.amount != 0u32.into() || contains_ticket(&parameter_type) {
+        return Err(Error::InvalidL1CallParameter);
+    }
+
+    let (entrypoint, value) = match &transaction.parameters {
+        Some(params) => (params.entrypoint.to_str().to_string(), params.value.clone()),
+        None => ("default".to_string(), Data::Unit(data::unit()).into()),
+    };
+    let ty: Type = parameter_type
+        .try_into()
+        .map_err(|_| Error::InvalidL1CallParameter)?;
+    let ty = search_entrypoint(ty, Some(entrypoint.as_str()), 0)
+        .map_err(|_| Error::InvalidL1CallParameter)?;
+    let parameters = StackItem::from_micheline(value, &ty)
+        .and_then(|item| item.into_micheline(&ty))
+        .map_err(|_| Error::InvalidL1CallParameter)?;
+
+    let call = OutboxCall {
+        destination,
+        entrypoint,
+        parameters,
+    };
+    queue_outbox_call(context, call.clone())?;
+    Ok(call)
+}
+
+#[cfg(test)]
+mod test {
+    use tezos_operation::operations::{Entrypoint, Parameters};
+
+    use super::*;
+    use crate::context::TezosEphemeralContext;
+
+    #[test]
+    fn test_l1_call() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+
+        let destination = "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm";
+        // or (nat %mint) (address %burn)
+        let parameter_type: Micheline = serde_json::from_str(
+            r#"{"prim":"or","args":[{"prim":"nat","annots":["%mint"]},{"prim":"address","annots":["%burn"]}]}"#,
+        )
+        .unwrap();
+        context.set_l1_contract(destination, parameter_type.clone())?;
+
+        let mut transaction = Transaction {
+            source: "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU".try_into()?,
+            counter: 1u32.into(),
+            fee: 0u32.into(),
+            gas_limit: 0u32.into(),
+            storage_limit: 0u32.into(),
+            amount: 0u32.into(),
+            destination: destination.try_into()?,
+            parameters: Some(Parameters {
+                entrypoint: Entrypoint::from_str("mint"),
+                value: serde_json::from_str(r#"{"int":"42"}"#).unwrap(),
+            }),
+        };
+
+        let call = execute_l1_call(
+            &mut context,
+            &transaction,
+            destination.try_into()?,
+            parameter_type.clone(),
+        )?;
+        assert_eq!("mint", call.entrypoint);
+        assert_eq!(vec![call], context.get_outbox_queue()?);
+
+        transaction.parameters = Some(Parameters {
+            entrypoint: Entrypoint::from_str("burn"),
+            value: serde_json::from_str(r#"{"int":"42"}"#).unwrap(),
+        });
+        let res = execute_l1_call(
+            &mut context,
+            &transaction,
+            destination.try_into()?,
+            parameter_type.clone(),
+        );
+        assert!(matches!(res, Err(Error::InvalidL1CallParameter)));
+
+        let mut config = context.get_config()?;
+        config.smart_rollup_max_outbox_messages_per_level = 1;
+        context.set_config(config)?;
+        transaction.parameters = Some(Parameters {
+            entrypoint: Entrypoint::from_str("mint"),
+            value: serde_json::from_str(r#"{"int":"1"}"#).unwrap(),
+        });
+        let res = execute_l1_call(
+            &mut context,
+            &transaction,
+            destination.try_into()?,
+            parameter_type,
+        );
+        assert!(matches!(res, Err(Error::OutboxLimitExceeded { limit: 1 })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_l1_call_with_ticket() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+
+        let destination = "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm";
+        // or (nat %mint) (pair %burn address (ticket unit))
+        let parameter_type: Micheline = serde_json::from_str(
+            r#"{"prim":"or","args":[{"prim":"nat","annots":["%mint"]},{"prim":"pair","args":[{"prim":"address"},{"prim":"ticket","args":[{"prim":"unit"}]}],"annots":["%burn"]}]}"#,
+        )
+        .unwrap();
+        assert!(contains_ticket(&parameter_type));
+
+        let transaction = Transaction {
+            source: "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU".try_into()?,
+            counter: 1u32.into(),
+            fee: 0u32.into(),
+            gas_limit: 0u32.into(),
+            storage_limit: 0u32.into(),
+            amount: 0u32.into(),
+            destination: destination.try_into()?,
+            parameters: Some(Parameters {
+                entrypoint: Entrypoint::from_str("mint"),
+                value: serde_json::from_str(r#"{"int":"42"}"#).unwrap(),
+            }),
+        };
+        let res = execute_l1_call(
+            &mut context,
+            &transaction,
+            destination.try_into()?,
+            parameter_type,
+        );
+        assert!(matches!(res, Err(Error::InvalidL1CallParameter)));
+        assert!(context.get_outbox_queue()?.is_empty());
+        Ok(())
+    }
+}
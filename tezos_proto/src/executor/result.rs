@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: MIT
 
 use derive_more::{From, TryInto};
-use tezos_core::types::encoded::{Address, Encoded};
+use tezos_core::types::encoded::Address;
 use tezos_operation::operations::{Origination, Reveal, Transaction};
 use tezos_rpc::models::operation::operation_result::{
     operations::origination::OriginationOperationResult, operations::reveal::RevealOperationResult,
@@ -116,15 +116,15 @@ impl ExecutionResult {
             _ => unimplemented!("Only internal transactions allowed"),
         }
     }
-}
 
-impl Into<OperationContentAndResult> for ExecutionResult {
-    fn into(self) -> OperationContentAndResult {
+    /// Converts top-level result into the receipt, fees are charged from the given account
+    /// (source, contract wallet or sponsor)
+    pub fn into_receipt(self, fee_payer: &str) -> OperationContentAndResult {
         match self {
             Self::Reveal { content, result } => OperationContentAndResult::Reveal(RevealReceipt {
                 metadata: Some(RevealMetadata {
                     operation_result: result,
-                    balance_updates: BalanceUpdates::fee(content.source.value(), &content.fee),
+                    balance_updates: BalanceUpdates::fee(fee_payer, &content.fee),
                 }),
                 ..content.into()
             }),
@@ -132,7 +132,7 @@ impl Into<OperationContentAndResult> for ExecutionResult {
                 OperationContentAndResult::Origination(OriginationReceipt {
                     metadata: Some(OriginationMetadata {
                         operation_result: result,
-                        balance_updates: BalanceUpdates::fee(content.source.value(), &content.fee),
+                        balance_updates: BalanceUpdates::fee(fee_payer, &content.fee),
                     }),
                     ..content.into()
                 })
@@ -151,7 +151,7 @@ impl Into<OperationContentAndResult> for ExecutionResult {
                 OperationContentAndResult::Transaction(TransactionReceipt {
                     metadata: Some(TransactionMetadata {
                         operation_result: result,
-                        balance_updates: BalanceUpdates::fee(content.source.value(), &content.fee),
+                        balance_updates: BalanceUpdates::fee(fee_payer, &content.fee),
                         internal_operation_results: internals,
                     }),
                     ..content.into()
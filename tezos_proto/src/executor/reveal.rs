@@ -2,7 +2,10 @@
 //
 // SPDX-License-Identifier: MIT
 
-use tezos_core::types::encoded::Encoded;
+use tezos_core::{
+    internal::crypto::blake2b,
+    types::encoded::{Encoded, ImplicitAddress, PublicKey},
+};
 use tezos_operation::operations::Reveal;
 use tezos_rpc::models::operation::operation_result::{
     operations::reveal::RevealOperationResult, OperationResultStatus,
@@ -13,6 +16,13 @@ use crate::{
     Result,
 };
 
+pub fn public_key_hash(public_key: &PublicKey) -> Result<ImplicitAddress> {
+    let bytes = public_key.to_bytes()?; // first byte represents the curve
+    let mut digest = blake2b(&bytes[1..], 20)?;
+    digest.insert(0, bytes[0]);
+    Ok(ImplicitAddress::from_bytes(digest.as_slice())?)
+}
+
 pub fn execute_reveal(
     context: &mut impl TezosContext,
     reveal: &Reveal,
@@ -43,10 +53,10 @@ pub fn execute_reveal(
         return result!(Failed);
     }
 
-    // TODO: check that public key actually matches address {
-    //     errors.inconsistent_hash(&reveal.source);
-    //     return Ok(make_receipt!(OperationResultStatus::Failed))
-    // }
+    if public_key_hash(&reveal.public_key)?.value() != reveal.source.value() {
+        errors.inconsistent_hash(reveal.source.value());
+        return result!(Failed);
+    }
 
     context.set_public_key(reveal.source.value(), reveal.public_key.clone())?;
     result!(Applied)
@@ -96,4 +106,31 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_reveal_inconsistent_hash() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+
+        let address = "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU";
+        // Public key of tz1Ng3bkhPwf6byrSWzBeBRTuaiKCQXzyRUK
+        let public_key =
+            PublicKey::try_from("edpktvzfDT9BVRGxGmd4XR5qNELdvQD25iviUbKaj1U8ZdNj1GwJRV").unwrap();
+
+        context.set_balance(address, Mutez::from(1000000000u32))?;
+
+        let reveal = Reveal {
+            source: address.try_into()?,
+            counter: 1u32.into(),
+            fee: 1000u32.into(),
+            gas_limit: 0u32.into(),
+            storage_limit: 0u32.into(),
+            public_key,
+        };
+
+        let res = execute_reveal(&mut context, &reveal, false)?;
+        assert!(!res.ok());
+        assert!(context.get_public_key(address)?.is_none());
+
+        Ok(())
+    }
 }
@@ -2,6 +2,7 @@
 //
 // SPDX-License-Identifier: MIT
 
+use michelson_vm::Error as InterpreterError;
 use tezos_core::types::{mutez::Mutez, number::Nat};
 pub use tezos_rpc::models::error::RpcError;
 
@@ -38,6 +39,24 @@ impl RpcErrors {
         })
     }
 
+    pub fn branch_refused(&mut self, branch: &str) {
+        self.errors.push(RpcError {
+            kind: "branch".into(),
+            id: "validate.operation.branch_refused".into(),
+            message: Some(format!("Unknown branch {}", branch)),
+            ..DEFAULT_ERROR
+        })
+    }
+
+    pub fn outdated_operation(&mut self, branch: &str) {
+        self.errors.push(RpcError {
+            kind: "outdated".into(),
+            id: "validate.operation.outdated".into(),
+            message: Some(format!("Branch {} is older than operation TTL", branch)),
+            ..DEFAULT_ERROR
+        })
+    }
+
     pub fn inconsistent_sources(&mut self) {
         self.errors.push(RpcError {
             kind: "permanent".into(),
@@ -156,6 +175,115 @@ impl RpcErrors {
         })
     }
 
+    pub fn too_many_internal_operations(&mut self, contract: &str, limit: usize) {
+        self.errors.push(RpcError {
+            kind: "permanent".into(),
+            id: "too_many_internal_operations".into(),
+            contract: Some(contract.into()),
+            message: Some(format!(
+                "Internal operation depth limit ({}) exceeded",
+                limit
+            )),
+            ..DEFAULT_ERROR
+        })
+    }
+
+    pub fn micheline_too_large(&mut self, message: String) {
+        self.errors.push(RpcError {
+            kind: "permanent".into(),
+            id: "validate.operation.micheline_too_large".into(),
+            message: Some(message),
+            ..DEFAULT_ERROR
+        })
+    }
+
+    pub fn bad_contract_parameter(&mut self, contract: &str) {
+        self.errors.push(RpcError {
+            kind: "permanent".into(),
+            id: "michelson_v1.bad_contract_parameter".into(),
+            contract: Some(contract.into()),
+            ..DEFAULT_ERROR
+        })
+    }
+
+    pub fn full_outbox(&mut self, contract: &str, limit: i32) {
+        self.errors.push(RpcError {
+            kind: "temporary".into(),
+            id: "smart_rollup.full_outbox".into(),
+            contract: Some(contract.into()),
+            message: Some(format!(
+                "Outbox messages limit per level ({}) reached",
+                limit
+            )),
+            ..DEFAULT_ERROR
+        })
+    }
+
+    pub fn stale_oracle_value(&mut self, oracle: &str, key: &str) {
+        self.errors.push(RpcError {
+            kind: "temporary".into(),
+            id: "oracle.stale_value".into(),
+            contract: Some(oracle.into()),
+            message: Some(format!("No fresh value for {}", key)),
+            ..DEFAULT_ERROR
+        })
+    }
+
+    pub fn sponsorship_refused(&mut self, sponsor: &str, message: String) {
+        self.errors.push(RpcError {
+            kind: "permanent".into(),
+            id: "sponsor.sponsorship_refused".into(),
+            contract: Some(sponsor.into()),
+            message: Some(message),
+            ..DEFAULT_ERROR
+        })
+    }
+
+    pub fn sponsor_budget_exceeded(&mut self, sponsor: &str, remaining: &Mutez) {
+        self.errors.push(RpcError {
+            kind: "temporary".into(),
+            id: "sponsor.daily_budget_exceeded".into(),
+            contract: Some(sponsor.into()),
+            balance: Some(remaining.to_string()),
+            ..DEFAULT_ERROR
+        })
+    }
+
+    pub fn operation_quota_exceeded(&mut self, contract: &str, limit: u32, levels: i32) {
+        self.errors.push(RpcError {
+            kind: "temporary".into(),
+            id: "anti_spam.operation_quota_exceeded".into(),
+            contract: Some(contract.into()),
+            message: Some(format!(
+                "Operation quota ({} per {} levels) reached",
+                limit, levels
+            )),
+            ..DEFAULT_ERROR
+        })
+    }
+
+    pub fn fee_too_low(&mut self, minimal: &Mutez, fee: &Mutez) {
+        self.errors.push(RpcError {
+            kind: "temporary".into(),
+            id: "anti_spam.fee_too_low".into(),
+            amount: Some(fee.to_string()),
+            message: Some(format!("Minimal fee is {}", minimal)),
+            ..DEFAULT_ERROR
+        })
+    }
+
+    pub fn insufficient_proof_of_work(&mut self, zero_bits: u32) {
+        self.errors.push(RpcError {
+            kind: "temporary".into(),
+            id: "anti_spam.insufficient_proof_of_work".into(),
+            message: Some(format!(
+                "Operation hash must start with {} zero bits",
+                zero_bits
+            )),
+            ..DEFAULT_ERROR
+        })
+    }
+
     pub fn runtime_error(&mut self, contract: &str, message: String) {
         self.errors.push(RpcError {
             kind: "temporary".into(),
@@ -165,6 +293,31 @@ impl RpcErrors {
             ..DEFAULT_ERROR
         })
     }
+
+    pub fn tez_error(&mut self, error: &InterpreterError) {
+        let (id, message) = match error {
+            InterpreterError::MutezAdditionOverflow { a, b } => {
+                ("tez.addition_overflow", format!("{} + {}", a, b))
+            }
+            InterpreterError::MutezSubtractionUnderflow { a, b } => {
+                ("tez.subtraction_underflow", format!("{} - {}", a, b))
+            }
+            InterpreterError::MutezMultiplicationOverflow { a, b } => {
+                ("tez.multiplication_overflow", format!("{} * {}", a, b))
+            }
+            // Same as L1 for out of range mutez literals
+            InterpreterError::MutezOutOfRange { value } => {
+                ("michelson_v1.invalid_constant", value.to_string())
+            }
+            _ => return,
+        };
+        self.errors.push(RpcError {
+            kind: "temporary".into(),
+            id: id.into(),
+            message: Some(message),
+            ..DEFAULT_ERROR
+        })
+    }
 }
 
 impl Into<Option<Vec<RpcError>>> for RpcErrors {
@@ -0,0 +1,580 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use michelson_vm::{
+    entrypoints::search_entrypoint, interpreter::InterpreterContext, script::MichelsonScript,
+    types::StackItem,
+};
+use tezos_core::types::{
+    encoded::{Address, ContractAddress, Encoded},
+    mutez::Mutez,
+};
+use tezos_michelson::micheline::{primitive_application::PrimitiveApplication, Micheline};
+use tezos_operation::operations::Transaction;
+
+use crate::{
+    context::{
+        scheduler::{ScheduledCall, ScheduledCallReceipt},
+        TezosContext,
+    },
+    executor::{balance_updates::BalanceUpdates, transaction::execute_transaction},
+    Error, Result,
+};
+
+/// Entrypoint of the scheduler registering a call, the transferred amount is the prepaid budget
+pub const SCHEDULE_ENTRYPOINT: &str = "schedule";
+/// Entrypoint of the scheduler cancelling a call (by id) and refunding the remaining budget
+pub const CANCEL_ENTRYPOINT: &str = "cancel";
+
+pub(crate) fn prim(name: &str, args: Vec<Micheline>, field: Option<&str>) -> Micheline {
+    let args = match args.is_empty() {
+        true => None,
+        false => Some(args),
+    };
+    PrimitiveApplication::new(
+        name.into(),
+        args,
+        field.map(|field| vec![format!("%{}", field)]),
+    )
+    .into()
+}
+
+/// Parameter type of the scheduler:
+/// `or (pair %schedule (address %destination) (string %entrypoint) (bytes %parameter)
+///     (nat %level) (nat %period) (mutez %fee) (nat %retries)) (nat %cancel)`
+pub fn scheduler_parameter_type() -> Micheline {
+    let mut schedule = prim("nat", vec![], Some("retries"));
+    for (ty, field) in [
+        ("mutez", "fee"),
+        ("nat", "period"),
+        ("nat", "level"),
+        ("bytes", "parameter"),
+        ("string", "entrypoint"),
+    ] {
+        schedule = prim("pair", vec![prim(ty, vec![], Some(field)), schedule], None);
+    }
+    let schedule = prim(
+        "pair",
+        vec![prim("address", vec![], Some("destination")), schedule],
+        Some(SCHEDULE_ENTRYPOINT),
+    );
+    prim(
+        "or",
+        vec![schedule, prim("nat", vec![], Some(CANCEL_ENTRYPOINT))],
+        None,
+    )
+}
+
+fn into_nat(item: Option<StackItem>) -> Result<usize> {
+    match item {
+        Some(StackItem::Nat(nat)) => nat.try_into().map_err(|_| Error::InvalidSchedulerParameter),
+        _ => Err(Error::InvalidSchedulerParameter),
+    }
+}
+
+fn into_i32(item: Option<StackItem>) -> Result<i32> {
+    i32::try_from(into_nat(item)?).map_err(|_| Error::InvalidSchedulerParameter)
+}
+
+/// Handles a call of the scheduler system contract, returns the id of the scheduled call.
+/// The owner of the scheduled call is the sender of the transaction (contract or account).
+pub fn execute_scheduler_call(
+    context: &mut (impl TezosContext + InterpreterContext),
+    transaction: &Transaction,
+    scheduler: &ContractAddress,
+    owner: Address,
+    payer: &str,
+    balance_updates: &mut BalanceUpdates,
+) -> Result<u64> {
+    let params = match &transaction.parameters {
+        Some(params) => params,
+        None => return Err(Error::InvalidSchedulerParameter),
+    };
+    let entrypoint = params.entrypoint.to_str();
+    let ty = search_entrypoint(scheduler_parameter_type().try_into()?, Some(entrypoint), 0)
+        .map_err(|_| Error::InvalidSchedulerParameter)?;
+    let item = StackItem::from_micheline(params.value.clone(), &ty)
+        .map_err(|_| Error::InvalidSchedulerParameter)?;
+
+    match entrypoint {
+        SCHEDULE_ENTRYPOINT => schedule_call(
+            context,
+            transaction,
+            scheduler,
+            owner,
+            payer,
+            item,
+            balance_updates,
+        ),
+        CANCEL_ENTRYPOINT => cancel_call(
+            context,
+            transaction,
+            scheduler,
+            owner,
+            item,
+            balance_updates,
+        ),
+        _ => Err(Error::InvalidSchedulerParameter),
+    }
+}
+
+/// Registers the call and locks the prepaid budget on the scheduler balance.
+/// The parameter is packed (`PACK`) and has to typecheck against the destination entrypoint.
+fn schedule_call(
+    context: &mut (impl TezosContext + InterpreterContext),
+    transaction: &Transaction,
+    scheduler: &ContractAddress,
+    owner: Address,
+    payer: &str,
+    item: StackItem,
+    balance_updates: &mut BalanceUpdates,
+) -> Result<u64> {
+    let mut items = match item {
+        StackItem::Pair(pair) => pair.into_items(7)?.into_iter(),
+        _ => return Err(Error::InvalidSchedulerParameter),
+    };
+
+    let destination = match items.next() {
+        Some(StackItem::Address(address)) => match address.unwrap() {
+            Address::Originated(kt) => ContractAddress::try_from(kt.contract_hash())?,
+            Address::Implicit(_) => return Err(Error::InvalidSchedulerParameter),
+        },
+        _ => return Err(Error::InvalidSchedulerParameter),
+    };
+    let entrypoint = match items.next() {
+        Some(StackItem::String(entrypoint)) => entrypoint.unwrap(),
+        _ => return Err(Error::InvalidSchedulerParameter),
+    };
+    let packed = match items.next() {
+        Some(StackItem::Bytes(bytes)) => bytes.unwrap(),
+        _ => return Err(Error::InvalidSchedulerParameter),
+    };
+    let level = into_i32(items.next())?;
+    let period = into_i32(items.next())?;
+    let fee: Mutez = match items.next() {
+        Some(StackItem::Mutez(fee)) => fee.try_into()?,
+        _ => return Err(Error::InvalidSchedulerParameter),
+    };
+    let retries =
+        u32::try_from(into_nat(items.next())?).map_err(|_| Error::InvalidSchedulerParameter)?;
+
+    // Cheap calls would flood the queue, the budget has to cover at least one execution
+    let min_fee = Mutez::try_from(context.get_config()?.rollup_limits.min_scheduled_fee)?;
+    if fee < min_fee || fee == 0u32.into() || transaction.amount < fee {
+        return Err(Error::InvalidSchedulerParameter);
+    }
+
+    let code = context
+        .get_contract_code(destination.value())?
+        .ok_or(Error::InvalidSchedulerParameter)?;
+    let ty = search_entrypoint(
+        MichelsonScript::try_from(code)?.get_type(),
+        Some(entrypoint.as_str()),
+        0,
+    )
+    .map_err(|_| Error::InvalidSchedulerParameter)?;
+    let parameters = StackItem::from_bytes(packed, &ty)
+        .and_then(|item| item.into_micheline(&ty))
+        .map_err(|_| Error::InvalidSchedulerParameter)?;
+
+    balance_updates.transfer(context, payer, scheduler.value(), &transaction.amount)?;
+
+    let fee: i64 = fee.try_into()?;
+    let budget: i64 = transaction.amount.try_into()?;
+    let call = ScheduledCall {
+        owner,
+        source: transaction.source.clone(),
+        destination,
+        entrypoint,
+        parameters,
+        // The head is the previous block, calls due at the current one are already executed
+        level: level.max(context.get_head()?.level + 2),
+        period,
+        fee: fee as u64,
+        budget: budget as u64,
+        retries,
+        failures: 0,
+    };
+
+    let id = context.get_scheduled_count()?;
+    context.set_scheduled_count(id + 1)?;
+    queue_call(context, id, call.level)?;
+    context.set_scheduled_call(id, Some(call))?;
+    Ok(id)
+}
+
+/// Appends the call to the queue of the level it is due at
+fn queue_call(context: &mut impl TezosContext, id: u64, level: i32) -> Result<()> {
+    let mut queue = context.get_scheduled_queue(level)?;
+    queue.push(id);
+    context.set_scheduled_queue(level, queue)
+}
+
+/// Cancels the call, only the owner can do that
+fn cancel_call(
+    context: &mut impl TezosContext,
+    transaction: &Transaction,
+    scheduler: &ContractAddress,
+    owner: Address,
+    item: StackItem,
+    balance_updates: &mut BalanceUpdates,
+) -> Result<u64> {
+    let id = into_nat(Some(item))? as u64;
+    let call = match context.get_scheduled_call(id)? {
+        Some(call) if call.owner.value() == owner.value() => call,
+        _ => return Err(Error::InvalidSchedulerParameter),
+    };
+    if transaction.amount != 0u32.into() {
+        return Err(Error::InvalidSchedulerParameter);
+    }
+
+    close_call(context, scheduler, id, &call, balance_updates)?;
+
+    let mut queue = context.get_scheduled_queue(call.level)?;
+    queue.retain(|queued| *queued != id);
+    context.set_scheduled_queue(call.level, queue)?;
+    Ok(id)
+}
+
+/// Removes the call and refunds the remaining budget to the owner
+fn close_call(
+    context: &mut impl TezosContext,
+    scheduler: &ContractAddress,
+    id: u64,
+    call: &ScheduledCall,
+    balance_updates: &mut BalanceUpdates,
+) -> Result<()> {
+    let budget = Mutez::try_from(call.budget)?;
+    balance_updates.transfer(context, scheduler.value(), call.owner.value(), &budget)?;
+    context.set_scheduled_call(id, None)
+}
+
+/// Executes the calls due at the given level, should be done before user operations.
+/// At most `max_scheduled_calls` are executed, the rest is postponed to the next level
+/// ahead of the calls due there. Every execution, either applied or failed, is paid
+/// from the prepaid budget.
+/// Failed calls are retried at the next level until the registered number of retries
+/// is exceeded, calls that are completed, cancelled or out of budget are removed.
+/// Returns the fees collected for the sequencer.
+pub fn execute_scheduled_calls(
+    context: &mut (impl TezosContext + InterpreterContext),
+    level: i32,
+) -> Result<Mutez> {
+    context.check_no_pending_changes()?;
+
+    let scheduler = match context.get_scheduler()? {
+        Some(scheduler) => scheduler,
+        None => return Ok(0u32.into()),
+    };
+
+    let mut due = context.get_scheduled_queue(level)?;
+    if due.is_empty() {
+        return Ok(0u32.into());
+    }
+    context.set_scheduled_queue(level, Vec::new())?;
+
+    let limit = context.get_config()?.rollup_limits.max_scheduled_calls;
+    let postponed = due.split_off(due.len().min(limit));
+    if !postponed.is_empty() {
+        for id in postponed.iter() {
+            if let Some(mut call) = context.get_scheduled_call(*id)? {
+                call.level = level + 1;
+                context.set_scheduled_call(*id, Some(call))?;
+            }
+        }
+        let queue = [postponed, context.get_scheduled_queue(level + 1)?].concat();
+        context.set_scheduled_queue(level + 1, queue)?;
+    }
+    context.commit()?;
+
+    let mut collected_fees: Mutez = 0u32.into();
+    let mut receipts: Vec<ScheduledCallReceipt> = Vec::new();
+
+    for id in due.into_iter() {
+        let mut call = match context.get_scheduled_call(id)? {
+            Some(call) => call,
+            None => continue,
+        };
+
+        // Sent on behalf of the owner, the transaction is processed like a top-level one.
+        // Script failures end up in the receipt, internal errors abort the batch.
+        let result = execute_transaction(
+            context,
+            &call.transaction()?,
+            Some(call.owner.clone()),
+            0,
+            false,
+        )?;
+        let errors = match result.ok() {
+            true => Vec::new(),
+            false => {
+                context.rollback();
+                result.errors()
+            }
+        };
+
+        let fee = Mutez::try_from(call.fee)?;
+        BalanceUpdates::reserve(context, scheduler.value(), &fee)?;
+        collected_fees += fee;
+        call.budget -= call.fee;
+
+        let applied = errors.is_empty();
+        call.failures = match applied {
+            true => 0,
+            false => call.failures + 1,
+        };
+        let next_level = match applied {
+            true if call.period > 0 => Some(level + call.period),
+            false if call.failures <= call.retries => Some(level + 1),
+            _ => None,
+        }
+        .filter(|_| call.budget >= call.fee);
+
+        if !applied {
+            context.log(format!(
+                "Scheduled call {} failed: {}",
+                id,
+                errors.join("\n")
+            ));
+        }
+
+        receipts.push(ScheduledCallReceipt {
+            id,
+            destination: call.destination.clone(),
+            entrypoint: call.entrypoint.clone(),
+            applied,
+            errors,
+            fee: call.fee,
+            next_level,
+        });
+
+        match next_level {
+            Some(next_level) => {
+                call.level = next_level;
+                context.set_scheduled_call(id, Some(call))?;
+                queue_call(context, id, next_level)?;
+            }
+            None => close_call(context, &scheduler, id, &call, &mut BalanceUpdates::new())?,
+        }
+        context.commit()?;
+    }
+
+    if !receipts.is_empty() {
+        context.set_scheduled_receipts(level, receipts)?;
+    }
+    context.commit()?;
+    Ok(collected_fees)
+}
+
+#[cfg(test)]
+mod test {
+    use tezos_operation::operations::{Entrypoint, Parameters};
+
+    use super::*;
+    use crate::{
+        batcher::apply_batch,
+        context::{head::Head, TezosEphemeralContext},
+    };
+
+    // parameter nat; storage nat; code { UNPAIR; ADD; NIL operation; PAIR }
+    const COUNTER_CODE: &str = r#"[
+        {"prim":"parameter","args":[{"prim":"nat"}]},
+        {"prim":"storage","args":[{"prim":"nat"}]},
+        {"prim":"code","args":[[
+            {"prim":"UNPAIR"},
+            {"prim":"ADD"},
+            {"prim":"NIL","args":[{"prim":"operation"}]},
+            {"prim":"PAIR"}]]}
+    ]"#;
+
+    #[test]
+    fn test_scheduled_calls() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+
+        let source = "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU";
+        let scheduler = ContractAddress::try_from("KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm")?;
+        let counter = "KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi";
+        let code: Micheline = serde_json::from_str(COUNTER_CODE).unwrap();
+        let storage: Micheline = serde_json::from_str(r#"{"int":"0"}"#).unwrap();
+        context.set_scheduler(scheduler.clone())?;
+        context.set_balance(source, Mutez::from(100000u32))?;
+        context.set_contract_code(counter, code)?;
+        context.set_contract_storage(counter, storage)?;
+        context.commit()?;
+
+        // Pair counter "default" (PACK 5) 0 (period 2) (fee 1000) (retries 0)
+        let value: Micheline = serde_json::from_str(
+            r#"{"prim":"Pair","args":[{"string":"KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi"},
+                {"prim":"Pair","args":[{"string":"default"},
+                {"prim":"Pair","args":[{"bytes":"050005"},
+                {"prim":"Pair","args":[{"int":"0"},
+                {"prim":"Pair","args":[{"int":"2"},
+                {"prim":"Pair","args":[{"int":"1000"},{"int":"0"}]}]}]}]}]}]}"#,
+        )
+        .unwrap();
+        let transaction = Transaction {
+            source: source.try_into()?,
+            counter: 1u32.into(),
+            fee: 0u32.into(),
+            gas_limit: 0u32.into(),
+            storage_limit: 0u32.into(),
+            amount: 2500u32.into(),
+            destination: scheduler.clone().into(),
+            parameters: Some(Parameters {
+                entrypoint: Entrypoint::from_str(SCHEDULE_ENTRYPOINT),
+                value,
+            }),
+        };
+        assert!(execute_transaction(&mut context, &transaction, None, 0, false)?.ok());
+        context.commit()?;
+        // Head is at -1, so the earliest level is the one after the block being applied
+        assert_eq!(vec![0u64], context.get_scheduled_queue(1)?);
+        assert_eq!(
+            Some(Mutez::from(2500u32)),
+            context.get_balance(scheduler.value())?
+        );
+
+        assert_eq!(
+            Mutez::from(1000u32),
+            execute_scheduled_calls(&mut context, 1)?
+        );
+        assert_eq!(
+            Some(serde_json::from_str(r#"{"int":"5"}"#).unwrap()),
+            context.get_contract_storage(counter)?
+        );
+        assert_eq!(Some(3), context.get_scheduled_receipts(1)?[0].next_level);
+        assert_eq!(vec![0u64], context.get_scheduled_queue(3)?);
+
+        // Not due yet
+        assert_eq!(Mutez::from(0u32), execute_scheduled_calls(&mut context, 2)?);
+
+        // Remaining budget does not cover another execution, the call is over
+        assert_eq!(
+            Mutez::from(1000u32),
+            execute_scheduled_calls(&mut context, 3)?
+        );
+        assert_eq!(None, context.get_scheduled_receipts(3)?[0].next_level);
+        assert!(context.get_scheduled_queue(3)?.is_empty());
+        assert!(context.get_scheduled_queue(5)?.is_empty());
+        assert_eq!(None, context.get_scheduled_call(0)?);
+        assert_eq!(Some(Mutez::from(98000u32)), context.get_balance(source)?);
+        assert_eq!(
+            Some(Mutez::from(0u32)),
+            context.get_balance(scheduler.value())?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_scheduled_in_batch() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+        let head = apply_batch(&mut context, Head::default(), vec![], false)?;
+
+        let source = "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU";
+        let scheduler = ContractAddress::try_from("KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm")?;
+        let counter = "KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi";
+        let code: Micheline = serde_json::from_str(COUNTER_CODE).unwrap();
+        let storage: Micheline = serde_json::from_str(r#"{"int":"0"}"#).unwrap();
+        context.set_scheduler(scheduler.clone())?;
+        context.set_batcher(source.try_into()?)?;
+        context.set_balance(source, Mutez::from(100000u32))?;
+        context.set_contract_code(counter, code)?;
+        context.set_contract_storage(counter, storage)?;
+        context.commit()?;
+
+        // Pair counter "default" (PACK 5) 0 (period 0) (fee 1000) (retries 0)
+        let value: Micheline = serde_json::from_str(
+            r#"{"prim":"Pair","args":[{"string":"KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi"},
+                {"prim":"Pair","args":[{"string":"default"},
+                {"prim":"Pair","args":[{"bytes":"050005"},
+                {"prim":"Pair","args":[{"int":"0"},
+                {"prim":"Pair","args":[{"int":"0"},
+                {"prim":"Pair","args":[{"int":"1000"},{"int":"0"}]}]}]}]}]}]}"#,
+        )
+        .unwrap();
+        let transaction = Transaction {
+            source: source.try_into()?,
+            counter: 1u32.into(),
+            fee: 0u32.into(),
+            gas_limit: 0u32.into(),
+            storage_limit: 0u32.into(),
+            amount: 1000u32.into(),
+            destination: scheduler.clone().into(),
+            parameters: Some(Parameters {
+                entrypoint: Entrypoint::from_str(SCHEDULE_ENTRYPOINT),
+                value,
+            }),
+        };
+        // Applied in the block following the head, the call is due at the next one
+        assert!(execute_transaction(&mut context, &transaction, None, 0, false)?.ok());
+        context.commit()?;
+        assert_eq!(vec![0u64], context.get_scheduled_queue(head.level + 2)?);
+
+        let head = apply_batch(&mut context, head, vec![], false)?;
+        let head = apply_batch(&mut context, head, vec![], false)?;
+        assert_eq!(1, context.get_scheduled_receipts(head.level)?.len());
+        assert_eq!(
+            Some(serde_json::from_str(r#"{"int":"5"}"#).unwrap()),
+            context.get_contract_storage(counter)?
+        );
+        assert_eq!(None, context.get_scheduled_call(0)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_postponed_calls() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+
+        let source = "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU";
+        let scheduler = ContractAddress::try_from("KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm")?;
+        let counter = "KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi";
+        let code: Micheline = serde_json::from_str(COUNTER_CODE).unwrap();
+        let storage: Micheline = serde_json::from_str(r#"{"int":"0"}"#).unwrap();
+        context.set_scheduler(scheduler.clone())?;
+        context.set_balance(scheduler.value(), Mutez::from(2000u32))?;
+        context.set_contract_code(counter, code)?;
+        context.set_contract_storage(counter, storage)?;
+
+        let mut config = context.get_config()?;
+        config.rollup_limits.max_scheduled_calls = 1;
+        context.set_config(config)?;
+
+        let call = ScheduledCall {
+            owner: source.try_into()?,
+            source: source.try_into()?,
+            destination: counter.try_into()?,
+            entrypoint: "default".into(),
+            parameters: serde_json::from_str(r#"{"int":"1"}"#).unwrap(),
+            level: 0,
+            period: 0,
+            fee: 1000,
+            budget: 1000,
+            retries: 0,
+            failures: 0,
+        };
+        context.set_scheduled_call(0, Some(call.clone()))?;
+        context.set_scheduled_call(1, Some(call))?;
+        context.set_scheduled_queue(0, vec![0, 1])?;
+        context.set_scheduled_queue(1, vec![2])?;
+        context.commit()?;
+
+        // The second call does not fit and goes ahead of the ones due at the next level
+        assert_eq!(
+            Mutez::from(1000u32),
+            execute_scheduled_calls(&mut context, 0)?
+        );
+        assert_eq!(1, context.get_scheduled_receipts(0)?.len());
+        assert!(context.get_scheduled_queue(0)?.is_empty());
+        assert_eq!(vec![1u64, 2], context.get_scheduled_queue(1)?);
+        assert_eq!(1, context.get_scheduled_call(1)?.unwrap().level);
+
+        assert_eq!(
+            Mutez::from(1000u32),
+            execute_scheduled_calls(&mut context, 1)?
+        );
+        assert_eq!(1, context.get_scheduled_receipts(1)?[0].id);
+        assert_eq!(vec![2u64], context.get_scheduled_queue(2)?);
+        Ok(())
+    }
+}
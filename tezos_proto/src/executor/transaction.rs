@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: MIT
 
 use michelson_vm::interpreter::InterpreterContext;
-use tezos_core::types::encoded::{Address, Encoded};
+use tezos_core::types::encoded::{Address, ContractAddress, Encoded};
 use tezos_michelson::micheline::Micheline;
 use tezos_operation::operations::{OperationContent, Transaction};
 use tezos_rpc::models::operation::{
@@ -16,8 +16,12 @@ use crate::{
     executor::balance_updates::BalanceUpdates,
     executor::contract::{execute_contract, expand_content, ContractOutput},
     executor::lazy_diff::LazyDiff,
+    executor::oracle::execute_oracle_request,
+    executor::outbox::execute_l1_call,
     executor::result::ExecutionResult,
     executor::rpc_errors::RpcErrors,
+    executor::scheduler::execute_scheduler_call,
+    executor::withdrawal::execute_withdrawal,
     Error, Result,
 };
 
@@ -25,6 +29,7 @@ pub fn execute_transaction(
     context: &mut (impl TezosContext + InterpreterContext),
     transaction: &Transaction,
     sender: Option<Address>,
+    depth: usize,
     skip: bool,
 ) -> Result<ExecutionResult> {
     let mut errors = RpcErrors::new();
@@ -62,15 +67,139 @@ pub fn execute_transaction(
         return result!(Skipped);
     }
 
+    let max_depth = context
+        .get_config()?
+        .rollup_limits
+        .max_internal_operation_depth;
+    if depth > max_depth {
+        errors.too_many_internal_operations(transaction.source.value(), max_depth);
+        return result!(Failed);
+    }
+
+    // Top-level transactions with a sender are sent on behalf of a contract wallet
+    // (or the owner of a scheduled call)
+    let payer = match (&sender, depth) {
+        (Some(wallet), 0) => wallet.value().to_string(),
+        _ => transaction.source.value().to_string(),
+    };
+
+    // Transfers to allowlisted bridges leave the rollup
+    if let Address::Originated(bridge) = &transaction.destination {
+        if context.is_bridge(bridge.value())? {
+            match execute_withdrawal(
+                context,
+                transaction,
+                &payer,
+                bridge.clone(),
+                &mut balance_updates,
+            ) {
+                Ok(_) => return result!(Applied),
+                Err(Error::BalanceTooLow { balance }) => {
+                    errors.balance_too_low(&transaction.amount, &balance, &payer);
+                    return result!(Failed);
+                }
+                Err(Error::InvalidWithdrawalParameter) => {
+                    errors.bad_contract_parameter(bridge.value());
+                    return result!(Failed);
+                }
+                Err(Error::OutboxLimitExceeded { limit }) => {
+                    errors.full_outbox(bridge.value(), limit);
+                    return result!(Failed);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    // Calls to declared L1 contracts are forwarded to the outbox
+    if let Address::Originated(proxy) = &transaction.destination {
+        if let Some(parameter_type) = context.get_l1_contract(proxy.contract_hash())? {
+            let destination = ContractAddress::try_from(proxy.contract_hash())?;
+            match execute_l1_call(context, transaction, destination, parameter_type) {
+                Ok(_) => return result!(Applied),
+                Err(Error::InvalidL1CallParameter) => {
+                    errors.bad_contract_parameter(proxy.value());
+                    return result!(Failed);
+                }
+                Err(Error::OutboxLimitExceeded { limit }) => {
+                    errors.full_outbox(proxy.value(), limit);
+                    return result!(Failed);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    // Calls of the scheduler register or cancel calls executed by the kernel
+    if let Address::Originated(scheduler) = &transaction.destination {
+        if let Some(address) = context.get_scheduler()? {
+            if address.contract_hash() == scheduler.contract_hash() {
+                let owner = sender.clone().unwrap_or(transaction.source.clone().into());
+                match execute_scheduler_call(
+                    context,
+                    transaction,
+                    &address,
+                    owner,
+                    &payer,
+                    &mut balance_updates,
+                ) {
+                    Ok(_) => return result!(Applied),
+                    Err(Error::BalanceTooLow { balance }) => {
+                        errors.balance_too_low(&transaction.amount, &balance, &payer);
+                        return result!(Failed);
+                    }
+                    Err(Error::InvalidSchedulerParameter) => {
+                        errors.bad_contract_parameter(scheduler.value());
+                        return result!(Failed);
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+    }
+
+    // Calls of the oracle are answered with a callback carrying the feed value
+    if let Address::Originated(oracle) = &transaction.destination {
+        if let Some(address) = context.get_oracle()? {
+            if address.contract_hash() == oracle.contract_hash() {
+                let callback = match execute_oracle_request(context, transaction) {
+                    Ok(callback) => callback,
+                    Err(Error::InvalidOracleParameter) => {
+                        errors.bad_contract_parameter(oracle.value());
+                        return result!(Failed);
+                    }
+                    Err(Error::StaleOracleValue { key }) => {
+                        errors.stale_oracle_value(oracle.value(), &key);
+                        return result!(Failed);
+                    }
+                    Err(err) => return Err(err),
+                };
+                let res = execute_transaction(
+                    context,
+                    &callback,
+                    Some(transaction.destination.clone()),
+                    depth + 1,
+                    false,
+                )?;
+                let applied = res.ok();
+                internal_results.push(res);
+                return match applied {
+                    true => result!(Applied),
+                    false => result!(Backtracked),
+                };
+            }
+        }
+    }
+
     let balance = match balance_updates.transfer(
         context,
-        transaction.source.value(),
+        &payer,
         transaction.destination.value(),
         &transaction.amount,
     ) {
         Ok((_, balance)) => balance,
         Err(Error::BalanceTooLow { balance }) => {
-            errors.balance_too_low(&transaction.amount, &balance, transaction.source.value());
+            errors.balance_too_low(&transaction.amount, &balance, &payer);
             return result!(Failed);
         }
         Err(err) => return Err(err),
@@ -89,6 +218,7 @@ pub fn execute_transaction(
             }
             Ok(ContractOutput::Error(err)) => {
                 errors.runtime_error(transaction.destination.value(), err.format());
+                errors.tez_error(&err);
                 return result!(Failed);
             }
             Err(err) => return Err(err),
@@ -101,6 +231,7 @@ pub fn execute_transaction(
                     context,
                     &tx,
                     Some(transaction.destination.clone()),
+                    depth + 1,
                     false,
                 ) {
                     Ok(res) => {
@@ -124,10 +255,10 @@ pub fn execute_transaction(
 #[cfg(test)]
 mod test {
     use tezos_core::types::mutez::Mutez;
-    use tezos_operation::operations::Transaction;
+    use tezos_operation::operations::{Entrypoint, Parameters, Transaction};
 
     use super::*;
-    use crate::{context::TezosEphemeralContext, Result};
+    use crate::{context::TezosEphemeralContext, validator::wallet::wallet_alias, Result};
 
     #[test]
     fn test_transaction_applied() -> Result<()> {
@@ -149,7 +280,7 @@ mod test {
             parameters: None,
         };
 
-        let res = execute_transaction(&mut context, &transaction, None, false);
+        let res = execute_transaction(&mut context, &transaction, None, 0, false);
         assert!(res.is_ok());
         assert!(res.unwrap().ok());
 
@@ -164,4 +295,80 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_stored_interpreter_limits() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+
+        let source = "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU";
+        let destination = "KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi";
+        // parameter nat; storage nat; code { UNPAIR; ADD; NIL operation; PAIR }
+        let code: Micheline = serde_json::from_str(
+            r#"[{"prim":"parameter","args":[{"prim":"nat"}]},
+                {"prim":"storage","args":[{"prim":"nat"}]},
+                {"prim":"code","args":[[{"prim":"UNPAIR"},{"prim":"ADD"},
+                    {"prim":"NIL","args":[{"prim":"operation"}]},{"prim":"PAIR"}]]}]"#,
+        )
+        .unwrap();
+        context.set_balance(source, Mutez::from(1000u32))?;
+        context.set_contract_code(destination, code)?;
+        context
+            .set_contract_storage(destination, serde_json::from_str(r#"{"int":"0"}"#).unwrap())?;
+
+        let transaction = Transaction {
+            source: source.try_into()?,
+            counter: 1u32.into(),
+            fee: 0u32.into(),
+            gas_limit: 0u32.into(),
+            storage_limit: 0u32.into(),
+            amount: 0u32.into(),
+            destination: destination.try_into()?,
+            parameters: Some(Parameters {
+                entrypoint: Entrypoint::from_str("default"),
+                value: serde_json::from_str(r#"{"int":"1"}"#).unwrap(),
+            }),
+        };
+        assert!(execute_transaction(&mut context, &transaction, None, 0, false)?.ok());
+
+        let mut config = context.get_config()?;
+        config.rollup_limits.max_stack_size = 1;
+        context.set_config(config)?;
+        assert!(!execute_transaction(&mut context, &transaction, None, 0, false)?.ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_wallet_withdrawal() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+
+        let wallet = ContractAddress::try_from("KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm")?;
+        let bridge = "KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi";
+        let alias = wallet_alias(&wallet)?;
+        context.set_balance(wallet.value(), Mutez::from(1000u32))?;
+        context.set_bridge(bridge.try_into()?)?;
+
+        let transaction = Transaction {
+            source: alias.clone(),
+            counter: 1u32.into(),
+            fee: 0u32.into(),
+            gas_limit: 0u32.into(),
+            storage_limit: 0u32.into(),
+            amount: 100u32.into(),
+            destination: bridge.try_into()?,
+            parameters: Some(Parameters {
+                entrypoint: Entrypoint::from_str("withdraw"),
+                value: serde_json::from_str(r#"{"string":"tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU"}"#)
+                    .unwrap(),
+            }),
+        };
+        let sender = Some(Address::Originated(wallet.clone()));
+        assert!(execute_transaction(&mut context, &transaction, sender, 0, false)?.ok());
+        assert_eq!(
+            Some(Mutez::from(900u32)),
+            context.get_balance(wallet.value())?
+        );
+        assert_eq!(None, context.get_balance(alias.value())?);
+        assert_eq!(1, context.get_outbox_queue()?.len());
+        Ok(())
+    }
 }
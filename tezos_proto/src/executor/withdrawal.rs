@@ -0,0 +1,148 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use michelson_vm::types::{AddressItem, MutezItem, PairItem, StackItem, UnitItem};
+use tezos_core::types::{
+    encoded::{Address, ContractAddress, Encoded},
+    mutez::Mutez,
+};
+use tezos_michelson::{micheline::Micheline, michelson::types};
+use tezos_operation::operations::Transaction;
+
+use crate::{
+    context::{outbox::OutboxCall, TezosContext},
+    executor::{
+        balance_updates::BalanceUpdates, deposit::ticket_transfer_type, outbox::queue_outbox_call,
+    },
+    Error, Result,
+};
+
+/// Entrypoint of the bridge contract: called on L2 to burn tez, and on L1 to unlock them
+pub const WITHDRAW_ENTRYPOINT: &str = "withdraw";
+
+/// Tez sent back to L1: burned on L2 and returned as a ticket to the bridge contract
+/// once the outbox message is executed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Withdrawal {
+    /// L1 bridge contract that issued the ticket, receives the outbox call
+    pub ticketer: ContractAddress,
+    /// L1 account receiving the unlocked tez
+    pub receiver: Address,
+    pub amount: Mutez,
+}
+
+impl Withdrawal {
+    /// Parameter of the L1 bridge call, same type as the deposit one (see `ticket_transfer_type`)
+    pub fn parameters(&self) -> Result<Micheline> {
+        let item: StackItem = PairItem::from_items(vec![
+            AddressItem::new(self.receiver.clone()).into(),
+            AddressItem::new(Address::Originated(self.ticketer.clone())).into(),
+            UnitItem::from(()).into(),
+            MutezItem::try_from(self.amount)?.into(),
+        ])?
+        .into();
+        Ok(item.into_micheline(&ticket_transfer_type())?)
+    }
+
+    /// Call of the bridge `withdraw` entrypoint unlocking the tez on L1
+    pub fn outbox_call(&self) -> Result<OutboxCall> {
+        Ok(OutboxCall {
+            destination: self.ticketer.clone(),
+            entrypoint: WITHDRAW_ENTRYPOINT.into(),
+            parameters: self.parameters()?,
+        })
+    }
+}
+
+/// Burns the transferred amount from the payer (the wallet for wallet-sourced operations)
+/// and queues the withdrawal for the outbox. The transaction has to call the `withdraw` entrypoint of an allowlisted bridge
+/// with the L1 receiver address as parameter.
+pub fn execute_withdrawal(
+    context: &mut impl TezosContext,
+    transaction: &Transaction,
+    payer: &str,
+    ticketer: ContractAddress,
+    balance_updates: &mut BalanceUpdates,
+) -> Result<Withdrawal> {
+    let receiver = match &transaction.parameters {
+        Some(params) if params.entrypoint.to_str() == WITHDRAW_ENTRYPOINT => {
+            match StackItem::from_micheline(params.value.clone(), &types::address()) {
+                Ok(StackItem::Address(receiver)) => receiver.unwrap(),
+                _ => return Err(Error::InvalidWithdrawalParameter),
+            }
+        }
+        _ => return Err(Error::InvalidWithdrawalParameter),
+    };
+    // Tickets cannot be empty
+    if transaction.amount == 0u32.into() {
+        return Err(Error::InvalidWithdrawalParameter);
+    }
+
+    balance_updates.burn(context, payer, &transaction.amount)?;
+
+    let withdrawal = Withdrawal {
+        ticketer,
+        receiver,
+        amount: transaction.amount,
+    };
+    queue_outbox_call(context, withdrawal.outbox_call()?)?;
+    Ok(withdrawal)
+}
+
+#[cfg(test)]
+mod test {
+    use tezos_operation::operations::{Entrypoint, Parameters};
+
+    use super::*;
+    use crate::context::TezosEphemeralContext;
+
+    #[test]
+    fn test_withdrawal() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+
+        let source = "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU";
+        let bridge = "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm";
+        context.set_balance(source, Mutez::from(1000u32))?;
+
+        let transaction = Transaction {
+            source: source.try_into()?,
+            counter: 1u32.into(),
+            fee: 0u32.into(),
+            gas_limit: 0u32.into(),
+            storage_limit: 0u32.into(),
+            amount: 100u32.into(),
+            destination: bridge.try_into()?,
+            parameters: Some(Parameters {
+                entrypoint: Entrypoint::from_str(WITHDRAW_ENTRYPOINT),
+                value: Micheline::from_bytes(
+                    hex::decode(
+                        "0100000024747a315633644853434a6e575052647a446d5a47435a61544d7569546d627450616b6d55",
+                    )?
+                    .as_slice(),
+                )?,
+            }),
+        };
+
+        let mut balance_updates = BalanceUpdates::new();
+        let withdrawal = execute_withdrawal(
+            &mut context,
+            &transaction,
+            source,
+            bridge.try_into()?,
+            &mut balance_updates,
+        )?;
+        assert_eq!(source, withdrawal.receiver.value());
+        assert_eq!(Some(Mutez::from(900u32)), context.get_balance(source)?);
+        assert_eq!(vec![withdrawal.outbox_call()?], context.get_outbox_queue()?);
+
+        // Pair "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU" (Pair "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm" (Pair Unit 100))
+        assert_eq!(
+            "07070100000024747a315633644853434a6e575052647a446d5a47435a61544d7569546d627450616b\
+            6d55070701000000244b54314d6a6a636236746d53734c6d37436233445351737a65506a666368504d\
+            3455786d0707030b00a401",
+            hex::encode(withdrawal.parameters()?.to_bytes()?)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use tezos_core::types::encoded::{Encoded, OperationHash};
+use tezos_operation::operations::SignedOperation;
+
+use crate::{
+    context::{anti_spam::leading_zero_bits, TezosContext},
+    executor::rpc_errors::RpcErrors,
+    Result,
+};
+
+/// Checks the operation against the anti-spam policy, if any.
+/// Returns `false` and fills the errors if the operation is refused.
+pub fn check_anti_spam(
+    context: &mut impl TezosContext,
+    opg: &SignedOperation,
+    hash: &OperationHash,
+    payer: &str,
+    errors: &mut RpcErrors,
+) -> Result<bool> {
+    let policy = match context.get_anti_spam_policy()? {
+        Some(policy) => policy,
+        None => return Ok(true),
+    };
+
+    for content in opg.contents.iter() {
+        let minimal = policy.minimal_fees.get(content)?;
+        if content.fee() < minimal {
+            errors.fee_too_low(&minimal, &content.fee());
+            return Ok(false);
+        }
+    }
+
+    if policy.pow_zero_bits > 0
+        && leading_zero_bits(hash.to_bytes()?.as_slice()) < policy.pow_zero_bits
+    {
+        errors.insufficient_proof_of_work(policy.pow_zero_bits);
+        return Ok(false);
+    }
+
+    if policy.quota_operations > 0 {
+        let window = policy.quota_window(context.get_head()?.level + 1);
+        let count = context.get_operation_quota(payer)?.count_at(window);
+        if count >= policy.quota_operations {
+            errors.operation_quota_exceeded(payer, policy.quota_operations, policy.quota_levels);
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Counts the operation towards the quota of the account
+pub fn record_operation(context: &mut impl TezosContext, payer: &str) -> Result<()> {
+    if let Some(policy) = context.get_anti_spam_policy()? {
+        if policy.quota_operations > 0 {
+            let window = policy.quota_window(context.get_head()?.level + 1);
+            let quota = context.get_operation_quota(payer)?;
+            context.set_operation_quota(payer, quota.add(1, window))?;
+        }
+    }
+    Ok(())
+}
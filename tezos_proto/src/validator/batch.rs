@@ -2,30 +2,52 @@
 //
 // SPDX-License-Identifier: MIT
 
+use michelson_vm::interpreter::InterpreterContext;
 use tezos_core::types::encoded::{Encoded, OperationHash};
 use tezos_operation::operations::SignedOperation;
 
 use crate::{
     context::TezosContext,
-    validator::operation::{validate_operation, ValidOperation, ValidatedOperation},
+    validator::{
+        anti_spam::record_operation,
+        operation::{validate_operation_with_steps, ValidOperation, ValidatedOperation},
+    },
     Result,
 };
 
+/// Instructions executed by wallet validations are added to `steps`.
+/// Branches of the `forced` operations are not checked.
 pub fn validate_batch(
-    context: &mut impl TezosContext,
+    context: &mut (impl TezosContext + InterpreterContext),
     batch_payload: Vec<(OperationHash, SignedOperation)>,
+    forced: &[OperationHash],
     atomic: bool,
+    steps: &mut usize,
 ) -> Result<Vec<ValidOperation>> {
     context.check_no_pending_changes()?;
 
     let mut operations: Vec<ValidOperation> = Vec::with_capacity(batch_payload.len());
 
     for (hash, opg) in batch_payload.into_iter() {
-        match validate_operation(context, opg, hash.clone(), false) {
+        let check_branch = !forced.contains(&hash);
+        let res =
+            validate_operation_with_steps(context, opg, hash.clone(), false, check_branch, steps);
+        match res {
             Ok(ValidatedOperation::Valid(op)) => {
-                let balance = context.get_balance(&op.source.value())?.unwrap();
-                context.set_balance(&op.source.value(), balance - op.total_spent)?;
-                context.set_counter(&op.source.value(), op.last_counter.clone())?;
+                let balance = context.get_balance(op.payer())?.unwrap_or(0u32.into());
+                context.set_balance(op.payer(), balance - (op.total_spent - op.total_fees))?;
+                let balance = context.get_balance(op.fee_payer())?.unwrap_or(0u32.into());
+                context.set_balance(op.fee_payer(), balance - op.total_fees)?;
+                context.set_counter(op.payer(), op.last_counter.clone())?;
+                record_operation(context, op.payer())?;
+                if let Some(sponsor) = &op.sponsor {
+                    let timestamp = context.get_head()?.timestamp;
+                    let spending = context.get_sponsor_spending(sponsor.value())?;
+                    context.set_sponsor_spending(
+                        sponsor.value(),
+                        spending.add(&op.total_fees, timestamp)?,
+                    )?;
+                }
                 operations.push(op);
             }
             Ok(ValidatedOperation::Invalid(op)) => {
@@ -0,0 +1,35 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use tezos_operation::operations::{OperationContent, SignedOperation, Transaction};
+
+pub const BRANCH: &str = "BMNvSHmWUkdonkG2oFwwQKxHUdrYQhUXqxLaSRX9wjMGfLddURC";
+
+/// Signature of `signed_transaction` by tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU
+pub const SIGNATURE: &str =
+    "sigw1WNdYweqz1c7zKcvZFHQ18swSv4HBWje5quRmixxitPk7z8jtY63qXgKLPVfTM6XGxExPatBWJP44Bknyu3hDHDKJZgY";
+
+/// Operation group with the test branch and signature, the signature is only valid
+/// for `signed_transaction`
+pub fn signed_operation(contents: Vec<OperationContent>) -> SignedOperation {
+    SignedOperation::new(
+        BRANCH.try_into().unwrap(),
+        contents,
+        SIGNATURE.try_into().unwrap(),
+    )
+}
+
+pub fn signed_transaction(source: &str) -> SignedOperation {
+    signed_operation(vec![Transaction::new(
+        source.try_into().unwrap(),
+        417u32.into(),
+        2336132u32.into(),
+        1527u32.into(),
+        357u32.into(),
+        498719u32.into(),
+        "tz1d5Dr3gjsxQo5XNbjAj558mLy3nGGQgMFA".try_into().unwrap(),
+        None,
+    )
+    .into()])
+}
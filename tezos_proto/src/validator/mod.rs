@@ -2,5 +2,9 @@
 //
 // SPDX-License-Identifier: MIT
 
+pub mod anti_spam;
 pub mod batch;
+#[cfg(test)]
+mod fixtures;
 pub mod operation;
+pub mod wallet;
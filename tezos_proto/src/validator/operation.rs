@@ -3,16 +3,26 @@
 // SPDX-License-Identifier: MIT
 
 use derive_more::{From, TryInto};
+use michelson_vm::interpreter::InterpreterContext;
 use tezos_core::types::{
-    encoded::{Encoded, ImplicitAddress, OperationHash},
+    encoded::{ContractAddress, Encoded, ImplicitAddress, OperationHash, PublicKey},
     mutez::Mutez,
     number::Nat,
 };
+use tezos_michelson::micheline::Micheline;
 use tezos_operation::operations::{OperationContent, SignedOperation};
 
 use crate::{
+    config::TezosConfig,
     context::TezosContext,
-    executor::rpc_errors::{RpcError, RpcErrors},
+    executor::{
+        reveal::public_key_hash,
+        rpc_errors::{RpcError, RpcErrors},
+    },
+    validator::{
+        anti_spam::check_anti_spam,
+        wallet::{check_wallet_signature, get_wallet},
+    },
     Error, Result,
 };
 
@@ -27,26 +37,172 @@ pub struct ValidOperation {
     pub hash: OperationHash,
     pub origin: SignedOperation,
     pub source: ImplicitAddress,
+    /// Contract wallet the operation is sent on behalf of, `source` is its alias then
+    pub wallet: Option<ContractAddress>,
+    /// Account paying the fees instead of the source
+    pub sponsor: Option<ImplicitAddress>,
     pub total_fees: Mutez,
     pub total_spent: Mutez,
     pub last_counter: Nat,
 }
 
+impl ValidOperation {
+    /// Account paying the fees and holding the counter
+    pub fn payer(&self) -> &str {
+        match &self.wallet {
+            Some(wallet) => wallet.value(),
+            None => self.source.value(),
+        }
+    }
+
+    /// Account paying the fees: the sponsor if any, the payer otherwise
+    pub fn fee_payer(&self) -> &str {
+        match &self.sponsor {
+            Some(sponsor) => sponsor.value(),
+            None => self.payer(),
+        }
+    }
+}
+
+fn micheline_node_count(expr: Micheline) -> usize {
+    let mut count: usize = 0;
+    let mut pending = vec![expr];
+    while let Some(node) = pending.pop() {
+        count += 1;
+        match node {
+            Micheline::Literal(_) => {}
+            Micheline::PrimitiveApplication(prim) => {
+                pending.extend(prim.into_args().unwrap_or_default())
+            }
+            Micheline::Sequence(seq) => pending.extend(seq.into_values()),
+        }
+    }
+    count
+}
+
+fn micheline_payload(content: &OperationContent) -> Vec<Micheline> {
+    match content {
+        OperationContent::Transaction(transaction) => transaction
+            .parameters
+            .iter()
+            .map(|params| params.value.clone())
+            .collect(),
+        OperationContent::Origination(origination) => vec![
+            Micheline::Sequence(origination.script.code.clone()),
+            origination.script.storage.clone(),
+        ],
+        _ => vec![],
+    }
+}
+
+fn check_micheline_limits(expr: Micheline, config: &TezosConfig) -> Result<Option<String>> {
+    let size = expr.pack(None)?.len();
+    if size > config.max_micheline_bytes_limit as usize {
+        return Ok(Some(format!(
+            "Expression size {} exceeds the limit of {} bytes",
+            size, config.max_micheline_bytes_limit
+        )));
+    }
+
+    let node_count = micheline_node_count(expr);
+    if node_count > config.max_micheline_node_count as usize {
+        return Ok(Some(format!(
+            "Expression has {} nodes, exceeding the limit of {}",
+            node_count, config.max_micheline_node_count
+        )));
+    }
+
+    Ok(None)
+}
+
+fn revealed_key(opg: &SignedOperation) -> Option<PublicKey> {
+    opg.contents
+        .iter()
+        .filter_map(|content| match content {
+            OperationContent::Reveal(reveal) => Some(reveal.public_key.clone()),
+            _ => None,
+        })
+        .next()
+}
+
+/// Checks only that the branch is live and that the operation is signed by its source
+/// (or authorized by its contract wallet). Fees and counters are checked at application,
+/// this is meant for operations that are queued before being included in a block.
+/// Returns the number of instructions executed by the wallet validation along with the result.
+pub fn check_signature_and_branch(
+    context: &mut (impl TezosContext + InterpreterContext),
+    opg: &SignedOperation,
+) -> Result<(bool, usize)> {
+    let head = context.get_head()?;
+    if opg.branch != head.hash && !context.get_live_blocks()?.contains(&opg.branch) {
+        return Ok((false, 0));
+    }
+
+    let mut sources = opg.contents.iter().map(|content| match content {
+        OperationContent::Reveal(reveal) => Some(&reveal.source),
+        OperationContent::Transaction(transaction) => Some(&transaction.source),
+        OperationContent::Origination(origination) => Some(&origination.source),
+        _ => None,
+    });
+    let source = match sources.next() {
+        Some(Some(source)) => source.clone(),
+        _ => return Ok((false, 0)),
+    };
+    if !sources.all(|address| address == Some(&source)) {
+        return Ok((false, 0));
+    }
+
+    if let Some(wallet) = get_wallet(context, &source, opg)? {
+        return check_wallet_signature(context, &wallet, opg);
+    }
+
+    let public_key = match context.get_public_key(source.value())? {
+        Some(value) => value,
+        None => match revealed_key(opg) {
+            Some(key) if public_key_hash(&key)?.value() == source.value() => key,
+            _ => return Ok((false, 0)),
+        },
+    };
+    Ok((opg.verify(&public_key)?, 0))
+}
+
 pub fn validate_operation(
-    context: &mut impl TezosContext,
+    context: &mut (impl TezosContext + InterpreterContext),
     opg: SignedOperation,
     hash: OperationHash,
     dry_run: bool,
+) -> Result<ValidatedOperation> {
+    validate_operation_with_steps(context, opg, hash, dry_run, true, &mut 0)
+}
+
+/// Same as `validate_operation`, instructions executed by the wallet validation are added
+/// to `steps` whether the operation is valid or not.
+/// The branch is not checked if `check_branch` is unset, e.g. for forced delayed operations
+/// which branch was checked when they were queued.
+pub fn validate_operation_with_steps(
+    context: &mut (impl TezosContext + InterpreterContext),
+    opg: SignedOperation,
+    hash: OperationHash,
+    dry_run: bool,
+    check_branch: bool,
+    steps: &mut usize,
 ) -> Result<ValidatedOperation> {
     let mut source: Option<ImplicitAddress> = None;
     let mut total_fees: Mutez = 0u32.into();
     let mut total_spent: Mutez = 0u32.into();
     let mut errors = RpcErrors::new();
+    let config = context.get_config()?;
 
-    // TODO: validate branch?
-    // On the one hand there should be something like TTL
-    // On the other hand if even with L2 we hit issues with throughput, why bother?
-    // In order to validate branch one need to keep the entire (or rolling) history of heads [Head]
+    let head = context.get_head()?;
+    if check_branch && opg.branch != head.hash && !context.get_live_blocks()?.contains(&opg.branch)
+    {
+        if context.has_batch_receipt(opg.branch.value())? {
+            errors.outdated_operation(opg.branch.value());
+        } else {
+            errors.branch_refused(opg.branch.value());
+        }
+        return Ok(ValidatedOperation::Invalid(errors.unwrap()));
+    }
 
     for content in opg.contents.iter() {
         let (address, amount) = match content {
@@ -70,8 +226,14 @@ pub fn validate_operation(
             }
         }
 
+        for expr in micheline_payload(content) {
+            if let Some(message) = check_micheline_limits(expr, &config)? {
+                errors.micheline_too_large(message);
+                return Ok(ValidatedOperation::Invalid(errors.unwrap()));
+            }
+        }
+
         // TODO: deny 0 amount if destination is implicit
-        // TODO: check against constant fee values (per operation kind)
 
         total_fees += content.fee();
         total_spent += content.fee() + amount.unwrap_or(0u32.into());
@@ -85,53 +247,118 @@ pub fn validate_operation(
         }
     };
 
-    let public_key = match context.get_public_key(source.value())? {
-        Some(value) => value,
-        None => {
-            let revealed_key = opg
-                .contents
-                .iter()
-                .filter_map(|content| match content {
-                    OperationContent::Reveal(reveal) => Some(reveal.public_key.clone()),
-                    _ => None,
-                })
-                .next();
-
-            match revealed_key {
-                Some(key) => key,
+    // Operations of contract wallets are authorized by the wallet itself
+    let wallet = get_wallet(context, &source, &opg)?;
+    let payer = match &wallet {
+        Some(wallet) => wallet.value(),
+        None => source.value(),
+    };
+
+    // Cheap checks go before the signature and wallet validation they protect.
+    // Reported at injection as well, quotas are only counted once the operation is included
+    if !check_anti_spam(context, &opg, &hash, payer, &mut errors)? {
+        return Ok(ValidatedOperation::Invalid(errors.unwrap()));
+    }
+
+    if let Some(wallet) = &wallet {
+        if !dry_run {
+            let (authorized, spent) = check_wallet_signature(context, wallet, &opg)?;
+            *steps += spent;
+            if !authorized {
+                errors.invalid_signature();
+                return Ok(ValidatedOperation::Invalid(errors.unwrap()));
+            }
+        }
+    } else {
+        let public_key = match context.get_public_key(source.value())? {
+            Some(value) => value,
+            None => match revealed_key(&opg) {
+                Some(key) => {
+                    if public_key_hash(&key)?.value() != source.value() {
+                        errors.inconsistent_hash(source.value());
+                        return Ok(ValidatedOperation::Invalid(errors.unwrap()));
+                    }
+                    key
+                }
                 None => {
                     errors.unrevealed_key(source.value());
                     return Ok(ValidatedOperation::Invalid(errors.unwrap()));
                 }
-            }
+            },
+        };
+
+        if !dry_run {
+            match opg.verify(&public_key) {
+                Ok(true) => (),
+                Ok(false) => {
+                    errors.invalid_signature();
+                    return Ok(ValidatedOperation::Invalid(errors.unwrap()));
+                }
+                Err(err) => return Err(err.into()),
+            };
         }
-    };
+    }
 
-    if !dry_run {
-        match opg.verify(&public_key) {
-            Ok(true) => (),
-            Ok(false) => {
-                errors.invalid_signature();
+    // Fees of sponsored operations are paid by the sponsor, within its policy
+    let sponsor = context.get_sponsorship(hash.value())?;
+    if let Some(sponsor) = &sponsor {
+        let policy = match context.get_sponsor_policy(sponsor.value())? {
+            Some(policy) => policy,
+            None => {
+                errors.sponsorship_refused(sponsor.value(), "Account is not a sponsor".into());
                 return Ok(ValidatedOperation::Invalid(errors.unwrap()));
             }
-            Err(err) => return Err(err.into()),
         };
+
+        let refused = opg
+            .contents
+            .iter()
+            .find_map(|content| policy.refused_destination(content));
+        if let Some(destination) = refused {
+            errors.sponsorship_refused(
+                sponsor.value(),
+                format!("Destination {} is not sponsored", destination),
+            );
+            return Ok(ValidatedOperation::Invalid(errors.unwrap()));
+        }
+
+        let timestamp = context.get_head()?.timestamp;
+        let spending = context.get_sponsor_spending(sponsor.value())?;
+        if let Some(remaining) = policy.remaining_budget(&spending, timestamp)? {
+            if remaining < total_fees {
+                errors.sponsor_budget_exceeded(sponsor.value(), &remaining);
+                return Ok(ValidatedOperation::Invalid(errors.unwrap()));
+            }
+        }
+
+        let balance = context.get_balance(sponsor.value())?.unwrap_or(0u32.into());
+        if balance < total_fees {
+            errors.contract_balance_too_low(&total_fees, &balance, sponsor.value());
+            return Ok(ValidatedOperation::Invalid(errors.unwrap()));
+        }
     }
 
-    let balance = match context.get_balance(source.value())? {
+    let total_charged = match &sponsor {
+        Some(_) => total_spent - total_fees,
+        None => total_spent,
+    };
+
+    let balance = match context.get_balance(payer)? {
         Some(value) => value,
+        // Sponsored accounts do not need any balance
+        None if sponsor.is_some() => 0u32.into(),
         None => {
-            errors.empty_implicit_contract(source.value());
+            errors.empty_implicit_contract(payer);
             return Ok(ValidatedOperation::Invalid(errors.unwrap()));
         }
     };
 
-    if balance < total_spent {
-        errors.contract_balance_too_low(&total_spent, &balance, source.value());
+    if balance < total_charged {
+        errors.contract_balance_too_low(&total_charged, &balance, payer);
         return Ok(ValidatedOperation::Invalid(errors.unwrap()));
     }
 
-    let mut counter = context.get_counter(source.value())?;
+    let mut counter = context.get_counter(payer)?;
 
     for content in opg.contents.iter() {
         let next_counter = match content {
@@ -141,7 +368,7 @@ pub fn validate_operation(
             _ => return Err(Error::OperationKindUnsupported),
         };
         if *next_counter <= counter {
-            errors.counter_in_the_past(source.value(), &(counter + 1u32.into()), next_counter);
+            errors.counter_in_the_past(payer, &(counter + 1u32.into()), next_counter);
             return Ok(ValidatedOperation::Invalid(errors.unwrap()));
         }
         counter = next_counter.clone();
@@ -151,6 +378,8 @@ pub fn validate_operation(
         hash,
         origin: opg,
         source,
+        wallet,
+        sponsor,
         total_fees,
         total_spent,
         last_counter: counter,
@@ -165,14 +394,30 @@ mod test {
         mutez::Mutez,
         number::Nat,
     };
-    use tezos_operation::operations::{Reveal, SignedOperation, Transaction};
+    use tezos_operation::operations::{
+        Entrypoint, Parameters, Reveal, SignedOperation, Transaction,
+    };
 
     use super::*;
-    use crate::{context::TezosEphemeralContext, Result};
+    use crate::{
+        context::{
+            anti_spam::{AntiSpamPolicy, MinimalFees, OperationQuota},
+            head::LiveBlocks,
+            sponsor::{SponsorPolicy, SponsorSpending},
+            TezosEphemeralContext,
+        },
+        validator::fixtures::{signed_operation, signed_transaction, BRANCH},
+        Result,
+    };
+
+    fn live_blocks(branch: &str) -> LiveBlocks {
+        LiveBlocks(vec![branch.try_into().unwrap()].into())
+    }
 
     #[test]
     fn test_valid_tx() -> Result<()> {
         let mut context = TezosEphemeralContext::default();
+        context.set_live_blocks(live_blocks(BRANCH))?;
 
         let address = "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU";
         context.set_balance(address, Mutez::from(1000000000u32))?;
@@ -183,25 +428,7 @@ mod test {
         )?;
         context.commit()?;
 
-        let opg = SignedOperation::new(
-            "BMNvSHmWUkdonkG2oFwwQKxHUdrYQhUXqxLaSRX9wjMGfLddURC"
-                .try_into()
-                .unwrap(),
-            vec![Transaction::new(
-                address.clone().try_into()?,
-                417u32.into(),
-                2336132u32.into(),
-                1527u32.into(),
-                357u32.into(),
-                498719u32.into(),
-                "tz1d5Dr3gjsxQo5XNbjAj558mLy3nGGQgMFA".try_into().unwrap(),
-                None,
-            )
-            .into()],
-            "sigw1WNdYweqz1c7zKcvZFHQ18swSv4HBWje5quRmixxitPk7z8jtY63qXgKLPVfTM6XGxExPatBWJP44Bknyu3hDHDKJZgY"
-                .try_into()
-                .unwrap(),
-        );
+        let opg = signed_transaction(address);
 
         let hash = opg.hash()?;
         let op: ValidOperation = validate_operation(&mut context, opg, hash, false)?.try_into()?;
@@ -211,9 +438,199 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_sponsored_tx() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+        context.set_live_blocks(live_blocks(BRANCH))?;
+
+        let address = "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU";
+        let sponsor = "tz1NEgotHhj4fkm8AcwquQqQBrQsAMRUg86c";
+        // Covers the amount, but not the fees
+        context.set_balance(address, Mutez::from(498719u32))?;
+        context.set_counter(address, Nat::try_from("100000").unwrap())?;
+        context.set_public_key(
+            address,
+            PublicKey::try_from("edpktipCJ3SkjvtdcrwELhvupnyYJSmqoXu3kdzK1vL6fT5cY8FTEa").unwrap(),
+        )?;
+        context.set_balance(sponsor, Mutez::from(1000u32))?;
+        context.set_sponsor_policy(
+            sponsor,
+            SponsorPolicy {
+                destinations: vec!["tz1d5Dr3gjsxQo5XNbjAj558mLy3nGGQgMFA".into()],
+                daily_budget: Some(500),
+            },
+        )?;
+        context.commit()?;
+
+        let opg = signed_transaction(address);
+        let hash = opg.hash()?;
+
+        let res = validate_operation(&mut context, opg.clone(), hash.clone(), false)?;
+        assert!(matches!(res, ValidatedOperation::Invalid(_)));
+
+        context.set_sponsorship(hash.value(), Some(sponsor.try_into()?))?;
+        context.commit()?;
+        let op: ValidOperation =
+            validate_operation(&mut context, opg.clone(), hash.clone(), false)?.try_into()?;
+        assert_eq!(sponsor, op.fee_payer());
+        assert_eq!(address, op.payer());
+
+        context.set_sponsor_spending(
+            sponsor,
+            SponsorSpending {
+                period: 0,
+                spent: 100,
+            },
+        )?;
+        context.commit()?;
+        match validate_operation(&mut context, opg, hash, false)? {
+            ValidatedOperation::Invalid(errors) => {
+                assert_eq!("sponsor.daily_budget_exceeded", errors[0].id)
+            }
+            ValidatedOperation::Valid(_) => panic!("Expected the sponsor budget to be exceeded"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_anti_spam() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+        context.set_live_blocks(live_blocks(BRANCH))?;
+
+        let address = "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU";
+        context.set_balance(address, Mutez::from(1000000000u32))?;
+        context.set_counter(address, Nat::try_from("100000").unwrap())?;
+        context.set_public_key(
+            address,
+            PublicKey::try_from("edpktipCJ3SkjvtdcrwELhvupnyYJSmqoXu3kdzK1vL6fT5cY8FTEa").unwrap(),
+        )?;
+        context.set_anti_spam_policy(AntiSpamPolicy {
+            minimal_fees: MinimalFees {
+                transaction: 1000,
+                ..Default::default()
+            },
+            ..Default::default()
+        })?;
+        context.commit()?;
+
+        let opg = signed_transaction(address);
+        let hash = opg.hash()?;
+
+        macro_rules! assert_refused {
+            ($id: expr) => {
+                match validate_operation(&mut context, opg.clone(), hash.clone(), false)? {
+                    ValidatedOperation::Invalid(errors) => assert_eq!($id, errors[0].id),
+                    ValidatedOperation::Valid(_) => panic!("Expected {}", $id),
+                }
+            };
+        }
+
+        assert_refused!("anti_spam.fee_too_low");
+        // Checked before the key and the signature of the source
+        let unrevealed = signed_transaction("tz1Ne4yzDRQPd5HFz6sTaCYCNHwFubT2MWsB");
+        let unrevealed_hash = unrevealed.hash()?;
+        match validate_operation(&mut context, unrevealed, unrevealed_hash, false)? {
+            ValidatedOperation::Invalid(errors) => {
+                assert_eq!("anti_spam.fee_too_low", errors[0].id)
+            }
+            ValidatedOperation::Valid(_) => panic!("Expected anti_spam.fee_too_low"),
+        }
+        // Injection runs the same checks
+        match validate_operation(&mut context, opg.clone(), hash.clone(), true)? {
+            ValidatedOperation::Invalid(errors) => {
+                assert_eq!("anti_spam.fee_too_low", errors[0].id)
+            }
+            ValidatedOperation::Valid(_) => panic!("Expected anti_spam.fee_too_low"),
+        }
+
+        let policy = AntiSpamPolicy {
+            quota_operations: 1,
+            quota_levels: 10,
+            ..Default::default()
+        };
+        context.set_anti_spam_policy(policy.clone())?;
+        context.commit()?;
+        let op: ValidOperation =
+            validate_operation(&mut context, opg.clone(), hash.clone(), false)?.try_into()?;
+        assert_eq!(op.payer(), address);
+
+        let window = policy.quota_window(context.get_head()?.level + 1);
+        context.set_operation_quota(address, OperationQuota::default().add(1, window))?;
+        context.commit()?;
+        assert_refused!("anti_spam.operation_quota_exceeded");
+
+        context.set_anti_spam_policy(AntiSpamPolicy {
+            pow_zero_bits: 64,
+            ..Default::default()
+        })?;
+        context.commit()?;
+        assert_refused!("anti_spam.insufficient_proof_of_work");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_branch() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+
+        let opg = signed_operation(vec![]);
+
+        let hash = opg.hash()?;
+        match validate_operation(&mut context, opg, hash, true)? {
+            ValidatedOperation::Invalid(errors) => {
+                assert_eq!("validate.operation.branch_refused", errors[0].id)
+            }
+            ValidatedOperation::Valid(_) => panic!("Expected branch to be refused"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_micheline_too_large() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+        context.set_live_blocks(live_blocks(BRANCH))?;
+
+        let address = "tz1V3dHSCJnWPRdzDmZGCZaTMuiTmbtPakmU";
+        context.set_balance(address, Mutez::from(1000000000u32))?;
+        context.set_public_key(
+            address,
+            PublicKey::try_from("edpktipCJ3SkjvtdcrwELhvupnyYJSmqoXu3kdzK1vL6fT5cY8FTEa").unwrap(),
+        )?;
+        context.commit()?;
+
+        let items = vec![r#"{"int": "1"}"#; 50001].join(",");
+        let parameter: Micheline = serde_json::from_str(&format!("[{}]", items)).unwrap();
+
+        let opg = signed_operation(vec![Transaction::new(
+            address.try_into()?,
+            417u32.into(),
+            1u32.into(),
+            1527u32.into(),
+            357u32.into(),
+            0u32.into(),
+            "KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm".try_into().unwrap(),
+            Some(Parameters {
+                entrypoint: Entrypoint::from_str("default"),
+                value: parameter,
+            }),
+        )
+        .into()]);
+
+        let hash = opg.hash()?;
+        let res = validate_operation(&mut context, opg, hash, true)?;
+        assert!(matches!(res, ValidatedOperation::Invalid(_)));
+
+        Ok(())
+    }
+
     #[test]
     fn test_reveal_and_tx_batch() -> Result<()> {
         let mut context = TezosEphemeralContext::default();
+        context.set_live_blocks(live_blocks(
+            "BMY9L2Nq2wTiHbS3UD8zncaKrbjpD3JdUvyf28ViJYadwpDKLBz",
+        ))?;
 
         let address = ImplicitAddress::try_from("tz1Ng3bkhPwf6byrSWzBeBRTuaiKCQXzyRUK").unwrap();
         context.set_balance(&address.value(), Mutez::from(1000000000u32))?;
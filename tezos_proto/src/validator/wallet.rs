@@ -0,0 +1,261 @@
+// SPDX-FileCopyrightText: 2023 Baking Bad <hello@bakingbad.dev>
+//
+// SPDX-License-Identifier: MIT
+
+use michelson_vm::{
+    interpreter::{InterpreterContext, OperationScope},
+    script::MichelsonScript,
+    stack::Stack,
+    types::{BytesItem, PairItem, SignatureItem, StackItem},
+};
+use tezos_core::types::encoded::{ContractAddress, Encoded, ImplicitAddress};
+use tezos_michelson::michelson::types;
+use tezos_operation::operations::{OperationContent, SignedOperation};
+
+use crate::{context::TezosContext, executor::contract::block_timestamp, Result};
+
+/// Entrypoint called to authorize operations on behalf of a contract wallet
+pub const VALIDATE_ENTRYPOINT: &str = "validate";
+
+/// Watermark prepended to the forged operation before signing (generic operation)
+const OPERATION_WATERMARK: u8 = 3;
+
+/// Implicit (tz1) address sharing the hash of the wallet contract, used as the `source`
+/// of operations signed on behalf of the wallet. No key can correspond to it.
+pub fn wallet_alias(wallet: &ContractAddress) -> Result<ImplicitAddress> {
+    let mut bytes = wallet.to_bytes()?;
+    bytes.insert(0, 0);
+    Ok(ImplicitAddress::from_bytes(bytes.as_slice())?)
+}
+
+/// Returns the contract wallet the operation is sent from, if the source is a wallet alias.
+/// Only transactions can be sent on behalf of a wallet.
+pub fn get_wallet(
+    context: &mut impl TezosContext,
+    source: &ImplicitAddress,
+    opg: &SignedOperation,
+) -> Result<Option<ContractAddress>> {
+    let bytes = source.to_bytes()?;
+    if bytes[0] != 0 || context.get_public_key(source.value())?.is_some() {
+        return Ok(None);
+    }
+    if !opg
+        .contents
+        .iter()
+        .all(|content| matches!(content, OperationContent::Transaction(_)))
+    {
+        return Ok(None);
+    }
+
+    let wallet = ContractAddress::from_bytes(&bytes[1..])?;
+    match context.get_contract_code(wallet.value())? {
+        Some(_) => Ok(Some(wallet)),
+        None => Ok(None),
+    }
+}
+
+/// Calls `%validate` of the wallet with the signed bytes (watermark and forged operation)
+/// and the signature. The operation is authorized if the call neither fails nor emits
+/// operations, the wallet storage is left intact.
+/// Returns the number of instructions executed along with the result, the call fails
+/// once the `max_steps` limit is reached.
+pub fn check_wallet_signature(
+    context: &mut (impl TezosContext + InterpreterContext),
+    wallet: &ContractAddress,
+    opg: &SignedOperation,
+) -> Result<(bool, usize)> {
+    let code = match context.get_contract_code(wallet.value())? {
+        Some(code) => code,
+        None => return Ok((false, 0)),
+    };
+    let storage = match context.get_contract_storage(wallet.value())? {
+        Some(storage) => storage,
+        None => return Ok((false, 0)),
+    };
+
+    let payload = [vec![OPERATION_WATERMARK], opg.to_forged_bytes()?].concat();
+    let item: StackItem = PairItem::from_items(vec![
+        BytesItem::from(payload).into(),
+        SignatureItem::from(opg.signature.clone()).into(),
+    ])?
+    .into();
+    let parameter = item.into_micheline(&types::pair(vec![types::bytes(), types::signature()]))?;
+
+    let head = context.get_head()?;
+    let now = block_timestamp(context, &head)?;
+    let script = MichelsonScript::try_from(code)?;
+    let alias = wallet_alias(wallet)?;
+
+    let scope = OperationScope {
+        amount: 0u32.into(),
+        balance: context.get_balance(wallet.value())?.unwrap_or(0u32.into()),
+        chain_id: head.chain_id,
+        level: head.level + 1,
+        now,
+        parameters: Some((VALIDATE_ENTRYPOINT.into(), parameter)),
+        self_address: wallet.clone(),
+        self_type: script.get_type(),
+        sender: alias.clone().into(),
+        source: alias,
+        storage,
+        limits: context.get_config()?.interpreter_limits(),
+    };
+
+    let mut stack = Stack::with_limits(&scope.limits);
+    match script.call_with_stack(&mut stack, &scope, context) {
+        Ok(ret) => Ok((ret.operations.is_empty(), stack.steps())),
+        Err(err @ michelson_vm::Error::Internal(_)) => Err(err.into()),
+        // Script failures (FAILWITH, step limit and other runtime errors) deny the operation
+        Err(_) => Ok((false, stack.steps())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tezos_core::types::{mutez::Mutez, number::Nat};
+    use tezos_michelson::micheline::Micheline;
+    use tezos_operation::operations::Transaction;
+
+    use super::*;
+    use crate::{
+        context::{head::LiveBlocks, TezosEphemeralContext},
+        executor::operation::execute_operation,
+        validator::{
+            fixtures::{signed_operation, BRANCH},
+            operation::{
+                validate_operation, validate_operation_with_steps, ValidOperation,
+                ValidatedOperation,
+            },
+        },
+    };
+
+    // parameter (or (pair %validate bytes signature) (unit %default)); storage bool;
+    // code { UNPAIR; IF_LEFT { DROP; DUP; IF {} { PUSH string "Denied"; FAILWITH } } { DROP };
+    //        NIL operation; PAIR }
+    const WALLET_CODE: &str = r#"[
+        {"prim":"parameter","args":[{"prim":"or","args":[
+            {"prim":"pair","args":[{"prim":"bytes"},{"prim":"signature"}],"annots":["%validate"]},
+            {"prim":"unit","annots":["%default"]}]}]},
+        {"prim":"storage","args":[{"prim":"bool"}]},
+        {"prim":"code","args":[[
+            {"prim":"UNPAIR"},
+            {"prim":"IF_LEFT","args":[
+                [{"prim":"DROP"},{"prim":"DUP"},{"prim":"IF","args":[[],[
+                    {"prim":"PUSH","args":[{"prim":"string"},{"string":"Denied"}]},
+                    {"prim":"FAILWITH"}]]}],
+                [{"prim":"DROP"}]]},
+            {"prim":"NIL","args":[{"prim":"operation"}]},
+            {"prim":"PAIR"}]]}
+    ]"#;
+
+    #[test]
+    fn test_wallet_operation() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+        context.set_live_blocks(LiveBlocks(vec![BRANCH.try_into()?].into()))?;
+
+        let wallet = ContractAddress::try_from("KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm")?;
+        let code: Micheline = serde_json::from_str(WALLET_CODE).unwrap();
+        let storage: Micheline = serde_json::from_str(r#"{"prim":"True"}"#).unwrap();
+        context.set_contract_code(wallet.value(), code)?;
+        context.set_contract_storage(wallet.value(), storage)?;
+        context.set_balance(wallet.value(), Mutez::from(10000u32))?;
+        context.set_counter(wallet.value(), Nat::try_from("1").unwrap())?;
+        context.commit()?;
+
+        let alias = wallet_alias(&wallet)?;
+        let destination = "tz1NEgotHhj4fkm8AcwquQqQBrQsAMRUg86c";
+        // Wallets check their own signatures, the fixture one does not matter
+        let opg = signed_operation(vec![Transaction::new(
+            alias.clone(),
+            1000u32.into(),
+            2u32.into(),
+            0u32.into(),
+            0u32.into(),
+            5000u32.into(),
+            destination.try_into()?,
+            None,
+        )
+        .into()]);
+        let hash = opg.hash()?;
+
+        let op: ValidOperation =
+            validate_operation(&mut context, opg.clone(), hash.clone(), false)?.try_into()?;
+        assert_eq!(Some(wallet.clone()), op.wallet);
+        assert_eq!(wallet.value(), op.payer());
+        context.rollback();
+
+        let receipt = execute_operation(&mut context, &op)?;
+        assert_eq!(1, receipt.contents.len());
+        assert_eq!(
+            Mutez::from(4000u32),
+            context.get_balance(wallet.value())?.unwrap()
+        );
+        assert_eq!(
+            Mutez::from(5000u32),
+            context.get_balance(destination)?.unwrap()
+        );
+        assert_eq!(context.get_counter(wallet.value())?, 2u32.into());
+
+        // Denied by the wallet
+        let storage: Micheline = serde_json::from_str(r#"{"prim":"False"}"#).unwrap();
+        context.set_contract_storage(wallet.value(), storage)?;
+        context.set_counter(wallet.value(), Nat::try_from("1").unwrap())?;
+        context.commit()?;
+        match validate_operation(&mut context, opg, hash, false)? {
+            ValidatedOperation::Invalid(errors) => {
+                assert_eq!("operation.invalid_signature", errors[0].id)
+            }
+            ValidatedOperation::Valid(_) => panic!("Expected the wallet to deny the operation"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_wallet_step_limit() -> Result<()> {
+        let mut context = TezosEphemeralContext::default();
+        context.set_live_blocks(LiveBlocks(vec![BRANCH.try_into()?].into()))?;
+
+        let wallet = ContractAddress::try_from("KT1Mjjcb6tmSsLm7Cb3DSQszePjfchPM4Uxm")?;
+        let code: Micheline = serde_json::from_str(WALLET_CODE).unwrap();
+        let storage: Micheline = serde_json::from_str(r#"{"prim":"True"}"#).unwrap();
+        context.set_contract_code(wallet.value(), code)?;
+        context.set_contract_storage(wallet.value(), storage)?;
+        context.set_balance(wallet.value(), Mutez::from(10000u32))?;
+        context.set_counter(wallet.value(), Nat::try_from("1").unwrap())?;
+        context.commit()?;
+
+        let opg = signed_operation(vec![Transaction::new(
+            wallet_alias(&wallet)?,
+            1000u32.into(),
+            2u32.into(),
+            0u32.into(),
+            0u32.into(),
+            5000u32.into(),
+            "tz1NEgotHhj4fkm8AcwquQqQBrQsAMRUg86c".try_into()?,
+            None,
+        )
+        .into()]);
+        let hash = opg.hash()?;
+
+        let (authorized, steps) = check_wallet_signature(&mut context, &wallet, &opg)?;
+        assert!(authorized);
+        assert!(steps > 5);
+        context.rollback();
+
+        // The wallet runs out of steps, the operation is denied but the steps are counted
+        let mut config = context.get_config()?;
+        config.rollup_limits.max_steps = 5;
+        context.set_config(config)?;
+        context.commit()?;
+
+        let mut steps: usize = 0;
+        match validate_operation_with_steps(&mut context, opg, hash, false, &mut steps)? {
+            ValidatedOperation::Invalid(errors) => {
+                assert_eq!("operation.invalid_signature", errors[0].id)
+            }
+            ValidatedOperation::Valid(_) => panic!("Expected the step limit to be exceeded"),
+        }
+        assert_eq!(5, steps);
+        Ok(())
+    }
+}